@@ -0,0 +1,48 @@
+use bevy::math::DVec2;
+use bevy_geo_tiles::{WebMercatorConversion, lonlat_to_mercator_batch, mercator_to_lonlat_batch};
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+const POINTS: usize = 1_000_000;
+
+fn lonlats() -> Vec<DVec2> {
+    (0..POINTS)
+        .map(|i| {
+            let t = i as f64 / POINTS as f64;
+            DVec2::new(t * 360.0 - 180.0, (t * 997.0).fract() * 170.0 - 85.0)
+        })
+        .collect()
+}
+
+fn batch_conversion(c: &mut Criterion) {
+    let lonlats = lonlats();
+    let mercators = lonlat_to_mercator_batch(&lonlats);
+    let mut group = c.benchmark_group("1M points");
+    group.sample_size(10);
+    group.bench_function("lonlat_to_mercator per point", |b| {
+        b.iter(|| {
+            black_box(&lonlats)
+                .iter()
+                .map(|p| p.lonlat_to_mercator())
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("lonlat_to_mercator_batch", |b| {
+        b.iter(|| lonlat_to_mercator_batch(black_box(&lonlats)))
+    });
+    group.bench_function("mercator_to_lonlat per point", |b| {
+        b.iter(|| {
+            black_box(&mercators)
+                .iter()
+                .map(|p| p.mercator_to_lonlat())
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("mercator_to_lonlat_batch", |b| {
+        b.iter(|| mercator_to_lonlat_batch(black_box(&mercators)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, batch_conversion);
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+use bevy::{math::bounding::BoundingVolume, prelude::*};
+
+use bevy_geo_tiles::{LoadingTiles, LocalOrigin, LocalOriginConversion, MapPlugin};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(MapPlugin {
+            initial_zoom: 10,
+            initial_center: (13.4064, 52.51977).into(),
+            tile_source: "https://tile.openstreetmap.org/{z}/{x}/{y}.png".to_string(),
+            ..Default::default()
+        })
+        .add_systems(Startup, spawn_status_text)
+        .add_systems(Update, (outline_loading_tiles, update_status_text))
+        .run();
+}
+
+#[derive(Component)]
+struct StatusText;
+
+fn spawn_status_text(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        },
+        StatusText,
+    ));
+}
+
+// draw a frame around every tile that is still loading
+fn outline_loading_tiles(loading: LoadingTiles, origin: Res<LocalOrigin>, mut gizmos: Gizmos) {
+    for tile in loading.iter() {
+        let bounds = tile.bounds.mercator_to_local(&origin);
+        gizmos.rect_2d(
+            bounds.center(),
+            bounds.half_size() * 2.0,
+            Color::srgb(1.0, 0.5, 0.0),
+        );
+    }
+}
+
+fn update_status_text(loading: LoadingTiles, mut text: Single<&mut Text, With<StatusText>>) {
+    text.0 = if loading.is_empty() {
+        String::new()
+    } else {
+        format!("Loading {} tiles...", loading.len())
+    };
+}
@@ -5,7 +5,7 @@ use bevy::{log::LogPlugin, prelude::*};
 use bevy_geo_tiles::shapes::polyline::{
     GeoPolyline, GeoPolylineConfig, KeepDisplayWidth, PolylineStyle,
 };
-use bevy_geo_tiles::{KeepDisplaySize, MapPlugin, MercatorCoords, WebMercatorConversion};
+use bevy_geo_tiles::{GeoMarker, MapPlugin, WebMercatorConversion};
 
 use bevy_geo_tiles::shapes::polygon::GeoPolygon;
 
@@ -27,6 +27,14 @@ fn main() {
         .run();
 }
 
+fn create_marker(mut commands: Commands) {
+    commands.spawn(GeoMarker::from_color(
+        (13.4064, 52.51977),
+        Color::srgb(1.0, 0.2, 0.2),
+        Vec2::splat(20.0),
+    ));
+}
+
 fn spawn_many_shapes(mut commands: Commands) {
     for i in 0..10_000 {
         commands.spawn((
@@ -1,6 +1,6 @@
-use bevy::{prelude::*, sprite::Anchor};
+use bevy::prelude::*;
 
-use bevy_geo_tiles::MapPlugin;
+use bevy_geo_tiles::{GeoMarker, MapPlugin};
 
 fn main() {
     App::new()
@@ -18,10 +18,5 @@ fn main() {
 fn add_marker(mut commands: Commands, asset_server: Res<AssetServer>) {
     let marker = asset_server.load("examples/marker.png");
 
-    commands.spawn((
-        Sprite::from_image(marker.clone()),
-        Anchor::BOTTOM_CENTER,
-        bevy_geo_tiles::MercatorCoords::from_latlon(52.51977, 13.4064).with_z(5.0),
-        bevy_geo_tiles::KeepDisplaySize,
-    ));
+    commands.spawn(GeoMarker::new((13.4064, 52.51977), marker));
 }
@@ -0,0 +1,93 @@
+//! Measures the frame time of a large viewport full of tiles with and without the tile atlas.
+//!
+//! Run it once with and once without the atlas and compare the printed frame times:
+//!
+//! ```sh
+//! cargo run --release --example tile_atlas
+//! cargo run --release --example tile_atlas -- --atlas
+//! ```
+//!
+//! Frames are measured once all visible tiles are loaded, so downloads and texture uploads don't count.
+//! Run it twice first, so the tiles come from the disk cache.
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    window::{PresentMode, WindowResolution},
+};
+use bevy_geo_tiles::{LoadingTiles, MapPlugin};
+
+// frames measured after all tiles are loaded
+const MEASURED_FRAMES: usize = 1000;
+// time to settle after the last tile was loaded, e.g. for the zoom fade
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+fn main() {
+    let tile_atlas = std::env::args().any(|arg| arg == "--atlas");
+    App::new()
+        .add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: format!("tile atlas: {tile_atlas}"),
+                resolution: WindowResolution::new(2560, 1440),
+                // measure the frame time, not the refresh rate of the monitor
+                present_mode: PresentMode::AutoNoVsync,
+                ..default()
+            }),
+            ..default()
+        }))
+        .add_plugins(MapPlugin {
+            // low zoom levels show the most tiles, the parent zoom level stays loaded behind them
+            initial_zoom: 7,
+            initial_center: (13.4064, 52.51977).into(),
+            tile_source: "https://tile.openstreetmap.org/{z}/{x}/{y}.png".to_string(),
+            tile_atlas,
+            ..Default::default()
+        })
+        .insert_resource(Measurement {
+            tile_atlas,
+            ..default()
+        })
+        .add_systems(Update, measure_frame_time)
+        .run();
+}
+
+#[derive(Resource, Default)]
+struct Measurement {
+    tile_atlas: bool,
+    loaded_since: Option<Duration>,
+    frame_times: Vec<Duration>,
+}
+
+fn measure_frame_time(
+    time: Res<Time<Real>>,
+    loading: LoadingTiles,
+    mut measurement: ResMut<Measurement>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    if !loading.is_empty() {
+        measurement.loaded_since = None;
+        measurement.frame_times.clear();
+        return;
+    }
+    let loaded_since = *measurement.loaded_since.get_or_insert(time.elapsed());
+    if time.elapsed() - loaded_since < SETTLE_TIME {
+        return;
+    }
+    measurement.frame_times.push(time.delta());
+    if measurement.frame_times.len() < MEASURED_FRAMES {
+        return;
+    }
+
+    let frame_times = &mut measurement.frame_times;
+    frame_times.sort();
+    let mean = frame_times.iter().sum::<Duration>() / frame_times.len() as u32;
+    let p95 = frame_times[frame_times.len() * 95 / 100];
+    info!(
+        "tile atlas {}: mean frame time {:.2} ms, 95th percentile {:.2} ms over {} frames",
+        if measurement.tile_atlas { "on" } else { "off" },
+        mean.as_secs_f64() * 1000.0,
+        p95.as_secs_f64() * 1000.0,
+        MEASURED_FRAMES,
+    );
+    exit.write(AppExit::Success);
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use bevy::{math::DVec3, prelude::*};
+
+use crate::MercatorCoords;
+
+/// Smoothly moves the [`MercatorCoords`] of an entity to `target` over `duration`, e.g. for live GPS tracking
+/// where a marker should glide to its next position instead of jumping there.
+///
+/// The interpolation is linear in mercator space and starts from the position the entity has on the first update.
+/// Inserting a new `AnimateTo` while an animation is running starts over from the current position.
+/// The component is removed once the target is reached, it has no effect on entities without [`MercatorCoords`].
+#[derive(Component, Debug, Clone)]
+pub struct AnimateTo {
+    /// Target position in mercator coordinates, the z coordinate is animated as well.
+    pub target: DVec3,
+    pub duration: Duration,
+    start: Option<DVec3>,
+    elapsed: Duration,
+}
+
+impl AnimateTo {
+    pub fn new(target: DVec3, duration: Duration) -> Self {
+        Self {
+            target,
+            duration,
+            start: None,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+pub(crate) fn animate_mercator_coords(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut AnimateTo, &mut MercatorCoords)>,
+) {
+    for (entity, mut animation, mut coords) in query.iter_mut() {
+        let start = *animation.start.get_or_insert(coords.0);
+        animation.elapsed += time.delta();
+        if animation.elapsed >= animation.duration {
+            coords.0 = animation.target;
+            commands.entity(entity).remove::<AnimateTo>();
+            continue;
+        }
+        let t = animation.elapsed.as_secs_f64() / animation.duration.as_secs_f64();
+        coords.0 = start.lerp(animation.target, t);
+    }
+}
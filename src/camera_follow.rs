@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+
+use crate::{LocalOrigin, MainCam, MercatorCoords, coords_to_local};
+
+/// Makes the [`MainCam`] smoothly follow an entity with [`MercatorCoords`], e.g. a vehicle or GPS position marker.
+///
+/// Insert it on the camera entity and remove it to stop following. While it is present, it takes precedence over
+/// panning the camera manually. It is removed automatically once the target is despawned or loses its [`MercatorCoords`].
+#[derive(Component, Debug, Clone, Copy)]
+pub struct CameraFollow {
+    /// The entity to follow.
+    pub target: Entity,
+    /// How quickly the camera catches up with the target (exponential decay rate per second).
+    /// Higher values follow more tightly.
+    pub lerp: f32,
+}
+
+impl CameraFollow {
+    pub fn new(target: Entity) -> Self {
+        Self { target, lerp: 5.0 }
+    }
+}
+
+pub(crate) fn follow_target(
+    mut commands: Commands,
+    time: Res<Time>,
+    origin: Res<LocalOrigin>,
+    mut cameras: Query<(Entity, &CameraFollow, &mut Transform), With<MainCam>>,
+    targets: Query<&MercatorCoords>,
+) {
+    for (entity, follow, mut transform) in cameras.iter_mut() {
+        let Ok(coords) = targets.get(follow.target) else {
+            debug!(
+                "camera follow target {} is gone, stop following",
+                follow.target
+            );
+            commands.entity(entity).remove::<CameraFollow>();
+            continue;
+        };
+        let target = coords_to_local(coords, false, &origin).truncate();
+        let mut position = transform.translation.truncate();
+        position.smooth_nudge(&target, follow.lerp, time.delta_secs());
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
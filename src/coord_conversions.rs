@@ -1,16 +1,28 @@
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+
+#[cfg(feature = "render")]
+use bevy::ecs::system::SystemParam;
 use bevy::{
-    ecs::system::SystemParam,
     math::{DVec2, DVec3, bounding::Aabb2d},
     prelude::*,
+    tasks::{ComputeTaskPool, ParallelSlice, TaskPool},
 };
 use miniproj::Projection;
 use miniproj_ops::popvis_pseudo_mercator::PopVisPseudoMercatorProjection;
 use tilemath::{BBox, Tile as TileMathTile};
 
-use crate::local_origin::{LocalOrigin, MercatorAabb2d, TileBounds};
-use crate::local_origin_conversions::LocalOriginConversion;
+use crate::local_origin::{MercatorAabb2d, TileBounds};
+#[cfg(feature = "render")]
+use crate::{MainCam, local_origin::LocalOrigin, local_origin_conversions::LocalOriginConversion};
+
+pub(crate) const WEB_MERCATOR_EXTENT: f64 = 20037508.342789244;
+
+/// Maximum latitude in degrees representable in Web Mercator, the projection diverges towards the poles.
+pub const MAX_MERCATOR_LATITUDE: f64 = 85.051_128_779_806_59;
 
-const WEB_MERCATOR_EXTENT: f64 = 20037508.342789244;
+// upper bound of horizontally repeated world copies considered for a single view
+#[cfg(feature = "render")]
+const MAX_WORLD_COPIES: i32 = 8;
 
 // Inlined miniproj::get_projection(3857).unwrap()
 const WEB_MERCATOR: PopVisPseudoMercatorProjection = PopVisPseudoMercatorProjection {
@@ -19,12 +31,14 @@ const WEB_MERCATOR: PopVisPseudoMercatorProjection = PopVisPseudoMercatorProject
     false_e: 0f64,
     false_n: 0f64,
 };
+#[cfg(feature = "render")]
 #[derive(SystemParam)]
 pub struct ViewportConv<'w, 's, MainCamMarker: Component> {
     camera: Single<'w, 's, (&'static Camera, &'static GlobalTransform), With<MainCamMarker>>,
     origin: Res<'w, LocalOrigin>,
 }
 
+#[cfg(feature = "render")]
 impl<'w, 's, MainCamMarker: Component> ViewportConv<'w, 's, MainCamMarker> {
     pub fn viewport_to_mercator_2d(&self, viewport_pos: Vec2) -> Result<DVec2> {
         let local = self
@@ -50,34 +64,115 @@ impl<'w, 's, MainCamMarker: Component> ViewportConv<'w, 's, MainCamMarker> {
         self.mercator_to_viewport(latlon.extend(0.0).lonlat_to_mercator())
     }
 
+    /// Bounding box of the visible area in mercator coordinates.
+    ///
+    /// If the camera is rotated (see [`MapBearing`](crate::MapBearing)) this is the axis-aligned box around the
+    /// rotated viewport, so it covers more than what is actually visible.
+    ///
+    /// Fails if the camera has no viewport yet, e.g. during startup or while the window is minimized.
     pub fn visible_mercator_aabb(&self) -> Result<MercatorAabb2d> {
-        if let Some(viewport) = self.camera.0.logical_viewport_rect() {
-            let local_bounds = Aabb2d::from_point_cloud(
-                Isometry2d::IDENTITY,
-                &[
-                    self.camera
-                        .0
-                        .viewport_to_world_2d(self.camera.1, viewport.max)?,
-                    self.camera
-                        .0
-                        .viewport_to_world_2d(self.camera.1, viewport.min)?,
-                ],
-            );
-            Ok(local_bounds.local_to_mercator(&self.origin))
-        } else {
-            todo!()
+        let Some(viewport) = self.camera.0.logical_viewport_rect() else {
+            return Err("camera has no viewport".into());
+        };
+        let corners = [
+            viewport.min,
+            Vec2::new(viewport.max.x, viewport.min.y),
+            viewport.max,
+            Vec2::new(viewport.min.x, viewport.max.y),
+        ];
+        let mut world_corners = [Vec2::ZERO; 4];
+        for (world, corner) in world_corners.iter_mut().zip(corners) {
+            *world = self.camera.0.viewport_to_world_2d(self.camera.1, corner)?;
         }
+        let local_bounds = Aabb2d::from_point_cloud(Isometry2d::IDENTITY, &world_corners);
+        Ok(local_bounds.local_to_mercator(&self.origin))
     }
 
     pub fn viewport_center_mercator(&self) -> Result<DVec2> {
         Ok(self.visible_mercator_aabb()?.center())
     }
+
+    /// Ground distance in meters covered by one logical pixel at the center of the viewport.
+    ///
+    /// Mercator stretches distances by `1 / cos(latitude)`, so the value varies with latitude:
+    /// at the same zoom level a pixel covers less ground towards the poles than at the equator.
+    pub fn meters_per_pixel(&self) -> Result<f64> {
+        let Some(viewport) = self.camera.0.logical_viewport_rect() else {
+            return Err("camera has no viewport".into());
+        };
+        let center = viewport.center();
+        let a = self.viewport_to_mercator_2d(center)?;
+        let b = self.viewport_to_mercator_2d(center + Vec2::X)?;
+        let latitude = a.mercator_to_lonlat().y.to_radians();
+        Ok(a.distance(b) * latitude.cos())
+    }
+}
+
+/// Converts between screen, local Bevy world, mercator and lon/lat coordinates,
+/// using the current [`LocalOrigin`] and the [`MainCam`].
+///
+/// Screen positions are logical viewport coordinates of the main camera, e.g. a cursor position.
+/// Conversions involving the screen fail if the main camera is missing.
+#[cfg(feature = "render")]
+#[derive(SystemParam)]
+pub struct GeoConverter<'w, 's> {
+    camera: Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<MainCam>>,
+    origin: Res<'w, LocalOrigin>,
+}
+
+#[cfg(feature = "render")]
+impl<'w, 's> GeoConverter<'w, 's> {
+    pub fn screen_to_local(&self, screen: Vec2) -> Result<Vec2> {
+        let (camera, transform) = self.camera.single()?;
+        Ok(camera.viewport_to_world_2d(transform, screen)?)
+    }
+
+    pub fn local_to_screen(&self, local: Vec2) -> Result<Vec2> {
+        let (camera, transform) = self.camera.single()?;
+        Ok(camera.world_to_viewport(transform, local.extend(0.0))?)
+    }
+
+    pub fn screen_to_mercator(&self, screen: Vec2) -> Result<DVec2> {
+        Ok(self.local_to_mercator(self.screen_to_local(screen)?))
+    }
+
+    pub fn mercator_to_screen(&self, mercator: DVec2) -> Result<Vec2> {
+        self.local_to_screen(self.mercator_to_local(mercator))
+    }
+
+    pub fn screen_to_lonlat(&self, screen: Vec2) -> Result<DVec2> {
+        Ok(self.screen_to_mercator(screen)?.mercator_to_lonlat())
+    }
+
+    pub fn lonlat_to_screen(&self, lonlat: DVec2) -> Result<Vec2> {
+        self.mercator_to_screen(lonlat.lonlat_to_mercator())
+    }
+
+    pub fn local_to_mercator(&self, local: Vec2) -> DVec2 {
+        local.local_to_mercator(&self.origin)
+    }
+
+    pub fn mercator_to_local(&self, mercator: DVec2) -> Vec2 {
+        mercator.mercator_to_local(&self.origin).as_vec2()
+    }
+
+    pub fn local_to_lonlat(&self, local: Vec2) -> DVec2 {
+        self.local_to_mercator(local).mercator_to_lonlat()
+    }
+
+    pub fn lonlat_to_local(&self, lonlat: DVec2) -> Vec2 {
+        self.mercator_to_local(lonlat.lonlat_to_mercator())
+    }
 }
 
 pub trait WebMercatorConversion {
     type Output;
     fn mercator_to_lonlat(&self) -> Self;
+    /// Converts lon/lat in degrees to Web Mercator.
+    ///
+    /// Latitudes are clamped to ±[`MAX_MERCATOR_LATITUDE`] so out-of-range input (e.g. the poles) results in finite coordinates.
     fn lonlat_to_mercator(&self) -> Self::Output;
+    /// Same as [`lonlat_to_mercator`](Self::lonlat_to_mercator) with latitude and longitude swapped.
     fn latlon_to_mercator(&self) -> Self::Output;
 }
 
@@ -88,7 +183,8 @@ impl WebMercatorConversion for DVec2 {
     }
 
     fn lonlat_to_mercator(&self) -> Self::Output {
-        DVec2::from(WEB_MERCATOR.deg_to_projected(self.x, self.y))
+        let lat = self.y.clamp(-MAX_MERCATOR_LATITUDE, MAX_MERCATOR_LATITUDE);
+        DVec2::from(WEB_MERCATOR.deg_to_projected(self.x, lat))
     }
 
     fn latlon_to_mercator(&self) -> Self::Output {
@@ -193,13 +289,16 @@ pub trait ToTileCoords {
 impl ToTileCoords for DVec2 {
     type Output = UVec2;
 
+    /// The x coordinate wraps around the antimeridian, the y coordinate is clamped to the valid range.
     fn mercator_to_tile_coords(&self, zoom: u8) -> Self::Output {
-        let scale = (1 << zoom) as f64;
-        let limit = 2u32.pow(zoom as u32) - 1;
+        let tiles = 1i64 << zoom;
 
         let norm = (self + WEB_MERCATOR_EXTENT) / (2. * WEB_MERCATOR_EXTENT);
-        let scaled = (norm * scale).floor().as_uvec2();
-        UVec2::new(scaled.x.clamp(0, limit), scaled.y % (limit + 1))
+        let scaled = (norm * tiles as f64).floor();
+        UVec2::new(
+            (scaled.x as i64).rem_euclid(tiles) as u32,
+            (scaled.y as i64).clamp(0, tiles - 1) as u32,
+        )
     }
 
     fn lonlat_to_tile_coords(&self, zoom: u8) -> Self::Output {
@@ -221,11 +320,18 @@ impl ToTileCoords for Vec2 {
 impl ToTileCoords for MercatorAabb2d {
     type Output = TileBounds;
 
+    /// Tile coordinates are clamped to a single world and do not wrap around the antimeridian.
     fn mercator_to_tile_coords(&self, zoom: u8) -> Self::Output {
         let max = self.max;
         let min = self.min;
         let scale = (1 << zoom) as f64;
         let limit = 2u32.pow(zoom as u32) - 1;
+        let norm_min = (min + WEB_MERCATOR_EXTENT) / (2.0 * WEB_MERCATOR_EXTENT);
+        let min_coords = (norm_min * scale)
+            .floor()
+            .max(DVec2::ZERO)
+            .as_uvec2()
+            .min(UVec2::splat(limit));
         let norm_max = (max + WEB_MERCATOR_EXTENT) / (2.0 * WEB_MERCATOR_EXTENT);
         let tile_max = norm_max * scale;
         let max_coords = (tile_max.ceil() - 1.0)
@@ -234,7 +340,7 @@ impl ToTileCoords for MercatorAabb2d {
             .min(UVec2::splat(limit));
 
         TileBounds {
-            min: min.mercator_to_tile_coords(zoom),
+            min: min_coords,
             max: max_coords,
         }
     }
@@ -244,6 +350,40 @@ impl ToTileCoords for MercatorAabb2d {
     }
 }
 
+/// Splits a bounding box that may extend beyond the antimeridian into parts per world copy.
+///
+/// Returns the index of the world copy (0 is the main world, 1 the copy to the east, -1 to the west)
+/// and the part of the bounding box inside that copy, shifted into the main world.
+#[cfg(feature = "render")]
+pub(crate) fn split_world_copies(bbox: &MercatorAabb2d) -> Vec<(i32, MercatorAabb2d)> {
+    let world_width = 2.0 * WEB_MERCATOR_EXTENT;
+    let first = ((bbox.min.x + WEB_MERCATOR_EXTENT) / world_width).floor() as i32;
+    let last = (((bbox.max.x + WEB_MERCATOR_EXTENT) / world_width).floor() as i32)
+        .min(first + MAX_WORLD_COPIES - 1);
+    (first..=last)
+        .map(|world| {
+            let offset = world as f64 * world_width;
+            let part = MercatorAabb2d {
+                min: DVec2::new((bbox.min.x - offset).max(-WEB_MERCATOR_EXTENT), bbox.min.y),
+                max: DVec2::new((bbox.max.x - offset).min(WEB_MERCATOR_EXTENT), bbox.max.y),
+            };
+            (world, part)
+        })
+        .collect()
+}
+
+/// Moves `mercator` by whole world widths so it ends up on the world copy closest to `reference_x`.
+#[cfg(feature = "render")]
+pub(crate) fn nearest_world_copy(mercator: DVec3, reference_x: f64) -> DVec3 {
+    let world_width = 2.0 * WEB_MERCATOR_EXTENT;
+    let offset = ((reference_x - mercator.x) / world_width).round();
+    DVec3::new(mercator.x + offset * world_width, mercator.y, mercator.z)
+}
+
+/// Mercator bounds of `tile`.
+///
+/// Tile y coordinates follow the TMS convention used internally by this crate: the origin is at the bottom (south),
+/// y grows northwards. For XYZ tiles (origin at the top, e.g. OpenStreetMap URLs) use `(1 << zoom) - 1 - y`.
 pub fn tile_to_mercator_aabb(tile: TileMathTile) -> MercatorAabb2d {
     let tile_size = (2.0 * WEB_MERCATOR_EXTENT) / (1u32 << tile.zoom) as f64;
 
@@ -259,6 +399,13 @@ pub fn tile_to_mercator_aabb(tile: TileMathTile) -> MercatorAabb2d {
     MercatorAabb2d { min, max }
 }
 
+/// WGS84 bounds of `tile` in degrees as its south-west and north-east corner (x = longitude, y = latitude),
+/// the y convention is the same as for [`tile_to_mercator_aabb`].
+pub fn tile_to_lonlat_bbox(tile: TileMathTile) -> (DVec2, DVec2) {
+    let bbox = tile_to_mercator_aabb(tile);
+    (bbox.min.mercator_to_lonlat(), bbox.max.mercator_to_lonlat())
+}
+
 impl<T> WebMercatorConversion for Vec<T>
 where
     T: WebMercatorConversion<Output = T> + Copy,
@@ -297,3 +444,418 @@ impl WebMercatorConversion for Vec<(f64, f64)> {
             .collect()
     }
 }
+
+/// Mean earth radius in meters (IUGG), used for spherical distance calculations.
+pub const EARTH_MEAN_RADIUS: f64 = 6_371_008.8;
+
+const WGS84_A: f64 = 6_378_137.0;
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// Great-circle distance in meters between two lon/lat points (in degrees) using the haversine formula.
+///
+/// Assumes a spherical earth, the error compared to the WGS84 ellipsoid is below 0.5%.
+/// Use [`vincenty_distance`] if you need more accuracy.
+pub fn haversine_distance(a_lonlat: DVec2, b_lonlat: DVec2) -> f64 {
+    let lat_a = a_lonlat.y.to_radians();
+    let lat_b = b_lonlat.y.to_radians();
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b_lonlat.x - a_lonlat.x).to_radians();
+
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_MEAN_RADIUS * h.sqrt().clamp(0.0, 1.0).asin()
+}
+
+/// Distance in meters between two lon/lat points (in degrees) on the WGS84 ellipsoid using Vincenty's inverse formula.
+///
+/// Accurate to within millimeters. For nearly antipodal points the iteration may not converge,
+/// in which case the result of [`haversine_distance`] is returned.
+pub fn vincenty_distance(a_lonlat: DVec2, b_lonlat: DVec2) -> f64 {
+    let b = WGS84_A * (1.0 - WGS84_F);
+    let l = (b_lonlat.x - a_lonlat.x).to_radians();
+    let u1 = ((1.0 - WGS84_F) * a_lonlat.y.to_radians().tan()).atan();
+    let u2 = ((1.0 - WGS84_F) * b_lonlat.y.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..200 {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == 0.0 {
+            // coincident points
+            return 0.0;
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos_sq_alpha = 1.0 - sin_alpha * sin_alpha;
+        // equatorial lines have cos_sq_alpha == 0
+        let cos_2sigma_m = if cos_sq_alpha != 0.0 {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos_sq_alpha
+        } else {
+            0.0
+        };
+        let c = WGS84_F / 16.0 * cos_sq_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos_sq_alpha));
+        let lambda_prev = lambda;
+        lambda = l
+            + (1.0 - c)
+                * WGS84_F
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+
+        if (lambda - lambda_prev).abs() < 1e-12 {
+            let u_sq = cos_sq_alpha * (WGS84_A * WGS84_A - b * b) / (b * b);
+            let big_a =
+                1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+            let big_b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+            let delta_sigma = big_b
+                * sin_sigma
+                * (cos_2sigma_m
+                    + big_b / 4.0
+                        * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                            - big_b / 6.0
+                                * cos_2sigma_m
+                                * (-3.0 + 4.0 * sin_sigma.powi(2))
+                                * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+            return b * big_a * (sigma - delta_sigma);
+        }
+    }
+    haversine_distance(a_lonlat, b_lonlat)
+}
+
+/// Initial bearing (forward azimuth) in degrees clockwise from north when travelling
+/// along the great circle from `from_lonlat` to `to_lonlat` (both in degrees).
+///
+/// The result is in the range `0.0..360.0`. Paths crossing the antimeridian are handled correctly.
+pub fn initial_bearing(from_lonlat: DVec2, to_lonlat: DVec2) -> f64 {
+    let lat_a = from_lonlat.y.to_radians();
+    let lat_b = to_lonlat.y.to_radians();
+    let d_lon = (to_lonlat.x - from_lonlat.x).to_radians();
+
+    let y = d_lon.sin() * lat_b.cos();
+    let x = lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * d_lon.cos();
+    let bearing = y.atan2(x).to_degrees().rem_euclid(360.0);
+    // rem_euclid can round tiny negative values up to exactly 360.0
+    if bearing >= 360.0 { 0.0 } else { bearing }
+}
+
+/// Points along the great circle from `a_lonlat` to `b_lonlat` (in degrees), split into `segments` parts of equal length.
+///
+/// The result contains `segments + 1` points including both ends. Longitudes are continuous along the path and may
+/// leave the `-180..180` range when it crosses the antimeridian, so the points can be drawn as a line without a jump.
+/// For antipodal points the great circle is ambiguous and the points are interpolated linearly in lon/lat instead.
+pub fn densify_geodesic(a_lonlat: DVec2, b_lonlat: DVec2, segments: usize) -> Vec<DVec2> {
+    let segments = segments.max(1);
+    let to_unit = |lonlat: DVec2| {
+        let (lon, lat) = (lonlat.x.to_radians(), lonlat.y.to_radians());
+        DVec3::new(lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin())
+    };
+    let (a, b) = (to_unit(a_lonlat), to_unit(b_lonlat));
+    let angle = a.dot(b).clamp(-1.0, 1.0).acos();
+
+    let mut points = Vec::with_capacity(segments + 1);
+    points.push(a_lonlat);
+    for i in 1..segments {
+        let t = i as f64 / segments as f64;
+        let point = if angle.sin().abs() < 1e-12 {
+            a_lonlat.lerp(b_lonlat, t)
+        } else {
+            // spherical linear interpolation between the unit vectors
+            let p = (a * ((1.0 - t) * angle).sin() + b * (t * angle).sin()) / angle.sin();
+            DVec2::new(
+                p.y.atan2(p.x).to_degrees(),
+                p.z.clamp(-1.0, 1.0).asin().to_degrees(),
+            )
+        };
+        points.push(point);
+    }
+    points.push(b_lonlat);
+
+    // keep the longitudes continuous, atan2 wraps them into -180..180
+    for i in 1..points.len() {
+        let previous = points[i - 1].x;
+        points[i].x += ((previous - points[i].x) / 360.0).round() * 360.0;
+    }
+    points
+}
+
+// number of points converted per task in the batch conversions
+const BATCH_CHUNK_SIZE: usize = 16_384;
+
+#[inline]
+fn lonlat_to_mercator_spherical(lonlat: DVec2) -> DVec2 {
+    let lat = lonlat
+        .y
+        .clamp(-MAX_MERCATOR_LATITUDE, MAX_MERCATOR_LATITUDE)
+        .to_radians();
+    DVec2::new(
+        WGS84_A * lonlat.x.to_radians(),
+        WGS84_A * (FRAC_PI_4 + lat / 2.0).tan().ln(),
+    )
+}
+
+#[inline]
+fn mercator_to_lonlat_spherical(mercator: DVec2) -> DVec2 {
+    DVec2::new(
+        (mercator.x / WGS84_A).to_degrees(),
+        (2.0 * (mercator.y / WGS84_A).exp().atan() - FRAC_PI_2).to_degrees(),
+    )
+}
+
+fn convert_batch(points: &[DVec2], convert: fn(DVec2) -> DVec2) -> Vec<DVec2> {
+    if points.len() <= BATCH_CHUNK_SIZE {
+        return points.iter().map(|p| convert(*p)).collect();
+    }
+    let pool = ComputeTaskPool::get_or_init(TaskPool::default);
+    points
+        .par_chunk_map(pool, BATCH_CHUNK_SIZE, move |_, chunk| {
+            chunk.iter().map(|p| convert(*p)).collect::<Vec<_>>()
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Converts many lon/lat points (in degrees) to Web Mercator at once.
+///
+/// Uses the closed-form spherical mercator formula instead of going through the projection per point
+/// and splits large inputs across the [`ComputeTaskPool`]. The results match
+/// [`WebMercatorConversion::lonlat_to_mercator`] within floating point tolerance, including the latitude clamping.
+pub fn lonlat_to_mercator_batch(points: &[DVec2]) -> Vec<DVec2> {
+    convert_batch(points, lonlat_to_mercator_spherical)
+}
+
+/// Converts many Web Mercator points to lon/lat in degrees at once, see [`lonlat_to_mercator_batch`].
+pub fn mercator_to_lonlat_batch(points: &[DVec2]) -> Vec<DVec2> {
+    convert_batch(points, mercator_to_lonlat_spherical)
+}
+
+/// Formats lon/lat in degrees as degrees, minutes and seconds, latitude first, e.g. `52°31'12"N 13°24'18"E`.
+///
+/// Seconds are rounded to whole numbers.
+pub fn format_dms(lonlat: DVec2) -> String {
+    format!(
+        "{} {}",
+        format_dms_component(lonlat.y, ['N', 'S']),
+        format_dms_component(lonlat.x, ['E', 'W'])
+    )
+}
+
+/// Formats lon/lat in degrees as decimal degrees with `precision` decimal places, latitude first,
+/// e.g. `52.5200°N 13.4050°E` for a precision of 4.
+pub fn format_decimal_degrees(lonlat: DVec2, precision: usize) -> String {
+    format!(
+        "{} {}",
+        format_degrees(lonlat.y, precision, ['N', 'S']),
+        format_degrees(lonlat.x, precision, ['E', 'W'])
+    )
+}
+
+/// Formats a single latitude or longitude with a hemisphere suffix, e.g. `52.52°N`.
+pub(crate) fn format_degrees(
+    value: f64,
+    precision: usize,
+    [positive, negative]: [char; 2],
+) -> String {
+    // avoid "-0.00" and a southern/western suffix for values that round to zero
+    let rounded = format!("{:.*}", precision, value.abs());
+    let is_zero = rounded.chars().all(|c| c == '0' || c == '.');
+    let suffix = if value < 0.0 && !is_zero {
+        negative
+    } else {
+        positive
+    };
+    format!("{rounded}°{suffix}")
+}
+
+fn format_dms_component(value: f64, [positive, negative]: [char; 2]) -> String {
+    // round once on whole seconds so 59.99" carries over into the minutes
+    let total_seconds = (value.abs() * 3600.0).round() as u64;
+    let degrees = total_seconds / 3600;
+    let minutes = total_seconds % 3600 / 60;
+    let seconds = total_seconds % 60;
+    let suffix = if value < 0.0 && total_seconds > 0 {
+        negative
+    } else {
+        positive
+    };
+    format!("{degrees}°{minutes}'{seconds}\"{suffix}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARIS: DVec2 = DVec2::new(2.3522, 48.8566);
+    const LONDON: DVec2 = DVec2::new(-0.1278, 51.5074);
+    const BERLIN: DVec2 = DVec2::new(13.4050, 52.5200);
+    const MUNICH: DVec2 = DVec2::new(11.5820, 48.1351);
+    const JFK: DVec2 = DVec2::new(-73.7781, 40.6413);
+    const LAX: DVec2 = DVec2::new(-118.4085, 33.9416);
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {expected} ± {tolerance}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn haversine_city_pairs() {
+        assert_close(haversine_distance(PARIS, LONDON), 343_560.0, 1_000.0);
+        assert_close(haversine_distance(BERLIN, MUNICH), 504_400.0, 1_000.0);
+        assert_close(haversine_distance(JFK, LAX), 3_974_300.0, 2_000.0);
+        assert_eq!(haversine_distance(BERLIN, BERLIN), 0.0);
+        // symmetric
+        assert_close(
+            haversine_distance(LONDON, PARIS),
+            haversine_distance(PARIS, LONDON),
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn vincenty_reference_line() {
+        // Flinders Peak to Buninyong, the example from Vincenty's 1975 paper: 54 972.271 m
+        let flinders_peak = DVec2::new(144.424_867_888_888_9, -37.951_033_416_666_67);
+        let buninyong = DVec2::new(143.926_495_527_777_8, -37.652_821_138_888_89);
+        assert_close(
+            vincenty_distance(flinders_peak, buninyong),
+            54_972.271,
+            0.01,
+        );
+    }
+
+    #[test]
+    fn vincenty_city_pairs() {
+        // ellipsoidal distance of JFK to LAX is about 2475 statute miles
+        assert_close(vincenty_distance(JFK, LAX), 3_983_000.0, 2_000.0);
+        // within the 0.5% of the spherical approximation
+        for (a, b) in [(PARIS, LONDON), (BERLIN, MUNICH), (JFK, LAX)] {
+            let vincenty = vincenty_distance(a, b);
+            assert_close(haversine_distance(a, b), vincenty, vincenty * 0.005);
+        }
+        assert_eq!(vincenty_distance(PARIS, PARIS), 0.0);
+    }
+
+    #[test]
+    fn vincenty_antipodal_is_finite() {
+        let distance = vincenty_distance(DVec2::new(0.0, 0.0), DVec2::new(180.0, 0.0));
+        assert!(distance.is_finite());
+        assert_close(distance, 20_003_931.0, 40_000.0);
+    }
+
+    #[test]
+    fn latitude_clamps_at_poles() {
+        let north = DVec2::new(10.0, 90.0).lonlat_to_mercator();
+        let south = DVec2::new(10.0, -90.0).lonlat_to_mercator();
+        assert!(north.is_finite() && south.is_finite());
+        // the clamped latitude is the edge of the square mercator world
+        assert_close(north.y, WEB_MERCATOR_EXTENT, 1e-3);
+        assert_close(south.y, -WEB_MERCATOR_EXTENT, 1e-3);
+        assert_eq!(
+            north,
+            DVec2::new(10.0, MAX_MERCATOR_LATITUDE).lonlat_to_mercator()
+        );
+        assert_close(north.mercator_to_lonlat().y, MAX_MERCATOR_LATITUDE, 1e-9);
+        assert_eq!(DVec2::new(90.0, 10.0).latlon_to_mercator(), north);
+        let batch = lonlat_to_mercator_batch(&[DVec2::new(10.0, 90.0)]);
+        assert_close(batch[0].y, north.y, 1e-3);
+    }
+
+    #[test]
+    fn batch_matches_per_point_conversion() {
+        // more points than a single chunk, so the parallel path is used as well
+        let lonlats = (0..BATCH_CHUNK_SIZE * 3 + 7)
+            .map(|i| {
+                let t = i as f64 / (BATCH_CHUNK_SIZE * 3) as f64;
+                DVec2::new(t * 360.0 - 180.0, (t * 997.0).fract() * 180.0 - 90.0)
+            })
+            .collect::<Vec<_>>();
+        let mercators = lonlat_to_mercator_batch(&lonlats);
+        assert_eq!(mercators.len(), lonlats.len());
+        for (lonlat, mercator) in lonlats.iter().zip(&mercators) {
+            let expected = lonlat.lonlat_to_mercator();
+            assert_close(mercator.x, expected.x, 1e-4);
+            assert_close(mercator.y, expected.y, 1e-4);
+        }
+        let back = mercator_to_lonlat_batch(&mercators);
+        for (mercator, lonlat) in mercators.iter().zip(&back) {
+            let expected = mercator.mercator_to_lonlat();
+            assert_close(lonlat.x, expected.x, 1e-9);
+            assert_close(lonlat.y, expected.y, 1e-9);
+        }
+        assert!(lonlat_to_mercator_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn tile_lonlat_bbox() {
+        let (south_west, north_east) = tile_to_lonlat_bbox(TileMathTile {
+            zoom: 0,
+            x: 0,
+            y: 0,
+        });
+        assert_close(south_west.x, -180.0, 1e-9);
+        assert_close(south_west.y, -MAX_MERCATOR_LATITUDE, 1e-9);
+        assert_close(north_east.x, 180.0, 1e-9);
+        assert_close(north_east.y, MAX_MERCATOR_LATITUDE, 1e-9);
+        // the north-east quarter at zoom 1, tile rows count from the south
+        let (south_west, north_east) = tile_to_lonlat_bbox(TileMathTile {
+            zoom: 1,
+            x: 1,
+            y: 1,
+        });
+        assert_close(south_west.x, 0.0, 1e-9);
+        assert_close(south_west.y, 0.0, 1e-9);
+        assert_close(north_east.x, 180.0, 1e-9);
+        assert_close(north_east.y, MAX_MERCATOR_LATITUDE, 1e-9);
+    }
+
+    #[test]
+    fn densify_geodesic_few_segments() {
+        let (a, b) = (DVec2::new(0.0, 0.0), DVec2::new(90.0, 0.0));
+        // zero segments are treated as one, just the ends
+        assert_eq!(densify_geodesic(a, b, 0), [a, b]);
+        assert_eq!(densify_geodesic(a, b, 1), [a, b]);
+        let points = densify_geodesic(a, b, 2);
+        assert_eq!(points.len(), 3);
+        assert_eq!((points[0], points[2]), (a, b));
+        assert_close(points[1].x, 45.0, 1e-9);
+        assert_close(points[1].y, 0.0, 1e-9);
+        // the same point twice
+        assert!(
+            densify_geodesic(PARIS, PARIS, 2)
+                .iter()
+                .all(|point| point.distance(PARIS) < 1e-6)
+        );
+        // the longitudes stay continuous across the antimeridian
+        let points = densify_geodesic(DVec2::new(170.0, 0.0), DVec2::new(-170.0, 0.0), 2);
+        assert_close(points[1].x, 180.0, 1e-9);
+        assert_close(points[2].x, 190.0, 1e-9);
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn camera_without_viewport_is_an_error() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        world.insert_resource(LocalOrigin::new(DVec3::ZERO));
+        // the render target is only resolved by the camera systems of the render plugins
+        world.spawn((Camera::default(), GlobalTransform::default(), MainCam));
+        let results = world
+            .run_system_once(|view: ViewportConv<MainCam>| {
+                (
+                    view.visible_mercator_aabb().is_err(),
+                    view.viewport_center_mercator().is_err(),
+                    view.meters_per_pixel().is_err(),
+                )
+            })
+            .unwrap();
+        assert_eq!(results, (true, true, true));
+    }
+}
@@ -0,0 +1,226 @@
+use bevy::{camera::CameraUpdateSystems, math::DVec2, prelude::*};
+
+use crate::{
+    LocalOrigin, LocalOriginConversion, LocalSpace, MAX_MERCATOR_LATITUDE, MainCam, MercatorAabb2d,
+    ViewportConv, WebMercatorConversion,
+    coord_conversions::{WEB_MERCATOR_EXTENT, format_degrees},
+};
+
+// grid intervals in degrees, from coarse to fine
+const INTERVALS: [f64; 16] = [
+    30.0, 10.0, 5.0, 2.0, 1.0, 0.5, 0.2, 0.1, 0.05, 0.02, 0.01, 0.005, 0.002, 0.001, 0.0005, 0.0002,
+];
+
+// draw the grid above the tiles but below markers and shapes
+const GRATICULE_Z: f32 = 0.5;
+
+pub(crate) fn graticule_plugin(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        // after the camera update, so the grid covers a resized window in the same frame
+        update_graticule
+            .after(CameraUpdateSystems)
+            .before(TransformSystems::Propagate),
+    );
+}
+
+/// Grid of latitude and longitude lines across the viewport with labels at the viewport edges.
+///
+/// Insert this resource to show the grid and remove it to hide it. The interval adapts to the zoom level.
+#[derive(Resource, Debug, Clone)]
+pub struct Graticule {
+    pub color: Color,
+    /// Line width in logical pixels
+    pub line_width: f32,
+    /// Maximum number of lines per direction, the finest interval staying below it is used
+    pub max_lines: u32,
+    /// Show the coordinate of each line at the top and left edge of the viewport
+    pub labels: bool,
+}
+
+impl Default for Graticule {
+    fn default() -> Self {
+        Self {
+            color: Color::BLACK.with_alpha(0.5),
+            line_width: 1.0,
+            max_lines: 8,
+            labels: true,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct GraticuleLine;
+
+#[derive(Component, Debug)]
+struct GraticuleLabel;
+
+fn update_graticule(
+    mut commands: Commands,
+    graticule: Option<Res<Graticule>>,
+    view: ViewportConv<MainCam>,
+    camera: Query<(Entity, &Camera), With<MainCam>>,
+    origin: Res<LocalOrigin>,
+    mut lines: Query<
+        (Entity, &mut Transform, &mut Sprite, &mut Visibility),
+        (With<GraticuleLine>, Without<GraticuleLabel>),
+    >,
+    mut labels: Query<
+        (
+            Entity,
+            &mut Node,
+            &mut Text,
+            &mut TextColor,
+            &mut UiTargetCamera,
+            &mut Visibility,
+        ),
+        (With<GraticuleLabel>, Without<GraticuleLine>),
+    >,
+    mut last_view: Local<Option<MercatorAabb2d>>,
+) {
+    let Some(graticule) = graticule else {
+        if last_view.take().is_some() {
+            for (entity, ..) in &lines {
+                commands.entity(entity).despawn();
+            }
+            for (entity, ..) in &labels {
+                commands.entity(entity).despawn();
+            }
+        }
+        return;
+    };
+    let (Ok(bbox), Ok((camera_entity, camera))) = (view.visible_mercator_aabb(), camera.single())
+    else {
+        return;
+    };
+    let Some(viewport_size) = camera.logical_viewport_size() else {
+        return;
+    };
+    // only update when the view or the settings changed
+    let unchanged = last_view.is_some_and(|last| last.min == bbox.min && last.max == bbox.max)
+        && !graticule.is_changed();
+    if unchanged {
+        return;
+    }
+    *last_view = Some(bbox);
+
+    let world_per_pixel = (bbox.size().x / viewport_size.x as f64) as f32;
+    let line_width = graticule.line_width * world_per_pixel;
+    // longitude is linear in mercator x, this keeps longitudes beyond ±180° on world copies
+    let lon_min = bbox.min.x / WEB_MERCATOR_EXTENT * 180.0;
+    let lon_max = bbox.max.x / WEB_MERCATOR_EXTENT * 180.0;
+    let lat_min = bbox.min.mercator_to_lonlat().y.max(-MAX_MERCATOR_LATITUDE);
+    let lat_max = bbox.max.mercator_to_lonlat().y.min(MAX_MERCATOR_LATITUDE);
+    let span = (lon_max - lon_min).max(lat_max - lat_min);
+    let interval = INTERVALS
+        .iter()
+        .copied()
+        .take_while(|interval| span / interval <= graticule.max_lines as f64)
+        .last()
+        .unwrap_or(INTERVALS[0]);
+    let precision = (-interval.log10().floor()).max(0.0) as usize;
+    let local_bbox = bbox.mercator_to_local(&origin);
+    let local_center = local_bbox.center();
+    let local_size = local_bbox.half_size() * 2.0;
+
+    // translation and size of each line, text and position of each label
+    let mut new_lines = Vec::new();
+    let mut new_labels = Vec::new();
+
+    // meridians, stepping in whole multiples of the interval to avoid accumulating rounding errors
+    for i in (lon_min / interval).ceil() as i64..=(lon_max / interval).floor() as i64 {
+        let lon = i as f64 * interval;
+        let x = DVec2::new(lon / 180.0 * WEB_MERCATOR_EXTENT, 0.0)
+            .mercator_to_local(&origin)
+            .x as f32;
+        new_lines.push((
+            Vec2::new(x, local_center.y),
+            Vec2::new(line_width, local_size.y),
+        ));
+        if graticule.labels {
+            let wrapped = (lon + 180.0).rem_euclid(360.0) - 180.0;
+            let left = (x - local_bbox.min.x) / world_per_pixel + 2.0;
+            new_labels.push((format_degrees(wrapped, precision, ['E', 'W']), left, 2.0));
+        }
+    }
+
+    // parallels
+    for i in (lat_min / interval).ceil() as i64..=(lat_max / interval).floor() as i64 {
+        let lat = i as f64 * interval;
+        let y = DVec2::new(0.0, lat).lonlat_to_local(&origin).y as f32;
+        new_lines.push((
+            Vec2::new(local_center.x, y),
+            Vec2::new(local_size.x, line_width),
+        ));
+        if graticule.labels {
+            let top = (local_bbox.max.y - y) / world_per_pixel + 2.0;
+            new_labels.push((format_degrees(lat, precision, ['N', 'S']), 2.0, top));
+        }
+    }
+
+    // reuse the existing entities, surplus ones are hidden until the view needs more lines again
+    let mut pool = lines.iter_mut();
+    for (translation, size) in new_lines {
+        let transform = Transform::from_translation(translation.extend(GRATICULE_Z))
+            .with_scale(size.extend(1.0));
+        match pool.next() {
+            Some((_, mut line_transform, mut sprite, mut visibility)) => {
+                *line_transform = transform;
+                sprite.color = graticule.color;
+                visibility.set_if_neq(Visibility::Inherited);
+            }
+            None => {
+                commands.spawn((
+                    GraticuleLine,
+                    LocalSpace,
+                    Sprite {
+                        color: graticule.color,
+                        custom_size: Some(Vec2::ONE),
+                        ..default()
+                    },
+                    transform,
+                    Pickable::IGNORE,
+                ));
+            }
+        }
+    }
+    for (.., mut visibility) in pool {
+        visibility.set_if_neq(Visibility::Hidden);
+    }
+
+    let label_color = TextColor(graticule.color.with_alpha(1.0));
+    let mut pool = labels.iter_mut();
+    for (text, left, top) in new_labels {
+        match pool.next() {
+            Some((_, mut node, mut label, mut color, mut target, mut visibility)) => {
+                node.left = Val::Px(left);
+                node.top = Val::Px(top);
+                if label.0 != text {
+                    label.0 = text;
+                }
+                color.set_if_neq(label_color);
+                target.set_if_neq(UiTargetCamera(camera_entity));
+                visibility.set_if_neq(Visibility::Inherited);
+            }
+            None => {
+                commands.spawn((
+                    GraticuleLabel,
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(left),
+                        top: Val::Px(top),
+                        ..default()
+                    },
+                    Text::new(text),
+                    TextFont::from_font_size(11.0),
+                    label_color,
+                    Pickable::IGNORE,
+                    UiTargetCamera(camera_entity),
+                ));
+            }
+        }
+    }
+    for (.., mut visibility) in pool {
+        visibility.set_if_neq(Visibility::Hidden);
+    }
+}
@@ -15,7 +15,13 @@ pub struct LocalSpace;
 ///
 /// The z coordinate is not used for coordinate conversions but can be used to create layers (i.e. displaying things above other things)
 /// Entities with this component automatically get a [Transform] component with the local coordinates relative to the `LocalOrigin`.
+/// As the map repeats horizontally, entities are displayed in the copy of the world closest to the `LocalOrigin`.
 #[derive(Component, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct MercatorCoords(pub DVec3);
 
 impl MercatorCoords {
@@ -28,13 +34,18 @@ impl MercatorCoords {
     }
 
     /// Creates MercatorCoords from latitude and longitude in degrees (WGS84 / EPSG:4326).
+    ///
+    /// See [`MercatorCoords::from_lonlat`] for the handling of out-of-range values.
     pub fn from_latlon(lat: f64, lon: f64) -> Self {
-        let mercator = DVec2::new(lon, lat).lonlat_to_mercator();
-        Self::new(mercator.x, mercator.y, 0.0)
+        Self::from_lonlat(lon, lat)
     }
 
     /// Creates MercatorCoords from longitude and latitude in degrees (WGS84 / EPSG:4326).
+    ///
+    /// The longitude is wrapped into -180..180, e.g. 200° becomes -160°, and the latitude is clamped
+    /// to ±[`MAX_MERCATOR_LATITUDE`](crate::MAX_MERCATOR_LATITUDE), so the position always lies on the main world.
     pub fn from_lonlat(lon: f64, lat: f64) -> Self {
+        let lon = (lon + 180.0).rem_euclid(360.0) - 180.0;
         let mercator = DVec2::new(lon, lat).lonlat_to_mercator();
         Self::new(mercator.x, mercator.y, 0.0)
     }
@@ -75,25 +86,35 @@ pub struct LocalOrigin {
 }
 
 impl LocalOrigin {
-    pub(crate) fn new(mercator_origin: DVec3) -> Self {
+    /// Creates a local origin at the given web mercator position with the default recenter distance (25 km).
+    pub fn new(mercator_origin: DVec3) -> Self {
         Self::with_distance(mercator_origin, DEFAULT_RECENTER_DISTANCE)
     }
 
-    pub(crate) fn with_distance(mercator_origin: DVec3, recenter_distance: f64) -> Self {
+    /// Creates a local origin at the given web mercator position that recenters once the camera
+    /// is more than `recenter_distance` (in local units) away from it.
+    pub fn with_distance(mercator_origin: DVec3, recenter_distance: f64) -> Self {
         Self {
             mercator_origin,
             recenter_distance,
         }
     }
 
-    pub(crate) fn mercator_origin(&self) -> DVec3 {
+    /// Web mercator position of the local coordinate system's origin.
+    pub fn mercator_origin(&self) -> DVec3 {
         self.mercator_origin
     }
 
-    pub(crate) fn recenter_distance(&self) -> f64 {
+    /// Distance from the origin (in local units) at which the camera triggers a recenter.
+    #[cfg_attr(
+        feature = "render",
+        doc = "See [`RecenterPolicy`](crate::RecenterPolicy)."
+    )]
+    pub fn recenter_distance(&self) -> f64 {
         self.recenter_distance
     }
 
+    #[cfg(feature = "render")]
     pub(crate) fn shift_mercator_origin(&mut self, delta: DVec3) {
         self.mercator_origin += delta;
     }
@@ -101,6 +122,7 @@ impl LocalOrigin {
 
 /// Axis-aligned bounding box in web mercator coordinates, uses DVec2 for min and max.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MercatorAabb2d {
     pub min: DVec2,
     pub max: DVec2,
@@ -122,10 +144,41 @@ impl MercatorAabb2d {
     pub fn half_size(&self) -> DVec2 {
         self.size() * 0.5
     }
+
+    /// Whether `point` lies inside the box, points on the edges are included.
+    pub fn contains_point(&self, point: DVec2) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    /// Whether `other` lies completely inside this box, touching edges are included.
+    pub fn contains(&self, other: &Self) -> bool {
+        other.min.cmpge(self.min).all() && other.max.cmple(self.max).all()
+    }
+
+    /// Whether the boxes overlap, boxes that only touch at an edge or corner intersect as well.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    /// Smallest box containing both boxes.
+    pub fn merge(&self, other: &Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// Extends the box by `amount` in every direction, e.g. to add a margin around a map extent.
+    pub fn grow(&self, amount: DVec2) -> Self {
+        Self::new(self.min - amount, self.max + amount)
+    }
+
+    /// Shrinks the box by `amount` in every direction, the inverse of [`MercatorAabb2d::grow`].
+    pub fn shrink(&self, amount: DVec2) -> Self {
+        self.grow(-amount)
+    }
 }
 
 /// Bounding box of tile coordinates.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileBounds {
     pub min: UVec2,
     pub max: UVec2,
@@ -142,3 +195,119 @@ impl TileBounds {
         self.min.y..=self.max.y
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bbox(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> MercatorAabb2d {
+        MercatorAabb2d::new(DVec2::new(min_x, min_y), DVec2::new(max_x, max_y))
+    }
+
+    #[test]
+    fn boxes_touching_at_an_edge_intersect() {
+        let a = bbox(0.0, 0.0, 10.0, 10.0);
+        // sharing the right edge, the top edge and only the top right corner
+        for b in [
+            bbox(10.0, 0.0, 20.0, 10.0),
+            bbox(0.0, 10.0, 10.0, 20.0),
+            bbox(10.0, 10.0, 20.0, 20.0),
+        ] {
+            assert!(a.intersects(&b));
+            assert!(b.intersects(&a));
+            assert!(!a.contains(&b));
+        }
+        // a gap of one unit
+        let apart = bbox(11.0, 0.0, 20.0, 10.0);
+        assert!(!a.intersects(&apart));
+        assert!(!apart.intersects(&a));
+    }
+
+    #[test]
+    fn edges_are_contained() {
+        let a = bbox(-5.0, -5.0, 5.0, 5.0);
+        assert!(a.contains(&a));
+        assert!(a.contains(&bbox(-5.0, 0.0, 0.0, 5.0)));
+        assert!(!a.contains(&bbox(-5.0, 0.0, 0.0, 5.5)));
+        for point in [
+            DVec2::new(-5.0, -5.0),
+            DVec2::new(5.0, 0.0),
+            DVec2::new(0.0, 5.0),
+        ] {
+            assert!(a.contains_point(point));
+        }
+        assert!(!a.contains_point(DVec2::new(5.0 + 1e-9, 0.0)));
+    }
+
+    #[test]
+    fn merge_of_touching_boxes() {
+        let merged = bbox(0.0, 0.0, 10.0, 10.0).merge(&bbox(10.0, 5.0, 20.0, 15.0));
+        assert_eq!(
+            (merged.min, merged.max),
+            (DVec2::ZERO, DVec2::new(20.0, 15.0))
+        );
+        assert!(merged.contains(&bbox(0.0, 0.0, 10.0, 10.0)));
+        assert!(merged.contains(&bbox(10.0, 5.0, 20.0, 15.0)));
+    }
+
+    #[test]
+    fn lonlat_constructors_wrap_longitude_and_clamp_latitude() {
+        let expected = DVec2::new(-160.0, 10.0).lonlat_to_mercator();
+        assert_eq!(MercatorCoords::from_lonlat(200.0, 10.0).xy(), expected);
+        assert_eq!(MercatorCoords::from_latlon(10.0, 200.0).xy(), expected);
+        let expected = DVec2::new(170.0, -20.0).lonlat_to_mercator();
+        assert_eq!(MercatorCoords::from_lonlat(-190.0, -20.0).xy(), expected);
+        assert_eq!(MercatorCoords::from_latlon(-20.0, -190.0).xy(), expected);
+        // whole turns end up on the main world as well
+        assert_eq!(
+            MercatorCoords::from_lonlat(30.0 + 720.0, 0.0).xy(),
+            MercatorCoords::from_lonlat(30.0, 0.0).xy()
+        );
+
+        let north = MercatorCoords::from_lonlat(200.0, 90.0);
+        let south = MercatorCoords::from_latlon(-90.0, -190.0);
+        assert!(north.xy().is_finite() && south.xy().is_finite());
+        assert_eq!(
+            north.xy(),
+            DVec2::new(-160.0, crate::MAX_MERCATOR_LATITUDE).lonlat_to_mercator()
+        );
+        assert_eq!(
+            south.xy(),
+            DVec2::new(170.0, -crate::MAX_MERCATOR_LATITUDE).lonlat_to_mercator()
+        );
+        assert_eq!(north.0.z, 0.0);
+    }
+
+    #[cfg(feature = "serde")]
+    fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(value: &T) -> T {
+        serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips_are_exact() {
+        // values without a short decimal representation
+        let x = 0.1 + 0.2;
+        let y = -20_037_508.342_789_244;
+        let z = f64::MIN_POSITIVE;
+
+        let coords = MercatorCoords(DVec3::new(x, y, z));
+        assert_eq!(round_trip(&coords).0, coords.0);
+        // stored as the plain vector
+        assert_eq!(
+            serde_json::to_value(coords).unwrap(),
+            serde_json::json!([x, y, z])
+        );
+
+        let bbox = MercatorAabb2d::new(DVec2::new(x, y), DVec2::new(-y, 1.0 / 3.0));
+        let bbox_trip = round_trip(&bbox);
+        assert_eq!((bbox_trip.min, bbox_trip.max), (bbox.min, bbox.max));
+
+        let bounds = TileBounds {
+            min: UVec2::new(0, 3),
+            max: UVec2::new(u32::MAX, 7),
+        };
+        let bounds_trip = round_trip(&bounds);
+        assert_eq!((bounds_trip.min, bounds_trip.max), (bounds.min, bounds.max));
+    }
+}
@@ -0,0 +1,139 @@
+use bevy::{math::DVec2, prelude::*, sprite::Anchor};
+
+use crate::{
+    KeepDisplaySize, MainCam, MercatorAabb2d, MercatorCoords, ViewportConv,
+    coord_conversions::nearest_world_copy, lonlat_to_mercator_batch,
+};
+
+/// Z coordinate markers are spawned at, above tiles and shapes.
+const MARKER_Z: f64 = 5.0;
+
+/// Appearance of a [`GeoMarker`].
+#[derive(Clone, Debug)]
+pub enum MarkerStyle {
+    /// An image anchored at its bottom center like a pin, e.g. loaded with `asset_server.load("marker.png")`
+    Image(Handle<Image>),
+    /// A solid rectangle of `size` centered on the position
+    Color { color: Color, size: Vec2 },
+}
+
+/// A sprite placed at a geographic position that keeps its size on screen while zooming.
+#[derive(Bundle, Clone, Debug)]
+pub struct GeoMarker {
+    pub sprite: Sprite,
+    pub anchor: Anchor,
+    pub coords: MercatorCoords,
+    pub keep_display_size: KeepDisplaySize,
+}
+
+impl GeoMarker {
+    /// Creates a marker showing `image` at the given longitude and latitude in degrees,
+    /// anchored at the bottom center of the image like a pin.
+    pub fn new(lonlat: impl Into<DVec2>, image: Handle<Image>) -> Self {
+        Self::from_style(lonlat, MarkerStyle::Image(image))
+    }
+
+    /// Creates a marker drawn as a solid rectangle of `size` centered on the given longitude and latitude in degrees.
+    pub fn from_color(lonlat: impl Into<DVec2>, color: impl Into<Color>, size: Vec2) -> Self {
+        Self::from_style(
+            lonlat,
+            MarkerStyle::Color {
+                color: color.into(),
+                size,
+            },
+        )
+    }
+
+    /// Creates a marker with the given style at the given longitude and latitude in degrees.
+    pub fn from_style(lonlat: impl Into<DVec2>, style: MarkerStyle) -> Self {
+        let lonlat = lonlat.into();
+        Self::from_mercator(MercatorCoords::from_lonlat(lonlat.x, lonlat.y), style)
+    }
+
+    fn from_mercator(coords: MercatorCoords, style: MarkerStyle) -> Self {
+        let (sprite, anchor) = match style {
+            MarkerStyle::Image(image) => (Sprite::from_image(image), Anchor::BOTTOM_CENTER),
+            MarkerStyle::Color { color, size } => (Sprite::from_color(color, size), Anchor::CENTER),
+        };
+        Self {
+            sprite,
+            anchor,
+            coords: coords.with_z(MARKER_Z),
+            keep_display_size: KeepDisplaySize,
+        }
+    }
+
+    /// Sets the z coordinate of the marker, higher values are drawn on top.
+    pub fn with_z(mut self, z: f64) -> Self {
+        self.coords = self.coords.with_z(z);
+        self
+    }
+}
+
+/// Spawns a marker for every longitude and latitude in degrees, see [`GeoMarker`].
+///
+/// Faster than spawning the markers one by one for large point datasets,
+/// the coordinates are converted at once with [`lonlat_to_mercator_batch`] and the entities are spawned in a batch.
+pub fn spawn_geo_markers(
+    commands: &mut Commands,
+    markers: impl IntoIterator<Item = (DVec2, MarkerStyle)>,
+) {
+    let (lonlats, styles): (Vec<_>, Vec<_>) = markers.into_iter().unzip();
+    let markers = lonlat_to_mercator_batch(&lonlats)
+        .into_iter()
+        .zip(styles)
+        .map(|(mercator, style)| {
+            GeoMarker::from_mercator(MercatorCoords::from_vec(mercator.extend(0.0)), style)
+        })
+        .collect::<Vec<_>>();
+    commands.spawn_batch(markers);
+}
+
+/// Hides the entity while its [`MercatorCoords`] are outside the visible area and shows it again once they are back in view,
+/// e.g. for large sets of markers.
+///
+/// Opt-in, entities without this component keep the visibility they are given. The entity is hidden, not despawned,
+/// and its [`Visibility`] is overwritten whenever the view or its position changes.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct CullOutsideViewport {
+    /// Extra space around the viewport as a fraction of its size, so markers partially sticking into the view stay visible
+    pub margin: f64,
+}
+
+impl Default for CullOutsideViewport {
+    fn default() -> Self {
+        Self { margin: 0.1 }
+    }
+}
+
+pub(crate) fn cull_outside_viewport(
+    view: ViewportConv<MainCam>,
+    mut query: Query<(
+        Ref<MercatorCoords>,
+        Ref<CullOutsideViewport>,
+        &mut Visibility,
+    )>,
+    mut last_view: Local<Option<MercatorAabb2d>>,
+) {
+    let Ok(bbox) = view.visible_mercator_aabb() else {
+        return;
+    };
+    // only entities that moved need an update while the view stays the same
+    let view_changed = !last_view.is_some_and(|last| last.min == bbox.min && last.max == bbox.max);
+    *last_view = Some(bbox);
+    for (coords, cull, mut visibility) in query.iter_mut() {
+        if !view_changed && !coords.is_changed() && !cull.is_changed() {
+            continue;
+        }
+        let grown = bbox.grow(bbox.size() * cull.margin);
+        // the copies on other world copies are children and hidden along with the entity,
+        // so it stays visible while any copy of it is in view
+        let nearest = nearest_world_copy(coords.0, grown.center().x);
+        let visible = grown.contains_point(nearest.truncate());
+        visibility.set_if_neq(if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
@@ -1,68 +1,379 @@
-use bevy::{input::gestures::PinchGesture, prelude::*};
+use bevy::{
+    input::{gestures::PinchGesture, touch::Touches},
+    prelude::*,
+    window::PrimaryWindow,
+};
 
-use crate::MIN_ORTHO_SCALE;
+use crate::{
+    MIN_ORTHO_SCALE, MainCam, NewScale, TileFetchConfig, TileStreamingConfig, scale_to_zoom,
+    zoom_to_scale,
+};
+
+// maximum pointer movement in logical pixels between the two clicks of a double click,
+// and between pressing and releasing the button for a click to count
+const DOUBLE_CLICK_DISTANCE: f32 = 10.0;
+// residual pan velocity in logical pixels per second below which the camera stops gliding
+const INERTIA_STOP_VELOCITY: f32 = 5.0;
+// the pointer has to be released within this many seconds after the last movement to keep gliding
+const INERTIA_RELEASE_TIME: f32 = 0.1;
+// relative scale change per second below which a pinch zoom stops gliding
+const PINCH_STOP_VELOCITY: f32 = 0.05;
 
 pub(crate) fn pancam_plugin(app: &mut App) {
-    app.add_systems(Startup, setup)
-        .add_systems(Update, (pinch_zoom, zoom_smooth).chain());
+    app.init_resource::<PanCamConfig>()
+        .add_observer(setup)
+        .add_systems(
+            Update,
+            ((pinch_zoom, snap_zoom, zoom_smooth).chain(), pan_inertia),
+        );
+}
+
+/// Settings for the built-in camera controls, used when the `bevy_pancam` feature is disabled.
+#[derive(Resource, Debug, Clone)]
+pub struct PanCamConfig {
+    /// Relative scale change per scrolled line, pixel-based scrolling (e.g. touchpads) uses a fifth of it
+    pub zoom_speed: f32,
+    /// Multiplier for pinch gestures
+    pub pinch_speed: f32,
+    /// Keep zooming for a moment after a pinch gesture ends, slowing down with `inertia_friction`
+    pub pinch_fling: bool,
+    /// Move the map with the pointer while dragging, `false` moves the camera with the pointer instead
+    pub invert_drag: bool,
+    /// Maximum time in seconds between two clicks (or taps) to zoom in by one level.
+    /// Holding shift while double-clicking zooms out instead.
+    pub double_click_time: f32,
+    /// How quickly the camera stops gliding after a drag is released, the residual velocity decays by
+    /// `exp(-inertia_friction * seconds)`. Higher values stop faster.
+    pub inertia_friction: f32,
+    /// Snap the zoom to the nearest tile zoom level once the user stopped zooming,
+    /// keeping tiles pixel-aligned at rest
+    pub snap_to_zoom_levels: bool,
+    /// Seconds without zoom input before snapping to the nearest zoom level
+    pub snap_delay: f32,
+    /// How quickly the camera scale follows zoom input, as the decay rate of the remaining difference per second.
+    /// Lower values ease in more smoothly, very high values like `1000.0` zoom almost instantly.
+    pub zoom_smoothing: f32,
+    /// Smallest change of the camera scale that is still applied, smaller steps end the zoom animation
+    pub zoom_stop_threshold: f32,
+}
+
+impl Default for PanCamConfig {
+    fn default() -> Self {
+        Self {
+            zoom_speed: 0.1,
+            pinch_speed: 1.0,
+            pinch_fling: true,
+            invert_drag: true,
+            double_click_time: 0.3,
+            inertia_friction: 5.0,
+            snap_to_zoom_levels: false,
+            snap_delay: 0.25,
+            zoom_smoothing: 20.0,
+            zoom_stop_threshold: 0.001,
+        }
+    }
 }
 
-fn setup(mut commands: Commands, window: Single<Entity, With<Window>>) {
-    commands.entity(*window).observe(camera_drag).observe(zoom);
+// attach the camera controls to every window, including windows opened later on
+fn setup(add: On<Add, Window>, mut commands: Commands) {
+    commands
+        .entity(add.entity)
+        .observe(press)
+        .observe(drag_start)
+        .observe(camera_drag)
+        .observe(drag_end)
+        .observe(zoom)
+        .observe(double_click_zoom);
+}
+
+/// Residual pan velocity of the camera after a drag is released
+#[derive(Component, Debug, Default)]
+pub(crate) struct PanInertia {
+    /// world units per second
+    velocity: Vec2,
+    dragging: bool,
+    last_drag_time: f32,
+    /// world units moved by drag events since `last_sample_time`
+    drag_movement: Vec2,
+    last_sample_time: f32,
+    /// logical pixels the pointer moved since the button was pressed, a click ending a longer drag is no double click
+    press_distance: f32,
+}
+
+fn press(_press: On<Pointer<Press>>, mut inertia: Single<&mut PanInertia, With<MainCam>>) {
+    inertia.press_distance = 0.0;
+}
+
+fn drag_start(
+    _drag: On<Pointer<DragStart>>,
+    mut inertia: Single<&mut PanInertia, With<MainCam>>,
+    time: Res<Time>,
+) {
+    inertia.velocity = Vec2::ZERO;
+    inertia.dragging = true;
+    inertia.drag_movement = Vec2::ZERO;
+    // the movement of this frame's drag events happened since the previous frame
+    inertia.last_sample_time = time.elapsed_secs() - time.delta_secs();
 }
 
 fn camera_drag(
     drag: On<Pointer<Drag>>,
-    mut cam: Single<(&Camera, &GlobalTransform, &mut Transform)>,
+    mut cam: Single<(&Camera, &GlobalTransform, &mut Transform, &mut PanInertia), With<MainCam>>,
+    config: Res<PanCamConfig>,
+    time: Res<Time>,
 ) -> Result {
-    let mut cam_viewport = cam.0.world_to_viewport(cam.1, cam.2.translation)?;
-    cam_viewport += drag.delta * -1.; // inverted feels more natural
-    cam.2.translation = cam.0.viewport_to_world_2d(cam.1, cam_viewport)?.extend(0.0);
+    let (camera, global_transform, transform, inertia) = &mut *cam;
+    let mut cam_viewport = camera.world_to_viewport(global_transform, transform.translation)?;
+    if config.invert_drag {
+        cam_viewport -= drag.delta; // inverted feels more natural
+    } else {
+        cam_viewport += drag.delta;
+    }
+    let new_translation = camera
+        .viewport_to_world_2d(global_transform, cam_viewport)?
+        .extend(0.0);
+    // several drag events can arrive in one frame, the velocity is sampled once per frame in `pan_inertia`
+    inertia.drag_movement += (new_translation - transform.translation).truncate();
+    inertia.press_distance = inertia.press_distance.max(drag.distance.length());
+    inertia.last_drag_time = time.elapsed_secs();
+    transform.translation = new_translation;
     Ok(())
 }
 
+fn drag_end(
+    _drag: On<Pointer<DragEnd>>,
+    mut inertia: Single<&mut PanInertia, With<MainCam>>,
+    time: Res<Time>,
+) {
+    inertia.dragging = false;
+    sample_drag_velocity(&mut inertia, time.elapsed_secs());
+    // the pointer was held still before releasing
+    if time.elapsed_secs() - inertia.last_drag_time > INERTIA_RELEASE_TIME {
+        inertia.velocity = Vec2::ZERO;
+    }
+}
+
+fn pan_inertia(
+    cam: Single<(&mut Transform, &Projection, &mut PanInertia), With<MainCam>>,
+    config: Res<PanCamConfig>,
+    time: Res<Time>,
+) {
+    let (mut transform, projection, mut inertia) = cam.into_inner();
+    if inertia.dragging {
+        sample_drag_velocity(&mut inertia, time.elapsed_secs());
+        return;
+    }
+    if inertia.velocity == Vec2::ZERO {
+        return;
+    }
+    let Projection::Orthographic(projection) = projection else {
+        return;
+    };
+    let world_per_pixel = projection.scale * transform.scale.x;
+    if inertia.velocity.length() < INERTIA_STOP_VELOCITY * world_per_pixel {
+        inertia.velocity = Vec2::ZERO;
+        return;
+    }
+    let delta_secs = time.delta_secs();
+    transform.translation += (inertia.velocity * delta_secs).extend(0.0);
+    inertia.velocity *= (-config.inertia_friction * delta_secs).exp();
+}
+
+// movement since the last sample divided by the time between the frames that had drag events
+fn sample_drag_velocity(inertia: &mut PanInertia, now: f32) {
+    let elapsed = now - inertia.last_sample_time;
+    if inertia.drag_movement == Vec2::ZERO || elapsed <= 0.0 {
+        return;
+    }
+    let velocity = inertia.drag_movement / elapsed;
+    inertia.velocity = inertia.velocity.lerp(velocity, 0.5);
+    inertia.drag_movement = Vec2::ZERO;
+    inertia.last_sample_time = now;
+}
+
 #[derive(Component, Debug)]
 pub(crate) struct SmoothZoom {
     pub(crate) target_scale: f32,
+    /// Viewport position that stays at the same world position while zooming, the viewport center if `None`
+    pub(crate) anchor: Option<Vec2>,
+    /// elapsed seconds of the last zoom input, `None` once snapped to a zoom level
+    last_input: Option<f32>,
+}
+impl SmoothZoom {
+    pub(crate) fn new(target_scale: f32) -> Self {
+        Self {
+            target_scale,
+            anchor: None,
+            last_input: None,
+        }
+    }
 }
 impl Default for SmoothZoom {
     fn default() -> Self {
-        Self { target_scale: 1.0 }
+        Self::new(1.0)
     }
 }
 
-fn zoom(scroll: On<Pointer<Scroll>>, mut zoom: Single<&mut SmoothZoom, With<Camera>>) {
+fn zoom(
+    scroll: On<Pointer<Scroll>>,
+    mut zoom: Single<&mut SmoothZoom, With<MainCam>>,
+    config: Res<PanCamConfig>,
+    time: Res<Time>,
+) {
     let speed = match scroll.unit {
-        bevy::input::mouse::MouseScrollUnit::Line => 0.1,
-        bevy::input::mouse::MouseScrollUnit::Pixel => 0.02,
+        bevy::input::mouse::MouseScrollUnit::Line => config.zoom_speed,
+        bevy::input::mouse::MouseScrollUnit::Pixel => config.zoom_speed * 0.2,
     };
-    zoom.target_scale *= 1.0 - (scroll.y * speed);
-    zoom.target_scale = zoom.target_scale.max(MIN_ORTHO_SCALE);
+    zoom_by(
+        &mut zoom,
+        1.0 - (scroll.y * speed),
+        Some(scroll.pointer_location.position),
+        time.elapsed_secs(),
+    );
+}
+
+/// Scales the zoom target by `factor`, keeping the world position under `anchor` (a window position) in place
+fn zoom_by(zoom: &mut SmoothZoom, factor: f32, anchor: Option<Vec2>, now: f32) {
+    zoom.target_scale = (zoom.target_scale * factor).max(MIN_ORTHO_SCALE);
+    zoom.anchor = anchor;
+    zoom.last_input = Some(now);
+}
+
+fn double_click_zoom(
+    click: On<Pointer<Click>>,
+    mut last_click: Local<Option<(f32, Vec2)>>,
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    config: Res<PanCamConfig>,
+    cam: Single<(&mut SmoothZoom, &PanInertia), With<MainCam>>,
+) {
+    if click.button != PointerButton::Primary {
+        return;
+    }
+    let (mut zoom, inertia) = cam.into_inner();
+    // the end of a pan, e.g. a short drag followed by a click shouldn't zoom
+    if inertia.press_distance > DOUBLE_CLICK_DISTANCE {
+        *last_click = None;
+        return;
+    }
+    let now = time.elapsed_secs();
+    let position = click.pointer_location.position;
+    let is_double_click = last_click.is_some_and(|(time, last_position)| {
+        now - time <= config.double_click_time
+            && last_position.distance(position) <= DOUBLE_CLICK_DISTANCE
+    });
+    if !is_double_click {
+        *last_click = Some((now, position));
+        return;
+    }
+    *last_click = None;
+
+    // one zoom level doubles or halves the scale
+    let factor = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        2.0
+    } else {
+        0.5
+    };
+    zoom_by(&mut zoom, factor, Some(position), now);
+}
+
+/// Pinch zoom speed kept after the gesture ends
+#[derive(Debug, Default)]
+struct PinchMomentum {
+    /// pinch amount per second
+    velocity: f32,
+    anchor: Option<Vec2>,
 }
 
 fn pinch_zoom(
     mut pinch: MessageReader<PinchGesture>,
-    mut zoom: Single<&mut SmoothZoom, With<Camera>>,
+    mut zoom: Single<&mut SmoothZoom, With<MainCam>>,
+    touches: Res<Touches>,
+    window: Option<Single<&Window, With<PrimaryWindow>>>,
+    config: Res<PanCamConfig>,
+    time: Res<Time>,
+    mut momentum: Local<PinchMomentum>,
 ) {
-    for p in pinch.read() {
-        zoom.target_scale *= 1.0 - (p.0);
-        zoom.target_scale = zoom.target_scale.max(MIN_ORTHO_SCALE);
+    let now = time.elapsed_secs();
+    let delta_secs = time.delta_secs();
+    let pinched: f32 = pinch.read().map(|p| p.0).sum();
+    if pinched != 0.0 {
+        // zoom around the center of the two fingers, or the cursor for touchpad gestures
+        let mut fingers = touches.iter().map(|touch| touch.position());
+        let anchor = match (fingers.next(), fingers.next()) {
+            (Some(a), Some(b)) => Some(a.midpoint(b)),
+            _ => window.and_then(|window| window.cursor_position()),
+        };
+        zoom_by(&mut zoom, 1.0 - (pinched * config.pinch_speed), anchor, now);
+        if delta_secs > 0.0 {
+            momentum.velocity = (momentum.velocity + pinched / delta_secs) * 0.5;
+        }
+        momentum.anchor = anchor;
+        return;
+    }
+    // fingers held still on the screen
+    if touches.iter().nth(1).is_some() {
+        momentum.velocity = 0.0;
+    }
+    if !config.pinch_fling || momentum.velocity.abs() < PINCH_STOP_VELOCITY {
+        momentum.velocity = 0.0;
+        return;
     }
+    let factor = 1.0 - (momentum.velocity * delta_secs * config.pinch_speed);
+    zoom_by(&mut zoom, factor, momentum.anchor, now);
+    momentum.velocity *= (-config.inertia_friction * delta_secs).exp();
+}
+
+fn snap_zoom(
+    mut zoom: Single<&mut SmoothZoom, With<MainCam>>,
+    config: Res<PanCamConfig>,
+    tile_fetch_config: Res<TileFetchConfig>,
+    streaming: Res<TileStreamingConfig>,
+    time: Res<Time>,
+) {
+    let Some(last_input) = zoom.last_input else {
+        return;
+    };
+    if !config.snap_to_zoom_levels || time.elapsed_secs() - last_input < config.snap_delay {
+        return;
+    }
+    zoom.last_input = None;
+    let zoom_offset = tile_fetch_config.zoom_offset;
+    let level = scale_to_zoom(zoom.target_scale, zoom_offset, streaming.tile_size);
+    zoom.target_scale = zoom_to_scale(level, zoom_offset, streaming.tile_size);
 }
 
 fn zoom_smooth(
     mut commands: Commands,
-    cam: Single<(&mut Projection, &mut SmoothZoom), With<Camera>>,
+    cam: Single<
+        (
+            &Camera,
+            &GlobalTransform,
+            &mut Transform,
+            &mut Projection,
+            &mut SmoothZoom,
+        ),
+        With<MainCam>,
+    >,
+    config: Res<PanCamConfig>,
     time: Res<Time>,
 ) {
-    let (proj, zoom) = cam.into_inner();
+    let (camera, global_transform, mut transform, proj, zoom) = cam.into_inner();
     if let Projection::Orthographic(ref mut proj) = *proj.into_inner() {
         let mut new_scale = proj.scale;
-        new_scale.smooth_nudge(&zoom.target_scale, 20., time.delta_secs());
+        new_scale.smooth_nudge(&zoom.target_scale, config.zoom_smoothing, time.delta_secs());
         new_scale = new_scale.max(MIN_ORTHO_SCALE);
-        if (proj.scale - new_scale).abs() < 0.001 {
+        if (proj.scale - new_scale).abs() < config.zoom_stop_threshold {
             return;
         }
+        if let Some(anchor) = zoom.anchor
+            && let Ok(anchor_world) = camera.viewport_to_world_2d(global_transform, anchor)
+        {
+            // offset relative to the camera, so it stays valid if the camera moved since the last transform propagation
+            let offset = anchor_world - global_transform.translation().truncate();
+            let ratio = new_scale / proj.scale;
+            transform.translation += (offset * (1.0 - ratio)).extend(0.0);
+        }
         proj.scale = new_scale;
         commands.trigger(NewScale(new_scale));
     }
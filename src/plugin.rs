@@ -0,0 +1,1709 @@
+use std::{ops::RangeInclusive, path::PathBuf, sync::Arc, time::Duration};
+
+use bevy::{
+    camera::{CameraUpdateSystems, RenderTarget, visibility::VisibilitySystems},
+    ecs::system::SystemParam,
+    math::{DVec2, DVec3, I64Vec3, bounding::BoundingVolume},
+    picking::pointer::{PointerId, PointerLocation},
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    sprite::Anchor,
+    window::{PrimaryWindow, WindowResized},
+};
+#[cfg(feature = "bevy_pancam")]
+use bevy_pancam::{PanCam, PanCamPlugin};
+use tilemath::{Tile as TileMathTile, TileIterator};
+
+#[cfg(not(feature = "bevy_pancam"))]
+use crate::pancam::{PanInertia, SmoothZoom, pancam_plugin};
+use crate::{
+    LocalOrigin, LocalOriginConversion, LocalSpace, MercatorAabb2d, MercatorCoords, OverlayLayers,
+    TileFetchConfig, TilePreset, TileServerHealth, TileSource, ToTileCoords, ViewportConv,
+    WebMercatorConversion,
+    coord_conversions::{WEB_MERCATOR_EXTENT, nearest_world_copy, split_world_copies},
+    tile_atlas::tile_atlas_plugin,
+    tile_fetcher::{
+        DEFAULT_USER_AGENT, PendingCacheClears, TileFetcher, apply_tile_fetch_results,
+        clear_tile_cache, default_cache_dir, queue_tile_downloads, reload_tiles_after_cache_clear,
+        tile_textures_plugin,
+    },
+    tile_to_mercator_aabb,
+    world_copy::{copy_to_world_copies, sync_world_copy_instances},
+};
+
+/// Size of a tile in pixels used unless [`MapPlugin::tile_size`] is set.
+pub const TILE_SIZE: f32 = 256.;
+pub const ZOOM_RANGE: RangeInclusive<u8> = 1..=18;
+
+// How many tiles to keep loaded
+const KEEP_UNUSED_TILES: usize = 1000;
+// increase this to make zoom levels "further away" for cleanup logic - closer tiles will be cleaned later
+const ZOOM_DISTANCE_FACTOR: u32 = 10;
+// roughly the tiles of a large viewport across the visible zoom levels, lower limits cause constant reloading
+const MIN_CACHED_TILES: usize = 256;
+// long enough to skip zoom levels passed during a scroll, short enough not to be noticed
+const ZOOM_DEBOUNCE: Duration = Duration::from_millis(100);
+// short enough to not delay zooming noticeably
+const ZOOM_FADE: Duration = Duration::from_millis(150);
+// a full screen of tiles within a few frames
+const MAX_TILES_APPLIED_PER_FRAME: usize = 32;
+
+pub const MIN_ORTHO_SCALE: f32 = 0.1;
+
+// z distance between tile layers of the same zoom level, has to stay well below the zoom level z spacing
+const LAYER_Z_STEP: f32 = 0.01;
+// atlas pages with a z offset within a layer, further pages reuse the offsets
+const ATLAS_Z_PAGES: usize = 64;
+
+#[cfg(not(feature = "bevy_pancam"))]
+pub const SCALE_ZOOM_OFFSET: f32 = 24.5;
+
+#[cfg(feature = "bevy_pancam")]
+pub const SCALE_ZOOM_OFFSET: f32 = 18.0;
+
+/// Marker component for the main camera
+#[derive(Component, Debug)]
+pub struct MainCam;
+
+#[derive(Event, Debug)]
+pub(crate) struct NewScale(pub f32);
+
+/// Orthographic camera scale showing the tiles of a map zoom level, as used when snapping the zoom.
+///
+/// `zoom` is clamped to the supported zoom range, `zoom_offset` should match [`TileFetchConfig::zoom_offset`]
+/// and `tile_size` [`MapPlugin::tile_size`]. Each zoom level halves the scale.
+///
+/// The inverse of [`scale_to_zoom`] within the zoom range:
+/// `scale_to_zoom(zoom_to_scale(z, offset, size), offset, size) == z`.
+pub fn zoom_to_scale(zoom: u8, zoom_offset: i8, tile_size: f32) -> f32 {
+    let level = zoom.clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end()) as i32 + 1 + zoom_offset as i32;
+    2.0f32.powf(SCALE_ZOOM_OFFSET - level as f32 - tile_size_levels(tile_size))
+}
+
+// Scale for `MapPlugin::initial_zoom`, which predates `zoom_to_scale` being the inverse of
+// `scale_to_zoom` and keeps its meaning: the tiles shown are 2 + 2 * zoom_offset levels lower
+fn initial_zoom_scale(initial_zoom: u8, zoom_offset: i8, tile_size: f32) -> f32 {
+    let level =
+        initial_zoom.clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end()) as i32 - 1 - zoom_offset as i32;
+    2.0f32.powf(SCALE_ZOOM_OFFSET - level as f32 - tile_size_levels(tile_size))
+}
+
+/// Zoom level of the tiles shown at the given orthographic camera scale, rounded to the nearest level
+/// and clamped to the supported zoom range.
+///
+/// This is the level reported by [`CurrentZoom`], see [`zoom_to_scale`] for the other direction.
+pub fn scale_to_zoom(scale: f32, zoom_offset: i8, tile_size: f32) -> u8 {
+    let zoom = (SCALE_ZOOM_OFFSET - scale.log2() - tile_size_levels(tile_size)).round() as i32
+        - 1
+        - zoom_offset as i32;
+    zoom.clamp(*ZOOM_RANGE.start() as i32, *ZOOM_RANGE.end() as i32) as u8
+}
+
+/// How many zoom levels lower tiles of `tile_size` pixels are shown than tiles of [`TILE_SIZE`],
+/// e.g. `1.0` for 512px tiles so they cover 512 pixels on screen.
+pub(crate) fn tile_size_levels(tile_size: f32) -> f32 {
+    (tile_size / TILE_SIZE).log2()
+}
+
+/// Bevy plugin for displaying slippy map tiles from a tile server (e.g. OpenStreetMap).
+///
+/// This plugin handles the fetching and displaying of map tiles, as well as managing the camera.
+/// It also exposes some components for working with the map.
+pub struct MapPlugin {
+    /// Initial zoom level of the map, between 1 and 19
+    pub initial_zoom: u8,
+    /// Size of the tiles of the tile server in pixels, e.g. 512 for servers with 512px tiles.
+    /// Larger tiles are shown at a lower zoom level, so they keep their pixel size on screen.
+    /// The [`TileFetchConfig::tile_size`] of `base_layer` and `overlay_layers` should match it.
+    /// Alternatively 512px tiles can be shown at 256px with a `zoom_offset` of -1, don't combine both.
+    /// Defaults to 256
+    pub tile_size: f32,
+    /// Exact initial orthographic scale of the camera, overrides `initial_zoom` if set,
+    /// e.g. to restore a previous view at a fractional zoom level.
+    /// Defaults to `None`
+    pub initial_scale: Option<f32>,
+    /// Initial center of the map in lon/lat (EPSG:4326 / WGS84)
+    pub initial_center: Vec2,
+    /// Whether to use TMS-style Y coordinates (origin bottom-left) instead of XYZ-style (origin top-left).
+    pub reverse_y: bool,
+    /// zoom level offset applied when fetching tiles (can be negative).
+    /// For example, with an offset of -1, tile 3/2/1 will be fetched when tile 4/4/2 is requested, see [`TileFetchConfig::zoom_offset`].
+    pub zoom_offset: i8,
+    /// Tile source URL template, e.g. "<https://tile.openstreetmap.org/{z}/{x}/{y}.png>"
+    pub tile_source: String,
+    /// headers to add to tile requests
+    /// Defaults to: `User-Agent: bevy-geo-tiles/0.1`
+    pub headers: Vec<(String, String)>,
+    /// Directory to use for caching tiles locally
+    /// Defaults to: `std::env::temp_dir()/bevy-geo-tiles-cache`
+    pub cache_directory: PathBuf,
+    /// Whether to cache downloaded tiles in `cache_directory`, disable to never write tiles to disk.
+    /// Defaults to true
+    pub cache_enabled: bool,
+    /// Optional [`TileSource`] used instead of `tile_source`, e.g. an [`MbTilesSource`](crate::MbTilesSource) for offline maps
+    /// or a [`DirectoryTileSource`](crate::DirectoryTileSource) with tiles bundled for offline use that falls back to `tile_source`.
+    pub custom_source: Option<Arc<dyn TileSource>>,
+    /// Opacity of the tile layer, between 0.0 (invisible) and 1.0 (opaque).
+    /// Defaults to 1.0
+    pub tile_opacity: f32,
+    /// Color multiplied into the base layer tiles, e.g. a dark gray to dim the map for a dark theme.
+    /// Defaults to white (unchanged)
+    pub tile_tint: Color,
+    /// Request high-DPI tiles when greater than 1, see [`TileFetchConfig::tile_pixel_ratio`].
+    /// The tile URL either needs an `{r}` placeholder or `@2x` is inserted after `{y}`.
+    /// Defaults to 1
+    pub tile_pixel_ratio: u8,
+    /// Complete configuration of the base tile layer, e.g. loaded from TileJSON with the `tilejson` feature.
+    /// If set, it is used instead of `tile_source`, `headers`, `cache_directory`, `cache_enabled`, `reverse_y`, `zoom_offset`,
+    /// `custom_source`, `tile_opacity`, `tile_tint` and `tile_pixel_ratio`.
+    /// Defaults to `None`
+    pub base_layer: Option<TileFetchConfig>,
+    /// Additional tile layers drawn on top of the base layer in the given order,
+    /// each with its own tile source, zoom offset and opacity.
+    /// Every layer needs its own `cache_directory`.
+    pub overlay_layers: Vec<TileFetchConfig>,
+    /// How many currently invisible tiles to keep loaded before the most distant ones are despawned.
+    /// Values below 256 are raised to 256 to avoid constantly reloading tiles.
+    /// Defaults to 1000
+    pub max_cached_tiles: usize,
+    /// Weight of zoom level differences when picking the most distant tiles to despawn,
+    /// higher values unload tiles of other zoom levels earlier.
+    /// Defaults to 10
+    pub zoom_distance_factor: u32,
+    /// Invisible tiles further away from the viewport center than this many tiles of the current zoom level are despawned
+    /// regardless of `max_cached_tiles`, so tiles of previous locations don't stay loaded after panning far away.
+    /// Defaults to `None`, only despawning tiles above `max_cached_tiles`
+    pub max_tile_distance: Option<u32>,
+    /// How long the zoom level has to stay the same before tiles of a new zoom level are spawned,
+    /// avoids downloading tiles of intermediate zoom levels while zooming quickly.
+    /// Defaults to 100 ms
+    pub zoom_debounce: Duration,
+    /// Duration of the crossfade when tiles of a zoom level are hidden or shown again, zero switches instantly.
+    /// Defaults to 150 ms
+    pub zoom_fade: Duration,
+    /// How many downloaded tiles are turned into textures per frame at most, the rest follows in the next frames.
+    /// Tiles are decoded in the background, but uploading many textures at once can still cause frame drops.
+    /// Defaults to 32
+    pub max_tiles_applied_per_frame: usize,
+    /// Pack the textures of raster tiles into shared atlas pages of 2048x2048 pixels, so the tiles of a page are drawn
+    /// in one batch instead of one draw call per tile. Helps at low zoom levels with a large viewport.
+    /// A page is uploaded again whenever tiles are added to it, so loading tiles costs more texture uploads.
+    /// Tiles larger than a page keep their own texture.
+    /// Defaults to false
+    pub tile_atlas: bool,
+    /// Run without a window and camera, e.g. for tests or server-side tile processing.
+    /// Skips the camera, camera controls, shapes and the view-dependent tile spawning;
+    /// tiles can still be spawned manually and are downloaded as usual.
+    /// Defaults to false
+    pub headless: bool,
+    /// Clear color of the map camera, visible where no tile is loaded yet or the tile server has no tiles.
+    /// Tiles, shapes and markers are drawn on top of it, transparent parts of tiles let it show through.
+    /// Defaults to `None`, using the [`ClearColor`] resource
+    pub background_color: Option<Color>,
+    /// Draw the `z/x/y` coordinate on every tile, can be toggled at runtime using [`ShowTileLabels`].
+    /// Defaults to false
+    #[cfg(feature = "debug_draw")]
+    pub show_tile_labels: bool,
+    /// Panic on startup instead of logging a warning if tiles are requested from OpenStreetMap
+    /// with the default `User-Agent`, see [`TileFetchConfig::uses_default_osm_user_agent`].
+    /// Defaults to false
+    pub require_user_agent: bool,
+}
+
+impl Default for MapPlugin {
+    fn default() -> Self {
+        Self {
+            initial_zoom: 9,
+            tile_size: TILE_SIZE,
+            initial_scale: None,
+            initial_center: Vec2::new(13.4050, 52.5200), // Berlin
+            reverse_y: false,
+            zoom_offset: 0,
+            tile_source: "https://tile.openstreetmap.org/{z}/{x}/{y}.png".to_string(),
+            headers: vec![("User-Agent".to_string(), DEFAULT_USER_AGENT.to_string())],
+            cache_directory: default_cache_dir(),
+            cache_enabled: true,
+            custom_source: None,
+            tile_opacity: 1.0,
+            tile_tint: Color::WHITE,
+            tile_pixel_ratio: 1,
+            base_layer: None,
+            overlay_layers: Vec::new(),
+            max_cached_tiles: KEEP_UNUSED_TILES,
+            zoom_distance_factor: ZOOM_DISTANCE_FACTOR,
+            max_tile_distance: None,
+            zoom_debounce: ZOOM_DEBOUNCE,
+            zoom_fade: ZOOM_FADE,
+            max_tiles_applied_per_frame: MAX_TILES_APPLIED_PER_FRAME,
+            tile_atlas: false,
+            headless: false,
+            background_color: None,
+            #[cfg(feature = "debug_draw")]
+            show_tile_labels: false,
+            require_user_agent: false,
+        }
+    }
+}
+
+impl MapPlugin {
+    /// Map plugin showing the tiles of a well-known tile server as the base layer, see [`TilePreset`].
+    ///
+    /// The preset is stored in `base_layer`, adjust settings like the headers there.
+    pub fn from_preset(preset: TilePreset) -> Self {
+        Self {
+            base_layer: Some(preset.into()),
+            ..default()
+        }
+    }
+
+    /// Map plugin for a minimal app without a window or camera, see the `headless` field.
+    pub fn headless() -> Self {
+        Self {
+            headless: true,
+            ..default()
+        }
+    }
+}
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        let zoom_offset = self
+            .base_layer
+            .as_ref()
+            .map_or(self.zoom_offset, |config| config.zoom_offset);
+        let tile_size = if self.tile_size.is_finite() && self.tile_size > 0.0 {
+            self.tile_size
+        } else {
+            TILE_SIZE
+        };
+        let (zoom, target_scale) = match self.initial_scale {
+            Some(scale) if scale.is_finite() && scale > 0.0 => {
+                let scale = scale.max(MIN_ORTHO_SCALE);
+                (scale_to_zoom(scale, zoom_offset, tile_size), scale)
+            }
+            _ => {
+                let scale = initial_zoom_scale(self.initial_zoom, zoom_offset, tile_size);
+                (scale_to_zoom(scale, zoom_offset, tile_size), scale)
+            }
+        };
+        let initial_mercator = self
+            .initial_center
+            .as_dvec2()
+            .lonlat_to_mercator()
+            .extend(1.0);
+        let origin = LocalOrigin::new(initial_mercator);
+        if self.max_cached_tiles < MIN_CACHED_TILES {
+            warn!(
+                "max_cached_tiles ({}) is too low, using {} instead",
+                self.max_cached_tiles, MIN_CACHED_TILES
+            );
+        }
+
+        #[cfg(not(feature = "bevy_pancam"))]
+        let camera_translation = initial_mercator.mercator_to_local(&origin).as_vec3();
+
+        let base_layer = self.base_layer.clone().unwrap_or_else(|| TileFetchConfig {
+            url_template: self.tile_source.clone(),
+            headers: self.headers.iter().cloned().collect(),
+            cache_directory: self.cache_directory.clone(),
+            cache_enabled: self.cache_enabled,
+            reverse_y: self.reverse_y,
+            zoom_offset: self.zoom_offset,
+            cache_extension: "png".to_string(),
+            custom_source: self.custom_source.clone(),
+            opacity: self.tile_opacity,
+            tile_tint: self.tile_tint,
+            tile_pixel_ratio: self.tile_pixel_ratio,
+            tile_size: tile_size as u32,
+            ..default()
+        });
+        for layer in std::iter::once(&base_layer).chain(&self.overlay_layers) {
+            if layer.tile_size as f32 != tile_size {
+                warn!(
+                    "tile layer {} has a tile_size of {} but the map uses {}, WMS and stitched tiles will be scaled",
+                    layer.url_template, layer.tile_size, tile_size
+                );
+            }
+            if layer.uses_default_osm_user_agent() {
+                let message = format!(
+                    "tiles from {} are requested with the default User-Agent, OpenStreetMap may block them. \
+                     Set a User-Agent identifying your application in `headers`, \
+                     see https://operations.osmfoundation.org/policies/tiles/",
+                    layer.url_template
+                );
+                if self.require_user_agent {
+                    panic!("{message}");
+                }
+                warn!("{message}");
+            }
+        }
+        app.insert_resource(base_layer)
+            .insert_resource(OverlayLayers(self.overlay_layers.clone()))
+            .insert_resource(CurrentZoom(zoom))
+            .insert_resource(TileStreamingConfig {
+                max_cached_tiles: self.max_cached_tiles.max(MIN_CACHED_TILES),
+                zoom_distance_factor: self.zoom_distance_factor,
+                max_tile_distance: self.max_tile_distance,
+                zoom_debounce: self.zoom_debounce,
+                zoom_fade: self.zoom_fade,
+                max_tiles_applied_per_frame: self.max_tiles_applied_per_frame.max(1),
+                tile_size,
+                tile_atlas: self.tile_atlas,
+            })
+            .init_resource::<TileFetcher>()
+            .init_resource::<TileServerHealth>()
+            .init_resource::<PendingCacheClears>()
+            .add_plugins((tile_textures_plugin, tile_atlas_plugin))
+            .insert_resource(origin)
+            .add_systems(
+                Update,
+                (
+                    reload_tiles_after_cache_clear,
+                    crate::animate::animate_mercator_coords,
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    (
+                        sync_added_mercator_coords,
+                        init_display_size.after(sync_added_mercator_coords),
+                        sync_changed_mercator_coords,
+                    )
+                        .in_set(GeoTilesSet::SyncCoords),
+                    crate::marker::cull_outside_viewport
+                        .after(CameraUpdateSystems)
+                        .before(VisibilitySystems::VisibilityPropagate),
+                    queue_tile_downloads,
+                    // tile textures need the image assets, which a minimal headless app may not have
+                    apply_tile_fetch_results.run_if(resource_exists::<Assets<Image>>),
+                ),
+            )
+            .configure_sets(
+                PostUpdate,
+                GeoTilesSet::SyncCoords.before(TransformSystems::Propagate),
+            )
+            .init_resource::<ExistingTilesSet>()
+            .init_resource::<TileEntities>()
+            .add_observer(handle_zoom_level)
+            .add_observer(tile_inserted)
+            .add_observer(tile_replaced)
+            .add_observer(keep_display_size)
+            .add_observer(update_locals_with_coords_on_origin_change)
+            .add_observer(recenter_origin)
+            .add_observer(clear_tile_cache);
+
+        if self.headless {
+            return;
+        }
+
+        #[cfg(feature = "bevy_pancam")]
+        let app = app.add_plugins(PanCamPlugin);
+
+        #[cfg(not(feature = "bevy_pancam"))]
+        let app = app.add_plugins(pancam_plugin);
+
+        #[cfg(feature = "shapes")]
+        let app = app.add_plugins(crate::shapes::shapes_plugin);
+
+        #[cfg(feature = "mvt")]
+        let app = app.add_plugins(crate::vector_tiles::vector_tiles_plugin);
+
+        #[cfg(feature = "graticule")]
+        let app = app.add_plugins(crate::graticule::graticule_plugin);
+
+        #[cfg(feature = "scale_bar")]
+        let app = app.add_plugins(crate::scale_bar::scale_bar_plugin);
+
+        #[cfg(feature = "debug_draw")]
+        app.insert_resource(ShowTileLabels(self.show_tile_labels));
+
+        let clear_color = self
+            .background_color
+            .map_or(ClearColorConfig::Default, ClearColorConfig::Custom);
+        app.init_resource::<MapBearing>()
+            .init_resource::<RecenterPolicy>()
+            .init_resource::<PointerGeoPosition>()
+            .add_systems(
+                Startup,
+                (move |mut commands: Commands| {
+                    commands
+                        .spawn((
+                            Camera2d,
+                            Camera {
+                                clear_color,
+                                ..default()
+                            },
+                            RenderTarget::default(),
+                            #[cfg(feature = "bevy_pancam")]
+                            Projection::Orthographic(OrthographicProjection {
+                                scale: target_scale,
+                                ..OrthographicProjection::default_2d()
+                            }),
+                            #[cfg(not(feature = "bevy_pancam"))]
+                            SmoothZoom::new(target_scale),
+                            #[cfg(not(feature = "bevy_pancam"))]
+                            PanInertia::default(),
+                            MainCam,
+                            LocalSpace,
+                            #[cfg(feature = "bevy_pancam")]
+                            PanCam::default(),
+                            #[cfg(not(feature = "bevy_pancam"))]
+                            Transform::from_translation(camera_translation)
+                                .with_scale(Vec3::splat(0.01)),
+                            Zoom(zoom),
+                        ))
+                        .with_related_entities::<ZoomOf>(|rel_c| {
+                            for z in ZOOM_RANGE {
+                                rel_c.spawn((
+                                    Zoom(z),
+                                    ZoomFade::default(),
+                                    Transform::default(),
+                                    Visibility::Inherited,
+                                    LocalSpace,
+                                ));
+                            }
+                        });
+                },),
+            )
+            .add_systems(
+                Update,
+                (
+                    apply_map_bearing,
+                    fade_zoom_levels,
+                    (
+                        crate::camera_follow::follow_target.before(update_local_origin),
+                        update_local_origin,
+                    )
+                        .in_set(GeoTilesSet::Origin),
+                    update_pointer_geo_position.after(GeoTilesSet::Origin),
+                    #[cfg(feature = "debug_draw")]
+                    debug_draw,
+                    #[cfg(feature = "debug_draw")]
+                    update_tile_labels,
+                    spawn_new_tiles,
+                    despawn_old_tiles,
+                    #[cfg(feature = "bevy_pancam")]
+                    handle_pancam_zoom,
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    spawn_tiles_after_resize
+                        .after(CameraUpdateSystems)
+                        .before(queue_tile_downloads)
+                        .run_if(on_message::<WindowResized>),
+                    (
+                        sync_world_copy_instances,
+                        (
+                            copy_to_world_copies::<Sprite>,
+                            copy_to_world_copies::<Anchor>,
+                        ),
+                    )
+                        .chain()
+                        .after(GeoTilesSet::SyncCoords)
+                        .before(TransformSystems::Propagate),
+                ),
+            );
+    }
+}
+
+/// System sets of the map, e.g. to run your own systems before or after the coordinate sync.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GeoTilesSet {
+    /// Moves the camera along with [`CameraFollow`](crate::CameraFollow) and the [`LocalOrigin`] along with the camera, in `Update`.
+    /// Systems reading the camera position or converting coordinates with the origin should run after it.
+    Origin,
+    /// Updates the `Transform` of entities with added or changed [`MercatorCoords`], in `PostUpdate`
+    /// before the transform propagation. Change `MercatorCoords` before it to see the change in the same frame.
+    SyncCoords,
+}
+
+/// Settings for loading, unloading and showing tiles, see [`MapPlugin`] for details.
+#[derive(Resource, Debug, Clone)]
+pub struct TileStreamingConfig {
+    pub max_cached_tiles: usize,
+    pub zoom_distance_factor: u32,
+    pub max_tile_distance: Option<u32>,
+    pub zoom_debounce: Duration,
+    pub zoom_fade: Duration,
+    pub max_tiles_applied_per_frame: usize,
+    pub tile_size: f32,
+    pub tile_atlas: bool,
+}
+
+impl Default for TileStreamingConfig {
+    fn default() -> Self {
+        Self {
+            max_cached_tiles: KEEP_UNUSED_TILES,
+            zoom_distance_factor: ZOOM_DISTANCE_FACTOR,
+            max_tile_distance: None,
+            zoom_debounce: ZOOM_DEBOUNCE,
+            zoom_fade: ZOOM_FADE,
+            max_tiles_applied_per_frame: MAX_TILES_APPLIED_PER_FRAME,
+            tile_size: TILE_SIZE,
+            tile_atlas: false,
+        }
+    }
+}
+
+/// The zoom level of the map view
+#[derive(Component)]
+#[relationship(relationship_target = ZoomLevels)]
+pub(crate) struct ZoomOf(Entity);
+
+#[derive(Component)]
+#[relationship_target(relationship = ZoomOf, linked_spawn)] // linked_spawn == despawn related
+pub(crate) struct ZoomLevels(Vec<Entity>);
+
+#[derive(Component, Eq, PartialEq)]
+pub(crate) struct Zoom(u8);
+
+/// Opacity of the tiles of a zoom level, faded towards `target` when the zoom level changes
+#[derive(Component, Debug)]
+pub(crate) struct ZoomFade {
+    opacity: f32,
+    target: f32,
+}
+
+impl Default for ZoomFade {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            target: 1.0,
+        }
+    }
+}
+
+impl ZoomFade {
+    /// Current opacity of the tiles of the zoom level
+    pub(crate) fn opacity(&self) -> f32 {
+        self.opacity
+    }
+}
+
+/// The integer zoom level of the tiles currently shown by the [`MainCam`], e.g. to adjust the detail of own data.
+///
+/// Updated whenever the camera zooms, use `Res<CurrentZoom>` with `is_changed()` to react to zoom level changes.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrentZoom(u8);
+
+impl CurrentZoom {
+    pub fn current_zoom(&self) -> u8 {
+        self.0
+    }
+}
+
+#[derive(SystemParam)]
+struct ZoomHelper<'w, 's, M: Component> {
+    cam: Single<'w, 's, (&'static Zoom, &'static ZoomLevels), With<M>>,
+}
+
+impl<'w, 's, M: Component> ZoomHelper<'w, 's, M> {
+    fn level_entity(&self) -> Entity {
+        let index = (self.cam.0.0.saturating_sub(*ZOOM_RANGE.start())) as usize;
+        self.cam.1.iter().nth(index).unwrap()
+    }
+    fn level(&self) -> u8 {
+        self.cam.0.0
+    }
+}
+
+#[derive(Component, Debug)]
+#[component(immutable)]
+#[require(TileLayer, WorldCopy)]
+pub struct Tile(pub TileMathTile);
+
+impl Tile {
+    /// The tile containing the longitude and latitude in degrees at `zoom`, e.g. to find the tile under a position.
+    ///
+    /// Longitudes wrap around the antimeridian and latitudes are clamped to the mercator range.
+    pub fn from_lonlat(lonlat: impl Into<DVec2>, zoom: u8) -> Self {
+        Self::from_mercator(lonlat.into().lonlat_to_mercator(), zoom)
+    }
+
+    /// The tile containing the web mercator position at `zoom`, see [`Tile::from_lonlat`].
+    pub fn from_mercator(mercator: DVec2, zoom: u8) -> Self {
+        let coords = mercator.mercator_to_tile_coords(zoom);
+        Self(TileMathTile {
+            zoom,
+            x: coords.x,
+            y: coords.y,
+        })
+    }
+}
+
+/// Formats the tile as `z/x/y`, with y in the TMS convention used by [`Tile`].
+impl std::fmt::Display for Tile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.0.zoom, self.0.x, self.0.y)
+    }
+}
+
+/// Error returned when parsing a [`Tile`] from a `z/x/y` string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTileError {
+    /// The string doesn't consist of three `/` separated numbers.
+    InvalidFormat(String),
+    /// x or y is outside of the `2^z` tiles of the zoom level.
+    OutOfRange { zoom: u8, x: u32, y: u32 },
+}
+
+impl std::fmt::Display for ParseTileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseTileError::InvalidFormat(input) => {
+                write!(f, "expected a tile as z/x/y, found '{input}'")
+            }
+            ParseTileError::OutOfRange { zoom, x, y } => {
+                write!(f, "tile {zoom}/{x}/{y} is outside of zoom level {zoom}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseTileError {}
+
+/// Parses a tile formatted as `z/x/y`, the inverse of the [`Display`](std::fmt::Display) implementation.
+impl std::str::FromStr for Tile {
+    type Err = ParseTileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseTileError::InvalidFormat(s.to_string());
+        let mut parts = s.trim().split('/');
+        let (Some(zoom), Some(x), Some(y), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let zoom: u8 = zoom.parse().map_err(|_| invalid())?;
+        let x: u32 = x.parse().map_err(|_| invalid())?;
+        let y: u32 = y.parse().map_err(|_| invalid())?;
+        // 2^32 tiles per row don't fit into the coordinates anymore
+        if zoom >= 32 || x >= 1 << zoom || y >= 1 << zoom {
+            return Err(ParseTileError::OutOfRange { zoom, x, y });
+        }
+        Ok(Tile(TileMathTile { zoom, x, y }))
+    }
+}
+
+/// Index of the tile layer a [`Tile`] belongs to, `0` is the base layer.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[component(immutable)]
+pub struct TileLayer(pub usize);
+
+/// Horizontal copy of the world a [`Tile`] is displayed in.
+///
+/// The map repeats horizontally across the antimeridian: `0` is the main world,
+/// `1` the copy east of it and `-1` the copy west of it.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[component(immutable)]
+pub struct WorldCopy(pub i32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct TileKey {
+    layer: usize,
+    world: i32,
+    tile: TileMathTile,
+}
+
+#[cfg(feature = "bevy_pancam")]
+fn handle_pancam_zoom(
+    mut query: Query<(&PanCam, &Camera, &Projection, &Transform), Changed<Transform>>,
+    mut commands: Commands,
+    mut prev_scale: Local<f32>,
+) {
+    for (_pancam, _camera, projection, _transform) in query.iter_mut() {
+        let proj = match projection {
+            Projection::Orthographic(proj) => proj,
+            _ => continue,
+        };
+        if (proj.scale - *prev_scale).abs() < 0.001 {
+            continue;
+        }
+        *prev_scale = proj.scale;
+        commands.trigger(NewScale(proj.scale));
+    }
+}
+
+fn handle_zoom_level(
+    scale: On<NewScale>,
+    cam: Single<(&mut Zoom, &ZoomLevels), Without<ZoomOf>>,
+    mut current_zoom: ResMut<CurrentZoom>,
+    mut zooms: Query<
+        (&Zoom, &mut Transform, &mut Visibility, &mut ZoomFade),
+        (With<ZoomOf>, Without<ZoomLevels>),
+    >,
+    tile_fetch_config: Res<TileFetchConfig>,
+    streaming: Res<TileStreamingConfig>,
+) {
+    let (mut zoom, levels) = cam.into_inner();
+    // https://www.desmos.com/calculator/dkbfdjvcfx
+    let current_scale: f32 = scale.event().0;
+    zoom.0 = scale_to_zoom(
+        current_scale,
+        tile_fetch_config.zoom_offset,
+        streaming.tile_size,
+    );
+    current_zoom.set_if_neq(CurrentZoom(zoom.0));
+    for e in levels.iter() {
+        let (level, mut tr, mut vis, mut fade) = zooms.get_mut(e).unwrap();
+        // levels are hidden by fade_zoom_levels once they are faded out
+        if level.0 == zoom.0 {
+            *vis = Visibility::Inherited;
+            fade.target = 1.0;
+            tr.translation.z = -1.0;
+        } else if level.0 == zoom.0.saturating_sub(1) {
+            *vis = Visibility::Inherited;
+            fade.target = 1.0;
+            tr.translation.z = -1.2;
+        } else if level.0 == zoom.0.saturating_add(1) {
+            *vis = Visibility::Inherited;
+            fade.target = 1.0;
+            tr.translation.z = -1.5;
+        } else {
+            fade.target = 0.0;
+            tr.translation.z = -2.0;
+        }
+    }
+}
+
+fn fade_zoom_levels(
+    time: Res<Time>,
+    streaming: Res<TileStreamingConfig>,
+    fetcher: Res<TileFetcher>,
+    mut levels: Query<(&mut ZoomFade, &mut Visibility, Option<&Children>), With<ZoomOf>>,
+    mut tiles: Query<(&TileLayer, &mut Sprite)>,
+) {
+    let step = if streaming.zoom_fade.is_zero() {
+        1.0
+    } else {
+        time.delta_secs() / streaming.zoom_fade.as_secs_f32()
+    };
+    for (mut fade, mut vis, children) in levels.iter_mut() {
+        if fade.opacity == fade.target {
+            continue;
+        }
+        let diff = fade.target - fade.opacity;
+        fade.opacity += diff.clamp(-step, step);
+        if fade.opacity <= 0.0 {
+            *vis = Visibility::Hidden;
+        }
+        for child in children.into_iter().flatten() {
+            if let Ok((layer, mut sprite)) = tiles.get_mut(*child) {
+                let base_alpha = fetcher.layer_config(layer.0).sprite_color().alpha();
+                sprite.color.set_alpha(base_alpha * fade.opacity);
+            }
+        }
+    }
+}
+
+/// Z coordinate of the tiles of `layer` within their zoom level.
+///
+/// Tiles in a page of the tile atlas are moved slightly towards the camera by page, so the tiles of a page are drawn
+/// one after another and batched instead of alternating with tiles of other pages.
+pub(crate) fn tile_z(layer: usize, atlas_page: Option<usize>) -> f32 {
+    let page_offset = atlas_page.map_or(0.0, |page| {
+        (page % ATLAS_Z_PAGES + 1) as f32 * LAYER_Z_STEP / (ATLAS_Z_PAGES + 1) as f32
+    });
+    -1.0 + layer as f32 * LAYER_Z_STEP + page_offset
+}
+
+fn new_tile(key: TileKey, origin: &LocalOrigin) -> impl Bundle {
+    //let tile_coord_limit = (2 as u32).pow(tile.zoom as u32) - 1;
+
+    let TileKey { layer, world, tile } = key;
+    let z = tile_z(layer, None);
+    let world_offset = DVec2::new(world as f64 * 2.0 * WEB_MERCATOR_EXTENT, 0.0);
+    let mut mercator_bounds = tile_to_mercator_aabb(tile);
+    mercator_bounds.min += world_offset;
+    mercator_bounds.max += world_offset;
+    let mercator_center = mercator_bounds.center().extend(z as f64);
+    let local_bounds = mercator_bounds.mercator_to_local(origin);
+    let translation = local_bounds.center().extend(z);
+    let scale = (local_bounds.half_size() * 2.0).extend(1.0);
+
+    (
+        LocalSpace,
+        MercatorCoords::from_vec(mercator_center),
+        Transform::from_translation(translation).with_scale(scale),
+        GlobalTransform::default(),
+        Visibility::Inherited,
+        InheritedVisibility::default(),
+        Tile(tile),
+        TileLayer(layer),
+        WorldCopy(world),
+    )
+}
+
+#[derive(Resource, Debug, Default)]
+struct ExistingTilesSet(HashSet<TileKey>);
+
+/// Looks up the spawned entity of a tile, e.g. to attach a custom overlay to a specific tile.
+///
+/// Tiles use TMS-style y coordinates (origin bottom-left), see [`Tile`].
+#[derive(Resource, Debug, Default)]
+pub struct TileEntities(HashMap<TileKey, Entity>);
+
+impl TileEntities {
+    /// Entity of `tile` in the base layer of the main world.
+    pub fn get(&self, tile: TileMathTile) -> Option<Entity> {
+        self.get_in(0, 0, tile)
+    }
+
+    /// Entity of `tile` in tile layer `layer` (see [`TileLayer`]) and world copy `world` (see [`WorldCopy`]).
+    pub fn get_in(&self, layer: usize, world: i32, tile: TileMathTile) -> Option<Entity> {
+        self.0.get(&TileKey { layer, world, tile }).copied()
+    }
+}
+
+// use component lifecycle events to keep the ExistingTilesSet up to date
+// https://docs.rs/bevy/latest/bevy/ecs/lifecycle/index.html
+fn tile_inserted(
+    insert: On<Insert, Tile>,
+    query: Query<(&Tile, &TileLayer, &WorldCopy)>,
+    mut existing: ResMut<ExistingTilesSet>,
+    mut entities: ResMut<TileEntities>,
+) {
+    let (tile, layer, world) = query.get(insert.entity).unwrap();
+    let key = TileKey {
+        layer: layer.0,
+        world: world.0,
+        tile: tile.0,
+    };
+    existing.0.insert(key);
+    entities.0.insert(key, insert.entity);
+}
+
+fn tile_replaced(
+    replace: On<Replace, Tile>,
+    query: Query<(&Tile, &TileLayer, &WorldCopy)>,
+    mut existing: ResMut<ExistingTilesSet>,
+    mut entities: ResMut<TileEntities>,
+) {
+    let (tile, layer, world) = query.get(replace.entity).unwrap();
+    let key = TileKey {
+        layer: layer.0,
+        world: world.0,
+        tile: tile.0,
+    };
+    existing.0.remove(&key);
+    // a tile spawned again before the old entity was despawned keeps the new entity
+    if entities.0.get(&key) == Some(&replace.entity) {
+        entities.0.remove(&key);
+    }
+}
+
+/// Local translation of an entity with [MercatorCoords].
+///
+/// Tiles are placed in their own world copy, everything else is placed on the world copy closest to the local origin
+/// and shown on the other visible world copies by its [`WorldCopyInstance`](crate::WorldCopyInstance) children.
+pub(crate) fn coords_to_local(
+    coords: &MercatorCoords,
+    is_tile: bool,
+    origin: &LocalOrigin,
+) -> Vec3 {
+    let mercator = if is_tile {
+        coords.0
+    } else {
+        nearest_world_copy(coords.0, origin.mercator_origin().x)
+    };
+    mercator.mercator_to_local(origin).as_vec3()
+}
+
+/// Draws entities with [`MercatorCoords`] further south in front of those further north, like a pseudo-2.5D map,
+/// e.g. for upright marker sprites that overlap.
+///
+/// A small offset derived from the mercator y coordinate is added to the z coordinate of the [`MercatorCoords`],
+/// so entities with different z values still keep their order. Takes effect the next time the coordinates change.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct SortByLatitude;
+
+// z offset per meter north of the local origin, small enough to stay within a z layer
+// across the recenter distance while still ordering markers a meter apart
+const LATITUDE_Z_PER_METER: f32 = 1e-6;
+
+fn mercator_coords_translation(
+    coords: &MercatorCoords,
+    is_tile: bool,
+    sort_by_latitude: bool,
+    origin: &LocalOrigin,
+) -> Vec3 {
+    let mut translation = coords_to_local(coords, is_tile, origin);
+    if sort_by_latitude {
+        translation.z -= translation.y * LATITUDE_Z_PER_METER;
+    }
+    translation
+}
+
+fn sync_added_mercator_coords(
+    mut commands: Commands,
+    origin: Res<LocalOrigin>,
+    mut with_transform: Query<
+        (
+            Entity,
+            &MercatorCoords,
+            &mut Transform,
+            Has<Tile>,
+            Has<SortByLatitude>,
+        ),
+        (Added<MercatorCoords>, With<Transform>),
+    >,
+    added_without_transform: Query<
+        (Entity, &MercatorCoords, Has<Tile>, Has<SortByLatitude>),
+        (Added<MercatorCoords>, Without<Transform>),
+    >,
+) {
+    for (entity, coords, mut transform, is_tile, sort) in with_transform.iter_mut() {
+        transform.translation = mercator_coords_translation(coords, is_tile, sort, &origin);
+        commands.entity(entity).insert(LocalSpace);
+    }
+
+    for (entity, coords, is_tile, sort) in added_without_transform.iter() {
+        let translation = mercator_coords_translation(coords, is_tile, sort, &origin);
+        commands.entity(entity).insert((
+            LocalSpace,
+            Transform::from_translation(translation),
+            GlobalTransform::default(),
+        ));
+    }
+}
+
+fn sync_changed_mercator_coords(
+    origin: Res<LocalOrigin>,
+    mut query: Query<
+        (
+            &MercatorCoords,
+            &mut Transform,
+            Has<Tile>,
+            Has<SortByLatitude>,
+        ),
+        Changed<MercatorCoords>,
+    >,
+) {
+    for (coords, mut transform, is_tile, sort) in query.iter_mut() {
+        transform.translation = mercator_coords_translation(coords, is_tile, sort, &origin);
+    }
+}
+
+/// Triggered when the [`LocalOrigin`] is recentered. Automatic recentering happens in [`Update`].
+///
+/// Entities in [`LocalSpace`] and the main camera are moved by the plugin, observe this event
+/// to keep other data stored in local coordinates in sync.
+#[derive(Event, Debug, Clone)]
+pub struct LocalOriginUpdated {
+    /// Shift of the origin in local coordinates, subtract it from local positions to keep them in place.
+    pub local_delta: Vec3,
+    /// Shift of the origin in web mercator coordinates.
+    pub mercator_delta: DVec3,
+}
+
+/// Triggered when tiles enter or leave the view, at most once per frame.
+///
+/// Tiles are given at the current zoom level in TMS coordinates (see [`tile_to_mercator_aabb`]), regardless of the
+/// number of tile layers and world copies they are shown in. Useful to load external data per tile.
+#[derive(Event, Debug, Clone)]
+pub struct VisibleTilesChanged {
+    pub added: Vec<TileMathTile>,
+    pub removed: Vec<TileMathTile>,
+}
+
+/// Recenters the [`LocalOrigin`] on `to_mercator` immediately instead of waiting for the camera to drift away,
+/// e.g. after moving the camera programmatically over a large distance.
+///
+/// All entities in [`LocalSpace`] and the main camera are moved accordingly. Only x and y of `to_mercator` are used,
+/// triggering it with the current origin does nothing.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct RecenterOrigin {
+    pub to_mercator: DVec3,
+}
+
+/// Decides when the [`LocalOrigin`] is recentered automatically, insert it as a resource to replace the default.
+///
+/// Recentering moves all entities in [`LocalSpace`], some apps want to avoid it at certain times,
+/// e.g. during animations. With [`RecenterPolicy::Manual`], own systems can trigger [`RecenterOrigin`] when convenient,
+/// e.g. on a timer or once the camera is idle.
+#[derive(Resource, Clone, Default)]
+pub enum RecenterPolicy {
+    /// Recenter once the camera is further than [`LocalOrigin::recenter_distance`] from the origin.
+    #[default]
+    Distance,
+    /// Never recenter automatically.
+    Manual,
+    /// Recenter on the camera whenever the function returns true,
+    /// it is called every frame with the camera position in local coordinates.
+    Custom(Arc<dyn Fn(Vec2, &LocalOrigin) -> bool + Send + Sync>),
+}
+
+impl std::fmt::Debug for RecenterPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Distance => write!(f, "Distance"),
+            Self::Manual => write!(f, "Manual"),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+fn recenter_origin(
+    event: On<RecenterOrigin>,
+    mut commands: Commands,
+    mut origin: ResMut<LocalOrigin>,
+    mut cam_query: Query<&mut Transform, With<MainCam>>,
+) {
+    let current = origin.mercator_origin();
+    let mercator_delta = DVec3::new(
+        event.to_mercator.x - current.x,
+        event.to_mercator.y - current.y,
+        0.0,
+    );
+    if mercator_delta == DVec3::ZERO {
+        return;
+    }
+    shift_local_origin(&mut commands, &mut origin, &mut cam_query, mercator_delta);
+}
+
+fn shift_local_origin(
+    commands: &mut Commands,
+    origin: &mut LocalOrigin,
+    cam_query: &mut Query<&mut Transform, With<MainCam>>,
+    mercator_delta: DVec3,
+) {
+    origin.shift_mercator_origin(mercator_delta);
+    let local_delta = mercator_delta.as_vec3();
+
+    for mut cam in cam_query.iter_mut() {
+        cam.translation -= local_delta;
+    }
+
+    commands.trigger(LocalOriginUpdated {
+        local_delta,
+        mercator_delta,
+    });
+}
+
+fn update_local_origin(
+    mut commands: Commands,
+    policy: Res<RecenterPolicy>,
+    mut origin: ResMut<LocalOrigin>,
+    mut cam_query: Query<&mut Transform, With<MainCam>>,
+) {
+    // the camera may be missing, e.g. during teardown
+    let Ok(camera) = cam_query.single() else {
+        return;
+    };
+    let camera_offset = camera.translation.truncate();
+
+    let recenter = match &*policy {
+        RecenterPolicy::Distance => (camera_offset.length() as f64) > origin.recenter_distance(),
+        RecenterPolicy::Manual => false,
+        RecenterPolicy::Custom(should_recenter) => should_recenter(camera_offset, &origin),
+    };
+    if !recenter || camera_offset == Vec2::ZERO {
+        return;
+    }
+
+    let delta = Vec3::new(camera_offset.x, camera_offset.y, 0.0);
+    shift_local_origin(&mut commands, &mut origin, &mut cam_query, delta.as_dvec3());
+}
+
+fn update_locals_with_coords_on_origin_change(
+    event: On<LocalOriginUpdated>,
+    origin: Res<LocalOrigin>,
+    mut locals: Query<
+        &mut Transform,
+        (
+            With<LocalSpace>,
+            Without<MainCam>,
+            Without<Zoom>,
+            Without<MercatorCoords>,
+        ),
+    >,
+    mut locals_with_coords: Query<
+        (
+            &MercatorCoords,
+            &mut Transform,
+            Has<Tile>,
+            Has<SortByLatitude>,
+        ),
+        (With<LocalSpace>, Without<MainCam>, Without<Zoom>),
+    >,
+) {
+    let delta = event.local_delta;
+    for mut transform in locals.iter_mut() {
+        transform.translation -= delta;
+    }
+    // recompute from the mercator coordinates, this also moves entities to the closest world copy
+    for (coords, mut transform, is_tile, sort) in locals_with_coords.iter_mut() {
+        transform.translation = mercator_coords_translation(coords, is_tile, sort, &origin);
+    }
+}
+
+/// Marker component to keep the display size of an entity constant when zooming in/out
+///
+/// Changes the scale of the transform based on the zoom level, so the entity keeps its size on screen.
+///
+/// The entity is scaled around its [`MercatorCoords`], so the point of a sprite given by its [`Anchor`](bevy::sprite::Anchor)
+/// stays at the geographic position while zooming. For pin-shaped markers use `Anchor::BOTTOM_CENTER`
+/// (as [`GeoMarker::new`](crate::GeoMarker::new) does) to keep the tip of the pin at the coordinate.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct KeepDisplaySize;
+
+fn display_scale(camera_scale: f32) -> Vec3 {
+    Vec2::splat(camera_scale * 0.1).extend(1.0)
+}
+
+fn keep_display_size(
+    scale: On<NewScale>,
+    mut query: Query<&mut Transform, (With<MercatorCoords>, With<KeepDisplaySize>)>,
+) {
+    let scale = display_scale(scale.event().0);
+    for mut tr in query.iter_mut() {
+        tr.scale = scale;
+    }
+}
+
+// entities spawned after the last zoom change would keep their initial scale until the next one
+fn init_display_size(
+    camera: Option<Single<&Projection, With<MainCam>>>,
+    mut query: Query<
+        &mut Transform,
+        (
+            With<MercatorCoords>,
+            With<KeepDisplaySize>,
+            Or<(Added<KeepDisplaySize>, Added<Transform>)>,
+        ),
+    >,
+) {
+    let Some(Projection::Orthographic(proj)) = camera.as_deref() else {
+        return;
+    };
+    let scale = display_scale(proj.scale);
+    for mut tr in query.iter_mut() {
+        tr.scale = scale;
+    }
+}
+
+fn spawn_new_tiles(
+    mut commands: Commands,
+    zoom: ZoomHelper<MainCam>,
+    view: ViewportConv<MainCam>,
+    existing_tiles: Res<ExistingTilesSet>,
+    origin: Res<LocalOrigin>,
+    fetcher: Res<TileFetcher>,
+    streaming: Res<TileStreamingConfig>,
+    time: Res<Time>,
+    mut visible_tiles: Local<HashSet<TileMathTile>>,
+    mut zoom_changed: Local<Option<(u8, Duration)>>,
+) -> Result<()> {
+    // wait for the zoom level to settle, the tiles of the previous level stay visible meanwhile
+    match *zoom_changed {
+        Some((level, _)) if level != zoom.level() => {
+            *zoom_changed = Some((zoom.level(), time.elapsed()));
+            return Ok(());
+        }
+        Some((_, since)) if time.elapsed() - since < streaming.zoom_debounce => return Ok(()),
+        Some(_) => {}
+        None => *zoom_changed = Some((zoom.level(), Duration::ZERO)),
+    }
+    // no viewport yet or the window is minimized, keep the tiles until there is something to show
+    let Ok(bbox) = view.visible_mercator_aabb() else {
+        return Ok(());
+    };
+    let current_visible = spawn_missing_tiles(
+        &mut commands,
+        &zoom,
+        &bbox,
+        &existing_tiles,
+        &fetcher,
+        &origin,
+    );
+
+    if current_visible != *visible_tiles {
+        commands.trigger(VisibleTilesChanged {
+            added: current_visible
+                .difference(&visible_tiles)
+                .copied()
+                .collect(),
+            removed: visible_tiles
+                .difference(&current_visible)
+                .copied()
+                .collect(),
+        });
+        *visible_tiles = current_visible;
+    }
+    Ok(())
+}
+
+/// Spawns the tiles at the new edges of the view right after the window was resized.
+///
+/// The camera only picks up the new window size in `PostUpdate`, so [`spawn_new_tiles`] would still see the
+/// old viewport and leave the new space empty for a frame.
+fn spawn_tiles_after_resize(
+    mut commands: Commands,
+    zoom: ZoomHelper<MainCam>,
+    view: ViewportConv<MainCam>,
+    existing_tiles: Res<ExistingTilesSet>,
+    origin: Res<LocalOrigin>,
+    fetcher: Res<TileFetcher>,
+) {
+    let Ok(bbox) = view.visible_mercator_aabb() else {
+        return;
+    };
+    spawn_missing_tiles(
+        &mut commands,
+        &zoom,
+        &bbox,
+        &existing_tiles,
+        &fetcher,
+        &origin,
+    );
+}
+
+/// Spawns the tiles of all layers covering `bbox` that don't exist yet, returns the tiles covering `bbox`.
+fn spawn_missing_tiles(
+    commands: &mut Commands,
+    zoom: &ZoomHelper<MainCam>,
+    bbox: &MercatorAabb2d,
+    existing_tiles: &ExistingTilesSet,
+    fetcher: &TileFetcher,
+    origin: &LocalOrigin,
+) -> HashSet<TileMathTile> {
+    let mut current_view_tiles = HashSet::new();
+    let mut current_visible = HashSet::new();
+    for (world, part) in split_world_copies(bbox) {
+        let tile_bounds = part.mercator_to_tile_coords(zoom.level());
+        current_visible.extend(TileIterator::new(
+            zoom.level(),
+            tile_bounds.x_range(),
+            tile_bounds.y_range(),
+        ));
+        // layers without tiles at this zoom level get no tile entities at all
+        let layers = (0..fetcher.layer_count())
+            .filter(|layer| fetcher.layer_config(*layer).serves_zoom(zoom.level()));
+        for layer in layers {
+            current_view_tiles.extend(
+                TileIterator::new(zoom.level(), tile_bounds.x_range(), tile_bounds.y_range())
+                    .map(|tile| TileKey { layer, world, tile }),
+            );
+        }
+    }
+    let diff = current_view_tiles.difference(&existing_tiles.0);
+    for key in diff {
+        commands
+            .entity(zoom.level_entity())
+            .with_child(new_tile(*key, origin));
+    }
+    current_visible
+}
+
+fn despawn_old_tiles(
+    mut commands: Commands,
+    zoom: ZoomHelper<MainCam>,
+    view: ViewportConv<MainCam>,
+    tiles: Query<(Entity, &Tile, &WorldCopy, &ViewVisibility)>,
+    streaming: Res<TileStreamingConfig>,
+) -> Result<()> {
+    let zoom_distance_factor = streaming.zoom_distance_factor;
+    let tiles = tiles.iter().filter(|(_, _, _, vis)| !vis.get());
+    if tiles.clone().count() < streaming.max_cached_tiles && streaming.max_tile_distance.is_none() {
+        return Ok(());
+    }
+    let mut tiles = tiles.collect::<Vec<_>>();
+    let Ok(center_mercator) = view.viewport_center_mercator() else {
+        return Ok(());
+    };
+    let center = center_mercator.mercator_to_tile_coords(zoom.level());
+    let center_world =
+        ((center_mercator.x + WEB_MERCATOR_EXTENT) / (2.0 * WEB_MERCATOR_EXTENT)).floor() as i64;
+    let me = I64Vec3::new(
+        center.x as i64 + (center_world << zoom.level()),
+        center.y as i64,
+        (zoom.level() as u32).saturating_mul(zoom_distance_factor) as i64,
+    );
+    if let Some(max_distance) = streaming.max_tile_distance {
+        let level = zoom.level();
+        tiles.retain(|(e, tile, world, _)| {
+            // position in tiles of the current zoom level, including the world copy
+            let to_level = |v: i64| {
+                if tile.0.zoom >= level {
+                    v >> (tile.0.zoom - level)
+                } else {
+                    v << (level - tile.0.zoom)
+                }
+            };
+            let x = to_level(tile.0.x as i64 + ((world.0 as i64) << tile.0.zoom));
+            let y = to_level(tile.0.y as i64);
+            let distant = (x - me.x).abs().max((y - me.y).abs()) > max_distance as i64;
+            if distant {
+                commands.entity(*e).despawn();
+            }
+            !distant
+        });
+        if tiles.len() < streaming.max_cached_tiles {
+            return Ok(());
+        }
+    }
+    // manhattan distance is cheap and good enough. maybe even better for this than euclidian
+    tiles.sort_unstable_by_key(|(_, a, world, _)| {
+        let other = I64Vec3::new(
+            a.0.x as i64 + ((world.0 as i64) << a.0.zoom),
+            a.0.y as i64,
+            (a.0.zoom as u32).saturating_mul(zoom_distance_factor) as i64,
+        );
+        (me - other).abs().element_sum()
+    });
+    for (e, _, _, _) in tiles.iter().skip(streaming.max_cached_tiles) {
+        commands.entity(*e).despawn();
+    }
+    Ok(())
+}
+
+/// Rotates the map view around the viewport center, e.g. for heading-up navigation.
+///
+/// The value is the bearing in degrees clockwise from north that points to the top of the viewport,
+/// `0.0` is the regular north-up view. Tiles, markers and shapes follow the rotation, the graticule labels
+/// stay at the viewport edges and are only accurate without rotation.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct MapBearing(pub f32);
+
+fn apply_map_bearing(bearing: Res<MapBearing>, mut camera: Query<&mut Transform, With<MainCam>>) {
+    if !bearing.is_changed() {
+        return;
+    }
+    for mut transform in camera.iter_mut() {
+        // rotating the camera counter-clockwise turns the map clockwise
+        transform.rotation = Quat::from_rotation_z(-bearing.0.to_radians());
+    }
+}
+
+/// Whether the `z/x/y` coordinate is drawn on every tile, useful for debugging alignment issues.
+#[cfg(feature = "debug_draw")]
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShowTileLabels(pub bool);
+
+/// Marks tiles that have a coordinate label child
+#[cfg(feature = "debug_draw")]
+#[derive(Component, Debug)]
+struct TileLabelled;
+
+#[cfg(feature = "debug_draw")]
+#[derive(Component, Debug)]
+struct TileLabel;
+
+#[cfg(feature = "debug_draw")]
+fn update_tile_labels(
+    mut commands: Commands,
+    show: Res<ShowTileLabels>,
+    unlabelled: Query<(Entity, &Tile), Without<TileLabelled>>,
+    labelled: Query<Entity, With<TileLabelled>>,
+    labels: Query<Entity, With<TileLabel>>,
+) {
+    if show.0 {
+        for (entity, tile) in unlabelled.iter() {
+            // XYZ coordinates as in OpenStreetMap URLs, internal tile rows count from the south
+            let xyz = crate::tile_fetcher::server_tile(&tile.0, false, 0, false);
+            commands.entity(entity).insert(TileLabelled).with_child((
+                TileLabel,
+                Text2d::new(format!("{}/{}/{}", xyz.zoom, xyz.x, xyz.y)),
+                Text2dShadow {
+                    offset: Vec2::new(2.0, -2.0),
+                    ..Default::default()
+                },
+                TextFont::from_font_size(100.0),
+                // relative to the tile size, tiles are displayed at roughly constant size
+                Transform::from_scale(Vec3::ONE / 1024.).with_translation(Vec3::Z),
+            ));
+        }
+    } else if show.is_changed() {
+        for entity in labelled.iter() {
+            commands.entity(entity).remove::<TileLabelled>();
+        }
+        for entity in labels.iter() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Position under a pointer in the different coordinate systems of the map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointerGeo {
+    /// Logical pixels relative to the top left corner of the viewport
+    pub viewport: Vec2,
+    /// Bevy world coordinates relative to the [`LocalOrigin`]
+    pub local: Vec2,
+    pub mercator: DVec2,
+    /// Longitude and latitude in degrees
+    pub lonlat: DVec2,
+}
+
+impl PointerGeo {
+    fn new(
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        pointer_pos: Vec2,
+        origin: &LocalOrigin,
+    ) -> Option<Self> {
+        // logical pixels relative to the viewport, the window scale factor is already applied
+        let viewport = pointer_pos - camera.logical_viewport_rect()?.min;
+        let local = camera
+            .viewport_to_world_2d(camera_transform, viewport)
+            .ok()?;
+        let mercator = local.local_to_mercator(origin);
+        Some(Self {
+            viewport,
+            local,
+            mercator,
+            lonlat: mercator.mercator_to_lonlat(),
+        })
+    }
+}
+
+/// Map position under the primary pointer (the mouse, or the first touch without a mouse), updated every frame,
+/// e.g. for showing the coordinates under the cursor in your own UI.
+///
+/// `None` while the pointer is outside the viewport of the [`MainCam`].
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PointerGeoPosition(pub Option<PointerGeo>);
+
+fn update_pointer_geo_position(
+    camera: Option<Single<(&Camera, &RenderTarget, &GlobalTransform), With<MainCam>>>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    pointers: Query<(&PointerId, &PointerLocation)>,
+    origin: Res<LocalOrigin>,
+    mut position: ResMut<PointerGeoPosition>,
+) {
+    let geo = camera.and_then(|camera| {
+        let (camera, render_target, camera_transform) = *camera;
+        let target = render_target.normalize(primary_window.single().ok())?;
+        let location = pointers
+            .iter()
+            .filter_map(|(id, location)| Some((id, location.location()?)))
+            .filter(|(_, location)| location.target == target)
+            .min_by_key(|(id, _)| !id.is_mouse())
+            .map(|(_, location)| location)?;
+        if !camera.logical_viewport_rect()?.contains(location.position) {
+            return None;
+        }
+        PointerGeo::new(camera, camera_transform, location.position, &origin)
+    });
+    position.set_if_neq(PointerGeoPosition(geo));
+}
+
+/// Position of the debug tooltip in UI units for a pointer at `viewport` logical pixels.
+#[cfg(feature = "debug_draw")]
+fn tooltip_position(viewport: Vec2, ui_scale: f32) -> Vec2 {
+    // UI nodes of the camera are laid out relative to its viewport in logical pixels divided by the UiScale,
+    // the offset from the pointer is in UI units so it doesn't change with the UiScale
+    viewport / ui_scale + Vec2::splat(5.0)
+}
+
+#[cfg(feature = "debug_draw")]
+pub fn debug_draw(
+    mut commands: Commands,
+    camera_query: Query<(Entity, &Camera, &RenderTarget, &GlobalTransform)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    pointers: Query<(Entity, &PointerLocation)>,
+    scale: Res<UiScale>,
+    origin: Res<LocalOrigin>,
+) {
+    for (entity, location) in &pointers {
+        let Some(pointer_location) = &location.location() else {
+            continue;
+        };
+        for (cam_e, camera, _render_target, cam_global_transform) in
+            camera_query.iter().filter(|(_, _, render_target, _)| {
+                render_target
+                    .normalize(primary_window.single().ok())
+                    .is_some_and(|target| target == pointer_location.target)
+            })
+        {
+            let Some(geo) = PointerGeo::new(
+                camera,
+                cam_global_transform,
+                pointer_location.position,
+                &origin,
+            ) else {
+                continue;
+            };
+            let tooltip_pos = tooltip_position(geo.viewport, scale.0);
+
+            let text = format!(
+                "Lat: {}, Lon: {},\n mercator x: {}, mercator y: {},\n local x: {}, local y: {}",
+                geo.lonlat.y,
+                geo.lonlat.x,
+                geo.mercator.x,
+                geo.mercator.y,
+                geo.local.x,
+                geo.local.y
+            );
+
+            commands
+                .entity(entity)
+                .despawn_related::<Children>()
+                .insert((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(tooltip_pos.x),
+                        top: Val::Px(tooltip_pos.y),
+                        padding: UiRect::px(10.0, 10.0, 8.0, 6.0),
+                        ..Default::default()
+                    },
+                    BackgroundColor(Color::BLACK.with_alpha(0.75)),
+                    GlobalZIndex(i32::MAX),
+                    Pickable::IGNORE,
+                    UiTargetCamera(cam_e),
+                    children![(Text::new(text.clone()), TextFont::from_font_size(12.0))],
+                ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_local_origin_without_camera_is_a_noop() {
+        let start = DVec3::new(1_000_000.0, 2_000_000.0, 1.0);
+        let mut app = App::new();
+        app.insert_resource(LocalOrigin::new(start))
+            .init_resource::<RecenterPolicy>()
+            .add_observer(update_locals_with_coords_on_origin_change)
+            .add_systems(Update, update_local_origin);
+        let marker = app
+            .world_mut()
+            .spawn((LocalSpace, Transform::from_xyz(1.0, 2.0, 3.0)))
+            .id();
+
+        app.update();
+        app.update();
+
+        assert_eq!(
+            app.world().resource::<LocalOrigin>().mercator_origin(),
+            start
+        );
+        assert_eq!(
+            app.world().get::<Transform>(marker).unwrap().translation,
+            Vec3::new(1.0, 2.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn tile_display_from_str_round_trip() {
+        for (zoom, x, y) in [
+            (0, 0, 0),
+            (3, 7, 5),
+            (18, 140_000, 170_000),
+            (31, u32::MAX >> 1, 0),
+        ] {
+            let tile = Tile(TileMathTile { zoom, x, y });
+            let text = tile.to_string();
+            assert_eq!(text, format!("{zoom}/{x}/{y}"));
+            assert_eq!(text.parse::<Tile>().unwrap().0, tile.0);
+        }
+        // surrounding whitespace is ignored
+        assert_eq!(
+            " 2/1/3\n".parse::<Tile>().unwrap().0,
+            TileMathTile {
+                zoom: 2,
+                x: 1,
+                y: 3
+            }
+        );
+    }
+
+    #[test]
+    fn tile_from_str_rejects_invalid_input() {
+        for input in [
+            "", "1/2", "1/2/3/4", "a/0/0", "1/-1/0", "1/0/0.5", "256/0/0", "1//0", "1 /0/0",
+        ] {
+            assert_eq!(
+                input.parse::<Tile>().unwrap_err(),
+                ParseTileError::InvalidFormat(input.to_string()),
+                "{input}"
+            );
+        }
+        assert_eq!(
+            "2/4/0".parse::<Tile>().unwrap_err(),
+            ParseTileError::OutOfRange {
+                zoom: 2,
+                x: 4,
+                y: 0
+            }
+        );
+        assert_eq!(
+            "2/0/4".parse::<Tile>().unwrap_err(),
+            ParseTileError::OutOfRange {
+                zoom: 2,
+                x: 0,
+                y: 4
+            }
+        );
+        assert_eq!(
+            "32/0/0".parse::<Tile>().unwrap_err(),
+            ParseTileError::OutOfRange {
+                zoom: 32,
+                x: 0,
+                y: 0
+            }
+        );
+    }
+
+    #[test]
+    fn zoom_to_scale_round_trips() {
+        for zoom_offset in -2..=2 {
+            for tile_size in [128.0, 256.0, 512.0, 1024.0] {
+                for zoom in ZOOM_RANGE {
+                    let scale = zoom_to_scale(zoom, zoom_offset, tile_size);
+                    assert_eq!(
+                        scale_to_zoom(scale, zoom_offset, tile_size),
+                        zoom,
+                        "offset {zoom_offset}, tile size {tile_size}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zoom_to_scale_clamps_to_zoom_range() {
+        assert_eq!(
+            zoom_to_scale(0, 0, TILE_SIZE),
+            zoom_to_scale(1, 0, TILE_SIZE)
+        );
+        assert_eq!(
+            zoom_to_scale(u8::MAX, 0, TILE_SIZE),
+            zoom_to_scale(*ZOOM_RANGE.end(), 0, TILE_SIZE)
+        );
+        assert_eq!(scale_to_zoom(f32::MAX, 0, TILE_SIZE), *ZOOM_RANGE.start());
+        assert_eq!(
+            scale_to_zoom(f32::MIN_POSITIVE, 0, TILE_SIZE),
+            *ZOOM_RANGE.end()
+        );
+    }
+
+    #[test]
+    fn initial_zoom_shows_tiles_two_levels_lower() {
+        for zoom in 3..=18 {
+            let scale = initial_zoom_scale(zoom, 0, TILE_SIZE);
+            assert_eq!(scale_to_zoom(scale, 0, TILE_SIZE), zoom - 2);
+        }
+        // the zoom offset is counted twice, once for the scale and once for the tiles shown
+        let scale = initial_zoom_scale(10, 1, TILE_SIZE);
+        assert_eq!(scale_to_zoom(scale, 1, TILE_SIZE), 6);
+    }
+
+    #[cfg(feature = "debug_draw")]
+    #[test]
+    fn tooltip_position_follows_ui_scale() {
+        let pointer = Vec2::new(400.0, 300.0);
+        assert_eq!(tooltip_position(pointer, 1.0), Vec2::new(405.0, 305.0));
+        // at a UiScale of 2 a UI unit covers two logical pixels
+        let position = tooltip_position(pointer, 2.0);
+        assert_eq!(position, Vec2::new(205.0, 155.0));
+        assert_eq!((position - Vec2::splat(5.0)) * 2.0, pointer);
+        assert_eq!(tooltip_position(pointer, 0.5), Vec2::new(805.0, 605.0));
+    }
+}
@@ -0,0 +1,182 @@
+use bevy::{camera::CameraUpdateSystems, prelude::*, ui::UiSystems};
+
+use crate::{MainCam, ViewportConv};
+
+const METERS_PER_FOOT: f64 = 0.3048;
+const FEET_PER_MILE: f64 = 5280.0;
+
+pub(crate) fn scale_bar_plugin(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        update_scale_bar
+            .after(CameraUpdateSystems)
+            .before(UiSystems::Layout),
+    );
+}
+
+/// Scale bar showing a rounded ground distance for the current zoom level, e.g. "200 m" or "5 km".
+///
+/// Insert this resource to show the scale bar and remove it to hide it.
+#[derive(Resource, Debug, Clone)]
+pub struct ScaleBar {
+    pub position: ScaleBarPosition,
+    pub unit: ScaleBarUnit,
+    /// Maximum width of the bar in logical pixels
+    pub max_width: f32,
+    pub color: Color,
+}
+
+impl Default for ScaleBar {
+    fn default() -> Self {
+        Self {
+            position: ScaleBarPosition::BottomLeft,
+            unit: ScaleBarUnit::Metric,
+            max_width: 120.0,
+            color: Color::BLACK,
+        }
+    }
+}
+
+/// Corner of the viewport the [`ScaleBar`] is placed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleBarPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleBarUnit {
+    /// meters and kilometers
+    Metric,
+    /// feet and miles
+    Imperial,
+}
+
+#[derive(Component, Debug)]
+struct ScaleBarNode;
+
+/// Largest 1, 2 or 5 times a power of ten that is at most `max`.
+fn round_down_nice(max: f64) -> f64 {
+    let magnitude = 10f64.powf(max.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|nice| *nice <= max)
+        .unwrap_or(magnitude)
+}
+
+/// Rounded distance in meters that fits `max_meters` and its label.
+fn scale_bar_distance(max_meters: f64, unit: ScaleBarUnit) -> (f64, String) {
+    match unit {
+        ScaleBarUnit::Metric if max_meters >= 1000.0 => {
+            let km = round_down_nice(max_meters / 1000.0);
+            (km * 1000.0, format!("{km} km"))
+        }
+        ScaleBarUnit::Metric => {
+            let m = round_down_nice(max_meters);
+            (m, format!("{m} m"))
+        }
+        ScaleBarUnit::Imperial => {
+            let max_feet = max_meters / METERS_PER_FOOT;
+            if max_feet >= FEET_PER_MILE {
+                let miles = round_down_nice(max_feet / FEET_PER_MILE);
+                (
+                    miles * FEET_PER_MILE * METERS_PER_FOOT,
+                    format!("{miles} mi"),
+                )
+            } else {
+                let feet = round_down_nice(max_feet);
+                (feet * METERS_PER_FOOT, format!("{feet} ft"))
+            }
+        }
+    }
+}
+
+fn update_scale_bar(
+    mut commands: Commands,
+    scale_bar: Option<Res<ScaleBar>>,
+    view: ViewportConv<MainCam>,
+    camera: Query<Entity, With<MainCam>>,
+    mut bar: Query<(Entity, &mut Node, &mut BorderColor, &Children), With<ScaleBarNode>>,
+    mut texts: Query<(&mut Text, &mut TextColor)>,
+) {
+    let Some(scale_bar) = scale_bar else {
+        for (entity, ..) in bar.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+    let (Ok(meters_per_pixel), Ok(camera_entity)) = (view.meters_per_pixel(), camera.single())
+    else {
+        return;
+    };
+    let (meters, label) = scale_bar_distance(
+        scale_bar.max_width as f64 * meters_per_pixel,
+        scale_bar.unit,
+    );
+    let width = (meters / meters_per_pixel) as f32;
+
+    let (left, right, top, bottom) = match scale_bar.position {
+        ScaleBarPosition::TopLeft => (Val::Px(10.0), Val::Auto, Val::Px(10.0), Val::Auto),
+        ScaleBarPosition::TopRight => (Val::Auto, Val::Px(10.0), Val::Px(10.0), Val::Auto),
+        ScaleBarPosition::BottomLeft => (Val::Px(10.0), Val::Auto, Val::Auto, Val::Px(10.0)),
+        ScaleBarPosition::BottomRight => (Val::Auto, Val::Px(10.0), Val::Auto, Val::Px(10.0)),
+    };
+
+    if let Ok((_, mut node, mut border, children)) = bar.single_mut() {
+        // avoid triggering a UI relayout every frame
+        if node.width != Val::Px(width) {
+            node.width = Val::Px(width);
+        }
+        if scale_bar.is_changed() {
+            node.left = left;
+            node.right = right;
+            node.top = top;
+            node.bottom = bottom;
+            *border = BorderColor::all(scale_bar.color);
+        }
+        for child in children.iter() {
+            if let Ok((mut text, mut color)) = texts.get_mut(child) {
+                if text.0 != label {
+                    text.0 = label.clone();
+                }
+                if scale_bar.is_changed() {
+                    color.0 = scale_bar.color;
+                }
+            }
+        }
+        return;
+    }
+
+    commands.spawn((
+        ScaleBarNode,
+        Node {
+            position_type: PositionType::Absolute,
+            left,
+            right,
+            top,
+            bottom,
+            width: Val::Px(width),
+            border: UiRect {
+                left: Val::Px(2.0),
+                right: Val::Px(2.0),
+                bottom: Val::Px(2.0),
+                top: Val::ZERO,
+            },
+            padding: UiRect::horizontal(Val::Px(4.0)),
+            ..default()
+        },
+        BorderColor::all(scale_bar.color),
+        BackgroundColor(Color::WHITE.with_alpha(0.6)),
+        GlobalZIndex(i32::MAX - 1),
+        Pickable::IGNORE,
+        UiTargetCamera(camera_entity),
+        children![(
+            Text::new(label),
+            TextFont::from_font_size(12.0),
+            TextColor(scale_bar.color),
+        )],
+    ));
+}
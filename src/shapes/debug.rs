@@ -0,0 +1,71 @@
+use bevy::{mesh::VertexAttributeValues, prelude::*};
+
+use crate::shapes::{polygon::GeoPolygon, polyline::GeoPolyline};
+
+const WIREFRAME_COLOR: Color = Color::srgb(0.0, 1.0, 1.0);
+const BOUNDING_BOX_COLOR: Color = Color::srgb(1.0, 0.5, 0.0);
+
+pub(crate) fn shape_debug_plugin(app: &mut App) {
+    app.init_resource::<ShapeDebugDraw>().add_systems(
+        PostUpdate,
+        draw_shape_debug
+            .after(TransformSystems::Propagate)
+            .run_if(|debug: Res<ShapeDebugDraw>| debug.wireframes || debug.bounding_boxes),
+    );
+}
+
+/// Draws the triangles or bounding boxes of the [`GeoPolygon`] and [`GeoPolyline`] meshes with gizmos,
+/// useful for debugging tessellation and winding order issues. Everything is disabled by default.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShapeDebugDraw {
+    /// Draw the edges of every triangle of the shape meshes
+    pub wireframes: bool,
+    /// Draw the axis-aligned bounding box of every shape
+    pub bounding_boxes: bool,
+}
+
+fn draw_shape_debug(
+    debug: Res<ShapeDebugDraw>,
+    meshes: Res<Assets<Mesh>>,
+    shapes: Query<
+        (&Mesh2d, &GlobalTransform, &InheritedVisibility),
+        Or<(With<GeoPolygon>, With<GeoPolyline>)>,
+    >,
+    mut gizmos: Gizmos,
+) {
+    for (mesh, transform, visibility) in shapes.iter() {
+        if !visibility.get() {
+            continue;
+        }
+        let Some(mesh) = meshes.get(&mesh.0) else {
+            continue;
+        };
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            continue;
+        };
+        let points: Vec<Vec2> = positions
+            .iter()
+            .map(|p| transform.transform_point(Vec3::from(*p)).truncate())
+            .collect();
+
+        if debug.wireframes {
+            let indices: Vec<usize> = match mesh.indices() {
+                Some(indices) => indices.iter().collect(),
+                None => (0..points.len()).collect(),
+            };
+            for triangle in indices.chunks_exact(3) {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| points[i]);
+                gizmos.linestrip_2d([a, b, c, a], WIREFRAME_COLOR);
+            }
+        }
+
+        if debug.bounding_boxes && !points.is_empty() {
+            let (min, max) = points.iter().fold((Vec2::MAX, Vec2::MIN), |(min, max), p| {
+                (min.min(*p), max.max(*p))
+            });
+            gizmos.rect_2d((min + max) * 0.5, max - min, BOUNDING_BOX_COLOR);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Conversions between shapes and [geo-types](https://crates.io/crates/geo-types) geometries.
+//!
+//! Coordinates of `geo` geometries are lon/lat in degrees (x = longitude, y = latitude),
+//! shapes use mercator coordinates.
+
+use bevy::math::DVec2;
+use geo_types::{Coord, LineString, Polygon};
+
+use crate::{
+    WebMercatorConversion,
+    shapes::{polygon::GeoPolygon, polyline::GeoPolyline},
+};
+
+fn line_to_mercator(line: &LineString<f64>) -> Vec<DVec2> {
+    line.coords()
+        .map(|coord| DVec2::new(coord.x, coord.y).lonlat_to_mercator())
+        .collect()
+}
+
+fn ring_to_mercator(ring: &LineString<f64>) -> Vec<DVec2> {
+    let mut points = line_to_mercator(ring);
+    // geo rings are closed, GeoPolygon closes itself
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    points
+}
+
+fn mercator_to_line(points: &[DVec2]) -> LineString<f64> {
+    points
+        .iter()
+        .map(|point| {
+            let lonlat = point.mercator_to_lonlat();
+            Coord {
+                x: lonlat.x,
+                y: lonlat.y,
+            }
+        })
+        .collect()
+}
+
+impl From<&LineString<f64>> for GeoPolyline {
+    fn from(line: &LineString<f64>) -> Self {
+        Self {
+            points: line_to_mercator(line),
+        }
+    }
+}
+
+impl From<LineString<f64>> for GeoPolyline {
+    fn from(line: LineString<f64>) -> Self {
+        Self::from(&line)
+    }
+}
+
+impl From<&GeoPolyline> for LineString<f64> {
+    fn from(polyline: &GeoPolyline) -> Self {
+        mercator_to_line(&polyline.points)
+    }
+}
+
+impl From<&Polygon<f64>> for GeoPolygon {
+    fn from(polygon: &Polygon<f64>) -> Self {
+        Self {
+            points: ring_to_mercator(polygon.exterior()),
+            holes: polygon.interiors().iter().map(ring_to_mercator).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Polygon<f64>> for GeoPolygon {
+    fn from(polygon: Polygon<f64>) -> Self {
+        Self::from(&polygon)
+    }
+}
+
+/// The fill color is lost, rings are closed by [`Polygon::new`].
+impl From<&GeoPolygon> for Polygon<f64> {
+    fn from(polygon: &GeoPolygon) -> Self {
+        Polygon::new(
+            mercator_to_line(&polygon.points),
+            polygon
+                .holes
+                .iter()
+                .map(|hole| mercator_to_line(hole))
+                .collect(),
+        )
+    }
+}
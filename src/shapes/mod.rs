@@ -1,11 +1,105 @@
+#[cfg(feature = "debug_draw")]
+mod debug;
+#[cfg(feature = "geo")]
+pub mod geo;
 pub mod polygon;
 pub mod polyline;
+pub mod wkt;
 use bevy::prelude::*;
 mod utils;
 
+#[cfg(feature = "debug_draw")]
+pub use debug::ShapeDebugDraw;
+
 use crate::shapes::polygon::polygon_plugin;
 use crate::shapes::polyline::polyline_plugin;
+use crate::shapes::utils::shape_materials_plugin;
+use crate::world_copy::{copy_to_world_copies, sync_world_copy_instances};
 
 pub(crate) fn shapes_plugin(app: &mut App) {
-    app.add_plugins((polygon_plugin, polyline_plugin));
+    app.add_plugins((shape_materials_plugin, polygon_plugin, polyline_plugin))
+        .add_systems(
+            PostUpdate,
+            (
+                copy_to_world_copies::<Mesh2d>,
+                copy_to_world_copies::<MeshMaterial2d<ColorMaterial>>,
+            )
+                .after(sync_world_copy_instances)
+                .before(TransformSystems::Propagate),
+        );
+    #[cfg(feature = "debug_draw")]
+    app.add_plugins(debug::shape_debug_plugin);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::DVec2;
+
+    use super::*;
+    use crate::MercatorCoords;
+    use crate::shapes::{polygon::GeoPolygon, polyline::GeoPolyline};
+
+    fn shape_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .add_plugins((shape_materials_plugin, polygon_plugin, polyline_plugin));
+        app
+    }
+
+    #[test]
+    fn shapes_with_nan_coordinates_are_skipped() {
+        let mut app = shape_app();
+        let nan = DVec2::new(f64::NAN, 0.0);
+        let square = vec![
+            DVec2::ZERO,
+            DVec2::new(100.0, 0.0),
+            DVec2::new(100.0, 100.0),
+            DVec2::new(0.0, 100.0),
+        ];
+        let world = app.world_mut();
+        let invalid = [
+            world
+                .spawn(GeoPolygon {
+                    points: vec![DVec2::ZERO, DVec2::new(100.0, 0.0), nan],
+                    ..default()
+                })
+                .id(),
+            world
+                .spawn(GeoPolygon {
+                    points: square.clone(),
+                    holes: vec![vec![nan; 3]],
+                    ..default()
+                })
+                .id(),
+            world
+                .spawn(GeoPolyline {
+                    points: vec![DVec2::ZERO, nan],
+                })
+                .id(),
+        ];
+        let valid = [
+            world
+                .spawn(GeoPolygon {
+                    points: square.clone(),
+                    ..default()
+                })
+                .id(),
+            world.spawn(GeoPolyline { points: square }).id(),
+        ];
+
+        app.update();
+
+        for entity in invalid {
+            let entity = app.world().entity(entity);
+            assert!(!entity.contains::<Mesh2d>());
+            assert!(!entity.contains::<MercatorCoords>());
+        }
+        for entity in valid {
+            let entity = app.world().entity(entity);
+            assert!(entity.contains::<Mesh2d>());
+            assert!(entity.get::<MercatorCoords>().unwrap().0.is_finite());
+        }
+    }
 }
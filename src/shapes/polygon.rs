@@ -1,8 +1,11 @@
-use bevy::{asset::RenderAssetUsages, math::DVec2, mesh::Indices, prelude::*};
+use bevy::{asset::RenderAssetUsages, color::Mix, math::DVec2, mesh::Indices, prelude::*};
 use lyon::{
     math::point,
     path::Path,
-    tessellation::{BuffersBuilder, FillOptions, FillTessellator, VertexBuffers},
+    tessellation::{
+        BuffersBuilder, FillOptions, FillTessellator, FillVertex, FillVertexConstructor,
+        StrokeOptions, StrokeTessellator, StrokeVertex, StrokeVertexConstructor, VertexBuffers,
+    },
 };
 
 use crate::{MercatorCoords, shapes::utils::*};
@@ -12,20 +15,145 @@ pub(crate) fn polygon_plugin(app: &mut App) {
 }
 
 /// A simple polygon defined by a list of points in mercator coordinates.
-/// The polygon is filled with a solid color.
+/// The polygon is filled with a solid color or a gradient, optional holes are cut out of it.
+/// With `fill_color: None` and no `fill_gradient` the polygon is hollow, only its `outline` is drawn (if any).
 ///
 /// When adding a GeoPolygon component to an entity, a Mesh2d, MeshMaterial2d and [MercatorCoords] will be automatically created and added to the entity.
 #[derive(Component, Debug, Clone)]
 pub struct GeoPolygon {
     pub points: Vec<DVec2>,
+    /// Interior rings in mercator coordinates that are left unfilled.
+    pub holes: Vec<Vec<DVec2>>,
+    /// Solid fill color, `None` leaves the polygon unfilled.
     pub fill_color: Option<Color>,
+    /// Gradient used instead of `fill_color` if set.
+    pub fill_gradient: Option<PolygonGradient>,
+    /// Stroke drawn along the outer ring and the holes.
+    pub outline: Option<PolygonOutline>,
+}
+
+impl GeoPolygon {
+    /// Hollow polygon, only the outline is drawn.
+    pub fn hollow(points: Vec<DVec2>, outline_width: f32, outline_color: Color) -> Self {
+        Self {
+            points,
+            fill_color: None,
+            outline: Some(PolygonOutline {
+                width: outline_width,
+                color: outline_color,
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// Outline of a [GeoPolygon].
+#[derive(Debug, Clone, Copy)]
+pub struct PolygonOutline {
+    /// Width in local units (mercator meters), like the width of a [GeoPolyline](crate::shapes::polyline::GeoPolyline).
+    pub width: f32,
+    pub color: Color,
+}
+
+/// Gradient fill of a [GeoPolygon], positions are in mercator coordinates so the gradient stays in place on the map.
+///
+/// Colors are interpolated per vertex, large polygons with few vertices may need additional points for smooth radial gradients.
+#[derive(Debug, Clone)]
+pub enum PolygonGradient {
+    /// Changes from `start_color` at `start` to `end_color` at `end`, constant beyond both points.
+    Linear {
+        start: DVec2,
+        end: DVec2,
+        start_color: Color,
+        end_color: Color,
+    },
+    /// Changes from `center_color` at `center` to `edge_color` at `radius` (in mercator meters) and beyond.
+    Radial {
+        center: DVec2,
+        radius: f64,
+        center_color: Color,
+        edge_color: Color,
+    },
+}
+
+impl PolygonGradient {
+    /// Color of the gradient at `mercator`.
+    pub fn color_at(&self, mercator: DVec2) -> Color {
+        match self {
+            PolygonGradient::Linear {
+                start,
+                end,
+                start_color,
+                end_color,
+            } => {
+                let direction = end - start;
+                let length_squared = direction.length_squared();
+                let t = if length_squared > 0.0 {
+                    (mercator - start).dot(direction) / length_squared
+                } else {
+                    0.0
+                };
+                start_color.mix(end_color, t.clamp(0.0, 1.0) as f32)
+            }
+            PolygonGradient::Radial {
+                center,
+                radius,
+                center_color,
+                edge_color,
+            } => {
+                let t = if *radius > 0.0 {
+                    mercator.distance(*center) / radius
+                } else {
+                    1.0
+                };
+                center_color.mix(edge_color, t.clamp(0.0, 1.0) as f32)
+            }
+        }
+    }
+}
+
+/// Computes the fill color of each vertex, vertex positions are relative to `first_pos`.
+struct WithFillColor<'a> {
+    gradient: Option<&'a PolygonGradient>,
+    color: Color,
+    first_pos: DVec2,
+}
+
+impl FillVertexConstructor<ColorVertex> for WithFillColor<'_> {
+    fn new_vertex(&mut self, vertex: FillVertex) -> ColorVertex {
+        let position = vertex.position();
+        let color = match self.gradient {
+            Some(gradient) => {
+                gradient.color_at(self.first_pos + DVec2::new(position.x as f64, position.y as f64))
+            }
+            None => self.color,
+        };
+        ColorVertex {
+            position: position.extend(0.0).to_array(),
+            color: color.to_linear().to_f32_array(),
+        }
+    }
+}
+
+struct WithOutlineColor([f32; 4]);
+
+impl StrokeVertexConstructor<ColorVertex> for WithOutlineColor {
+    fn new_vertex(&mut self, vertex: StrokeVertex) -> ColorVertex {
+        ColorVertex {
+            position: vertex.position().extend(0.0).to_array(),
+            color: self.0,
+        }
+    }
 }
 
 impl Default for GeoPolygon {
     fn default() -> Self {
         Self {
             points: Vec::new(),
+            holes: Vec::new(),
             fill_color: Some(Color::WHITE),
+            fill_gradient: None,
+            outline: None,
         }
     }
 }
@@ -35,9 +163,28 @@ fn sync_polygon_added(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut shape_materials: ResMut<ShapeMaterials>,
 ) {
     for (entity, polygon) in query.iter() {
         debug!("Adding polygon with {} points", polygon.points.len());
+        let finite = |ring: &Vec<DVec2>| ring.iter().all(|p| p.is_finite());
+        if !finite(&polygon.points) || !polygon.holes.iter().all(finite) {
+            warn!("Skipping polygon {entity} with non-finite coordinates");
+            commands
+                .entity(entity)
+                .remove::<(Mesh2d, MeshMaterial2d<ColorMaterial>)>();
+            continue;
+        }
+        if polygon.points.len() < 3 {
+            warn!(
+                "Skipping polygon {entity} with {} points, at least 3 are required",
+                polygon.points.len()
+            );
+            commands
+                .entity(entity)
+                .remove::<(Mesh2d, MeshMaterial2d<ColorMaterial>)>();
+            continue;
+        }
         let (vertices, first_pos) = points_to_relative(&polygon.points);
         let mut path_builder = Path::builder();
         if let Some((first, rest)) = vertices.split_first() {
@@ -47,42 +194,90 @@ fn sync_polygon_added(
             });
             path_builder.close();
         }
+        // holes without an area can't cut anything out
+        for hole in polygon.holes.iter().filter(|hole| hole.len() >= 3) {
+            let mut hole = hole.iter().map(|p| (p - first_pos).as_vec2());
+            if let Some(first) = hole.next() {
+                path_builder.begin(point(first.x, first.y));
+                hole.for_each(|p| {
+                    path_builder.line_to(point(p.x, p.y));
+                });
+                path_builder.close();
+            }
+        }
         let path = path_builder.build();
-        let mut tessellator = FillTessellator::new();
         let mut mesh = Mesh::new(
             bevy::mesh::PrimitiveTopology::TriangleList,
             RenderAssetUsages::default(),
         );
 
-        let fill_options =
-            FillOptions::tolerance(0.1).with_fill_rule(lyon::path::FillRule::NonZero);
+        // holes don't have to be wound opposite to the outer ring
+        let fill_rule = if polygon.holes.is_empty() {
+            lyon::path::FillRule::NonZero
+        } else {
+            lyon::path::FillRule::EvenOdd
+        };
+        let fill_options = FillOptions::tolerance(0.1).with_fill_rule(fill_rule);
 
-        let mut buffers: VertexBuffers<SimpleVertex, u32> = VertexBuffers::new();
-        tessellator
-            .tessellate(
+        let filled = polygon.fill_gradient.is_some() || polygon.fill_color.is_some();
+        let mut buffers: VertexBuffers<ColorVertex, u32> = VertexBuffers::new();
+        if filled {
+            let constructor = WithFillColor {
+                gradient: polygon.fill_gradient.as_ref(),
+                color: polygon.fill_color.unwrap_or(Color::WHITE),
+                first_pos,
+            };
+            if let Err(err) = FillTessellator::new().tessellate(
                 &path,
                 &fill_options,
-                &mut BuffersBuilder::new(&mut buffers, WithoutColor),
-            )
-            .unwrap();
-        mesh.insert_indices(Indices::U32(buffers.indices));
-        mesh.insert_attribute(
-            Mesh::ATTRIBUTE_POSITION,
+                &mut BuffersBuilder::new(&mut buffers, constructor),
+            ) {
+                warn!("Failed to tessellate polygon {entity}: {err:?}");
+                continue;
+            }
+        }
+        if let Some(outline) = &polygon.outline {
+            // tessellated separately, the outline indices are offset when appending it to the fill
+            let mut stroke: VertexBuffers<ColorVertex, u32> = VertexBuffers::new();
+            let stroke_options = StrokeOptions::tolerance(0.1).with_line_width(outline.width);
+            let constructor = WithOutlineColor(outline.color.to_linear().to_f32_array());
+            if let Err(err) = StrokeTessellator::new().tessellate_path(
+                &path,
+                &stroke_options,
+                &mut BuffersBuilder::new(&mut stroke, constructor),
+            ) {
+                warn!("Failed to tessellate outline of polygon {entity}: {err:?}");
+                continue;
+            }
+            let offset = buffers.vertices.len() as u32;
+            buffers.vertices.extend(stroke.vertices);
             buffers
-                .vertices
-                .iter()
-                .map(|v| v.position)
-                .collect::<Vec<[f32; 3]>>(),
-        );
+                .indices
+                .extend(stroke.indices.into_iter().map(|index| index + offset));
+        }
+        if buffers.indices.is_empty() {
+            // neither filled nor outlined
+            commands
+                .entity(entity)
+                .remove::<(Mesh2d, MeshMaterial2d<ColorMaterial>)>();
+            continue;
+        }
 
-        let material = ColorMaterial {
-            color: polygon.fill_color.unwrap_or(Color::WHITE),
-            ..Default::default()
+        // a solid fill is colored by the material, everything else by vertex colors
+        let color = if polygon.fill_gradient.is_none() && polygon.outline.is_none() {
+            polygon.fill_color.unwrap_or(Color::WHITE)
+        } else {
+            let colors: Vec<[f32; 4]> = buffers.vertices.iter().map(|v| v.color).collect();
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+            Color::WHITE
         };
+        let positions: Vec<[f32; 3]> = buffers.vertices.iter().map(|v| v.position).collect();
+        mesh.insert_indices(Indices::U32(buffers.indices));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
 
         commands.entity(entity).insert((
             Mesh2d(meshes.add(mesh)),
-            MeshMaterial2d(materials.add(material)),
+            MeshMaterial2d(shape_materials.get_or_add_color(color, &mut materials)),
             MercatorCoords(first_pos.extend(5.0)),
         ));
     }
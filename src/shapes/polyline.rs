@@ -1,6 +1,9 @@
 use bevy::{asset::RenderAssetUsages, math::DVec2, mesh::Indices, prelude::*};
 
-use crate::{MercatorCoords, NewScale, shapes::utils::*};
+use crate::{
+    MercatorCoords, NewScale, WebMercatorConversion, coord_conversions::nearest_world_copy,
+    densify_geodesic, haversine_distance, shapes::utils::*,
+};
 use lyon::{
     math::point,
     path::{LineCap, LineJoin, Path},
@@ -66,6 +69,9 @@ pub struct GeoPolylineConfig {
     pub line_join: LineJoin,
     pub miter_limit: f32,
     pub tolerance: f32,
+    /// Draw segments longer than this many meters along the great circle instead of a straight mercator line,
+    /// e.g. for flight paths. Points inserted into a segment use the width and color of its first point.
+    pub geodesic_threshold: Option<f64>,
 }
 
 #[derive(Component, Debug, Clone)]
@@ -81,6 +87,7 @@ impl GeoPolylineConfig {
             line_join: LineJoin::Round,
             miter_limit: 4.0,
             tolerance: 1.0,
+            geodesic_threshold: None,
         }
     }
 }
@@ -97,14 +104,108 @@ impl Default for GeoPolylineConfig {
             line_join: LineJoin::Round,
             miter_limit: 4.0,
             tolerance: 1.0,
+            geodesic_threshold: None,
+        }
+    }
+}
+
+// limits the number of points inserted into a single segment for very small thresholds
+const MAX_GEODESIC_SEGMENTS: usize = 256;
+
+/// Splits segments longer than `threshold` meters along the great circle.
+///
+/// Returns the new points and for each of them the index of the original point starting its segment.
+fn densify_polyline(points: &[DVec2], threshold: f64) -> (Vec<DVec2>, Vec<usize>) {
+    let mut dense = Vec::with_capacity(points.len());
+    let mut sources = Vec::with_capacity(points.len());
+    for (i, pair) in points.windows(2).enumerate() {
+        dense.push(pair[0]);
+        sources.push(i);
+        let (a, b) = (pair[0].mercator_to_lonlat(), pair[1].mercator_to_lonlat());
+        let segments = (haversine_distance(a, b) / threshold).ceil() as usize;
+        if segments < 2 {
+            continue;
+        }
+        let segments = segments.min(MAX_GEODESIC_SEGMENTS);
+        let inner = densify_geodesic(a, b, segments);
+        for lonlat in &inner[1..segments] {
+            // stay on the world copy of the previous point, the longitudes may cross the antimeridian
+            let previous = dense[dense.len() - 1].x;
+            let point = nearest_world_copy(lonlat.lonlat_to_mercator().extend(0.0), previous);
+            dense.push(point.truncate());
+            sources.push(i);
         }
     }
+    if let Some(last) = points.last() {
+        dense.push(*last);
+        sources.push(points.len() - 1);
+    }
+    (dense, sources)
 }
 
 #[derive(Component, Debug, Clone)]
 struct LyonPolyline {
     first_pos: DVec2,
     path: Path,
+    /// settings the path was built with, a style change that keeps them reuses the path
+    geodesic_threshold: Option<f64>,
+    attributes: PathAttributes,
+}
+
+/// Per point widths and colors of a style, they are part of the path
+#[derive(Debug, Clone, PartialEq)]
+enum PathAttributes {
+    None,
+    Colors(Vec<Color>),
+    Widths(Vec<f32>),
+    WidthsAndColors(Vec<f32>, Vec<Color>),
+}
+
+impl PathAttributes {
+    fn of(style: &PolylineStyle) -> Self {
+        match style {
+            PolylineStyle::ConstantWidthConstantColor { .. } => Self::None,
+            PolylineStyle::ConstantWidthVariableColor { colors, .. } => {
+                Self::Colors(colors.clone())
+            }
+            PolylineStyle::VariableWidthConstantColor { widths, .. } => {
+                Self::Widths(widths.clone())
+            }
+            PolylineStyle::VariableWidthVariableColor { widths, colors } => {
+                Self::WidthsAndColors(widths.clone(), colors.clone())
+            }
+        }
+    }
+}
+
+/// Stroke settings the mesh of a polyline was tessellated with, a style change that keeps them only swaps the material
+#[derive(Component, Debug, Clone, PartialEq)]
+struct TessellatedStroke {
+    start_cap: LineCap,
+    end_cap: LineCap,
+    line_join: LineJoin,
+    miter_limit: f32,
+    tolerance: f32,
+    /// constant line width, variable widths are part of the path
+    width: Option<f32>,
+}
+
+impl TessellatedStroke {
+    fn of(config: &GeoPolylineConfig) -> Self {
+        let width = match &config.style {
+            PolylineStyle::ConstantWidthConstantColor { width, .. }
+            | PolylineStyle::ConstantWidthVariableColor { width, .. } => Some(*width),
+            _ => None,
+        };
+        Self {
+            start_cap: config.start_cap,
+            end_cap: config.end_cap,
+            line_join: config.line_join,
+            miter_limit: config.miter_limit,
+            tolerance: config.tolerance,
+            width,
+        }
+    }
 }
 
 /// Marker component to keep the polyline width in display size regardless of zoom level.
@@ -133,11 +234,53 @@ fn insert_polyline_initial_style(
 }
 
 fn sync_polyline(
-    query: Query<(Entity, &GeoPolyline, &GeoPolylineConfig), Changed<GeoPolylineConfig>>,
+    query: Query<
+        (
+            Entity,
+            Ref<GeoPolyline>,
+            &GeoPolylineConfig,
+            Option<&LyonPolyline>,
+        ),
+        Or<(Changed<GeoPolyline>, Changed<GeoPolylineConfig>)>,
+    >,
     mut commands: Commands,
 ) {
-    for (entity, polyline, config) in query.iter() {
-        let (vertices, first_pos) = points_to_relative(&polyline.points);
+    for (entity, polyline, config, existing) in query.iter() {
+        let attributes = PathAttributes::of(&config.style);
+        // e.g. only the color or width changed
+        if !polyline.is_changed()
+            && existing.is_some_and(|existing| {
+                existing.geodesic_threshold == config.geodesic_threshold
+                    && existing.attributes == attributes
+            })
+        {
+            continue;
+        }
+        if !polyline.points.iter().all(|p| p.is_finite()) {
+            warn!("Skipping polyline {entity} with non-finite coordinates");
+            commands
+                .entity(entity)
+                .remove::<(LyonPolyline, Mesh2d, MeshMaterial2d<ColorMaterial>)>();
+            continue;
+        }
+        if polyline.points.len() < 2 {
+            warn!(
+                "Skipping polyline {entity} with {} points, at least 2 are required",
+                polyline.points.len()
+            );
+            commands
+                .entity(entity)
+                .remove::<(LyonPolyline, Mesh2d, MeshMaterial2d<ColorMaterial>)>();
+            continue;
+        }
+        let (points, sources) = match config.geodesic_threshold {
+            Some(threshold) if threshold > 0.0 => densify_polyline(&polyline.points, threshold),
+            _ => (
+                polyline.points.clone(),
+                (0..polyline.points.len()).collect(),
+            ),
+        };
+        let (vertices, first_pos) = points_to_relative(&points);
         let path = match &config.style {
             PolylineStyle::ConstantWidthConstantColor { width: _, color: _ } => {
                 let mut path_builder = Path::builder();
@@ -156,7 +299,7 @@ fn sync_polyline(
                     let color = color_to_f32_array(colors.first());
                     path_builder.begin(point(first.x, first.y), &color);
                     rest.iter().enumerate().for_each(|(i, p)| {
-                        let color = color_to_f32_array(colors.get(i + 1));
+                        let color = color_to_f32_array(colors.get(sources[i + 1]));
                         path_builder.line_to(point(p.x, p.y), &color);
                     });
                     path_builder.end(false);
@@ -169,7 +312,7 @@ fn sync_polyline(
                     let width = widths.first().cloned().unwrap_or(1.0f32);
                     path_builder.begin(point(first.x, first.y), &[width]);
                     rest.iter().enumerate().for_each(|(i, p)| {
-                        let width = widths.get(i + 1).cloned().unwrap_or(1.0f32);
+                        let width = widths.get(sources[i + 1]).cloned().unwrap_or(1.0f32);
                         path_builder.line_to(point(p.x, p.y), &[width]);
                     });
                     path_builder.end(false);
@@ -184,8 +327,8 @@ fn sync_polyline(
                     let attributes = [width, color[0], color[1], color[2], color[3]];
                     path_builder.begin(point(first.x, first.y), &attributes);
                     rest.iter().enumerate().for_each(|(i, p)| {
-                        let width = widths.get(i + 1).cloned().unwrap_or(1.0f32);
-                        let color = color_to_f32_array(colors.get(i + 1));
+                        let width = widths.get(sources[i + 1]).cloned().unwrap_or(1.0f32);
+                        let color = color_to_f32_array(colors.get(sources[i + 1]));
                         let attributes = [width, color[0], color[1], color[2], color[3]];
                         path_builder.line_to(point(p.x, p.y), &attributes);
                     });
@@ -194,19 +337,43 @@ fn sync_polyline(
                 path_builder.build()
             }
         };
-        commands
-            .entity(entity)
-            .insert((LyonPolyline { first_pos, path },));
+        commands.entity(entity).insert((LyonPolyline {
+            first_pos,
+            path,
+            geodesic_threshold: config.geodesic_threshold,
+            attributes,
+        },));
     }
 }
 
 fn sync_polyline_config(
-    query: Query<(Entity, &GeoPolylineConfig, &LyonPolyline), Changed<GeoPolylineConfig>>,
+    query: Query<
+        (
+            Entity,
+            &GeoPolylineConfig,
+            Ref<LyonPolyline>,
+            Option<&TessellatedStroke>,
+        ),
+        Or<(Changed<GeoPolylineConfig>, Changed<LyonPolyline>)>,
+    >,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut shape_materials: ResMut<ShapeMaterials>,
 ) {
-    for (entity, config, lyon_polyline) in query.iter() {
+    for (entity, config, lyon_polyline, tessellated) in query.iter() {
+        let stroke = TessellatedStroke::of(config);
+        if !lyon_polyline.is_changed() && tessellated == Some(&stroke) {
+            // only the color changed, variable colors are part of the path and rebuild it
+            if let PolylineStyle::ConstantWidthConstantColor { color, .. }
+            | PolylineStyle::VariableWidthConstantColor { color, .. } = &config.style
+            {
+                commands.entity(entity).insert(MeshMaterial2d(
+                    shape_materials.get_or_add_color(*color, &mut materials),
+                ));
+            }
+            continue;
+        }
         let mut stroke_options = StrokeOptions::default()
             .with_start_cap(config.start_cap)
             .with_end_cap(config.end_cap)
@@ -224,13 +391,14 @@ fn sync_polyline_config(
             PolylineStyle::ConstantWidthConstantColor { width, color } => {
                 stroke_options.line_width = *width;
                 let mut buffers: VertexBuffers<SimpleVertex, u32> = VertexBuffers::new();
-                tessellator
-                    .tessellate(
-                        &lyon_polyline.path,
-                        &stroke_options,
-                        &mut BuffersBuilder::new(&mut buffers, WithoutColor),
-                    )
-                    .unwrap();
+                if let Err(err) = tessellator.tessellate(
+                    &lyon_polyline.path,
+                    &stroke_options,
+                    &mut BuffersBuilder::new(&mut buffers, WithoutColor),
+                ) {
+                    warn!("Failed to tessellate polyline {entity}: {err:?}");
+                    continue;
+                }
                 (
                     buffers.vertices.iter().map(|v| v.position).collect(),
                     buffers.indices,
@@ -240,13 +408,14 @@ fn sync_polyline_config(
             PolylineStyle::ConstantWidthVariableColor { width, colors: _ } => {
                 stroke_options.line_width = *width;
                 let mut buffers: VertexBuffers<ColorVertex, u32> = VertexBuffers::new();
-                tessellator
-                    .tessellate(
-                        &lyon_polyline.path,
-                        &stroke_options,
-                        &mut BuffersBuilder::new(&mut buffers, WithColor),
-                    )
-                    .unwrap();
+                if let Err(err) = tessellator.tessellate(
+                    &lyon_polyline.path,
+                    &stroke_options,
+                    &mut BuffersBuilder::new(&mut buffers, WithColor),
+                ) {
+                    warn!("Failed to tessellate polyline {entity}: {err:?}");
+                    continue;
+                }
                 let colors: Vec<[f32; 4]> = buffers.vertices.iter().map(|v| v.color).collect();
                 mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
                 (
@@ -258,13 +427,14 @@ fn sync_polyline_config(
             PolylineStyle::VariableWidthConstantColor { widths: _, color } => {
                 stroke_options.variable_line_width = Some(0);
                 let mut buffers: VertexBuffers<SimpleVertex, u32> = VertexBuffers::new();
-                tessellator
-                    .tessellate_path(
-                        &lyon_polyline.path,
-                        &stroke_options,
-                        &mut BuffersBuilder::new(&mut buffers, WithoutColor),
-                    )
-                    .unwrap();
+                if let Err(err) = tessellator.tessellate_path(
+                    &lyon_polyline.path,
+                    &stroke_options,
+                    &mut BuffersBuilder::new(&mut buffers, WithoutColor),
+                ) {
+                    warn!("Failed to tessellate polyline {entity}: {err:?}");
+                    continue;
+                }
                 (
                     buffers.vertices.iter().map(|v| v.position).collect(),
                     buffers.indices,
@@ -277,13 +447,14 @@ fn sync_polyline_config(
             } => {
                 stroke_options.variable_line_width = Some(0);
                 let mut buffers: VertexBuffers<ColorVertex, u32> = VertexBuffers::new();
-                tessellator
-                    .tessellate_path(
-                        &lyon_polyline.path,
-                        &stroke_options,
-                        &mut BuffersBuilder::new(&mut buffers, WithColor),
-                    )
-                    .unwrap();
+                if let Err(err) = tessellator.tessellate_path(
+                    &lyon_polyline.path,
+                    &stroke_options,
+                    &mut BuffersBuilder::new(&mut buffers, WithColor),
+                ) {
+                    warn!("Failed to tessellate polyline {entity}: {err:?}");
+                    continue;
+                }
                 let colors: Vec<[f32; 4]> = buffers.vertices.iter().map(|v| v.color).collect();
                 mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
                 (
@@ -295,14 +466,11 @@ fn sync_polyline_config(
         };
         mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
         mesh.insert_indices(Indices::U32(indices));
-        let material = ColorMaterial {
-            color,
-            ..Default::default()
-        };
         commands.entity(entity).insert((
             Mesh2d(meshes.add(mesh)),
-            MeshMaterial2d(materials.add(material)),
+            MeshMaterial2d(shape_materials.get_or_add_color(color, &mut materials)),
             MercatorCoords(lyon_polyline.first_pos.extend(5.0)),
+            stroke,
         ));
     }
 }
@@ -1,7 +1,12 @@
-use bevy::math::{DVec2, Vec2};
+use bevy::{
+    math::{DVec2, Vec2},
+    prelude::*,
+};
 
 use lyon::tessellation::{FillVertexConstructor, StrokeVertexConstructor};
 
+use crate::shared_assets::{AssetUser, SharedAssets, shared_assets_plugin};
+
 pub(crate) fn points_to_relative(points: &Vec<DVec2>) -> (Vec<Vec2>, DVec2) {
     if points.is_empty() {
         return (vec![], DVec2::ZERO);
@@ -65,3 +70,30 @@ impl FillVertexConstructor<SimpleVertex> for WithoutColor {
         }
     }
 }
+
+impl AssetUser for MeshMaterial2d<ColorMaterial> {
+    type Asset = ColorMaterial;
+
+    fn asset_id(&self) -> AssetId<ColorMaterial> {
+        self.id()
+    }
+}
+
+/// Materials of the shapes by color, so shapes with the same color share a material and can be batched.
+pub(crate) type ShapeMaterials = SharedAssets<[u32; 4], MeshMaterial2d<ColorMaterial>>;
+
+impl ShapeMaterials {
+    pub(crate) fn get_or_add_color(
+        &mut self,
+        color: Color,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> Handle<ColorMaterial> {
+        let key = color.to_linear().to_f32_array().map(f32::to_bits);
+        self.get_or_add(key, || materials.add(ColorMaterial::from(color)))
+    }
+}
+
+/// Shares the materials of shapes with the same color until no shape uses them anymore.
+pub(crate) fn shape_materials_plugin(app: &mut App) {
+    app.add_plugins(shared_assets_plugin::<[u32; 4], MeshMaterial2d<ColorMaterial>>);
+}
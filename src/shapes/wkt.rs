@@ -0,0 +1,285 @@
+//! Parsing of [WKT](https://en.wikipedia.org/wiki/Well-known_text_representation_of_geometry) geometries into shapes.
+//!
+//! Coordinates are interpreted as lon/lat in degrees and projected to mercator coordinates.
+//! Supported are `LINESTRING`, `MULTILINESTRING`, `POLYGON` (with holes), `MULTIPOLYGON`
+//! and `GEOMETRYCOLLECTION`s of these. Z and M values are ignored.
+
+use std::fmt;
+
+use bevy::math::DVec2;
+
+use crate::{
+    WebMercatorConversion,
+    shapes::{polygon::GeoPolygon, polyline::GeoPolyline},
+};
+
+/// A shape parsed from WKT, ready to be spawned.
+#[derive(Debug, Clone)]
+pub enum WktShape {
+    Polyline(GeoPolyline),
+    Polygon(GeoPolygon),
+}
+
+/// Error returned when parsing WKT fails.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WktError {
+    /// The input ended in the middle of a geometry.
+    UnexpectedEnd,
+    /// Unexpected token at the given byte offset.
+    UnexpectedToken { position: usize, found: String },
+    /// The geometry type is valid WKT but can't be displayed as a shape, e.g. `POINT`.
+    UnsupportedGeometry(String),
+}
+
+impl fmt::Display for WktError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WktError::UnexpectedEnd => write!(f, "unexpected end of WKT input"),
+            WktError::UnexpectedToken { position, found } => {
+                write!(f, "unexpected '{found}' at position {position}")
+            }
+            WktError::UnsupportedGeometry(kind) => write!(f, "unsupported geometry type {kind}"),
+        }
+    }
+}
+
+impl std::error::Error for WktError {}
+
+/// Parses a WKT geometry into shapes, multi geometries and collections result in multiple shapes.
+///
+/// `EMPTY` geometries result in no shapes.
+pub fn parse_wkt(input: &str) -> Result<Vec<WktShape>, WktError> {
+    let mut parser = Parser::new(input)?;
+    let mut shapes = Vec::new();
+    parser.geometry(&mut shapes)?;
+    if let Some((position, token)) = parser.tokens.get(parser.index) {
+        return Err(WktError::UnexpectedToken {
+            position: *position,
+            found: token.to_string(),
+        });
+    }
+    Ok(shapes)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(f64),
+    Open,
+    Close,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{word}"),
+            Token::Number(number) => write!(f, "{number}"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self, WktError> {
+        let mut tokens = Vec::new();
+        let mut chars = input.char_indices().peekable();
+        while let Some(&(position, c)) = chars.peek() {
+            let token = match c {
+                c if c.is_whitespace() => {
+                    chars.next();
+                    continue;
+                }
+                '(' => Token::Open,
+                ')' => Token::Close,
+                ',' => Token::Comma,
+                c if c.is_ascii_alphabetic() => {
+                    let mut word = String::new();
+                    while let Some(&(_, c)) = chars.peek()
+                        && c.is_ascii_alphabetic()
+                    {
+                        word.push(c.to_ascii_uppercase());
+                        chars.next();
+                    }
+                    tokens.push((position, Token::Word(word)));
+                    continue;
+                }
+                c if c.is_ascii_digit() || matches!(c, '-' | '+' | '.') => {
+                    let mut number = String::new();
+                    while let Some(&(_, c)) = chars.peek()
+                        && (c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                    {
+                        number.push(c);
+                        chars.next();
+                    }
+                    let value = number.parse().map_err(|_| WktError::UnexpectedToken {
+                        position,
+                        found: number.clone(),
+                    })?;
+                    tokens.push((position, Token::Number(value)));
+                    continue;
+                }
+                c => {
+                    return Err(WktError::UnexpectedToken {
+                        position,
+                        found: c.to_string(),
+                    });
+                }
+            };
+            tokens.push((position, token));
+            chars.next();
+        }
+        Ok(Self { tokens, index: 0 })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), WktError> {
+        let token = self
+            .tokens
+            .get(self.index)
+            .cloned()
+            .ok_or(WktError::UnexpectedEnd)?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), WktError> {
+        let (position, token) = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(WktError::UnexpectedToken {
+                position,
+                found: token.to_string(),
+            })
+        }
+    }
+
+    /// Consumes `EMPTY` if it is the next token.
+    fn empty(&mut self) -> bool {
+        if self.peek() == Some(&Token::Word("EMPTY".to_string())) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses a comma separated list enclosed in parentheses.
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, WktError>,
+    ) -> Result<Vec<T>, WktError> {
+        let mut items = Vec::new();
+        if self.empty() {
+            return Ok(items);
+        }
+        self.expect(Token::Open)?;
+        loop {
+            items.push(item(self)?);
+            let (position, token) = self.next()?;
+            match token {
+                Token::Comma => continue,
+                Token::Close => break,
+                token => {
+                    return Err(WktError::UnexpectedToken {
+                        position,
+                        found: token.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(items)
+    }
+
+    fn geometry(&mut self, shapes: &mut Vec<WktShape>) -> Result<(), WktError> {
+        let (position, token) = self.next()?;
+        let Token::Word(kind) = token else {
+            return Err(WktError::UnexpectedToken {
+                position,
+                found: token.to_string(),
+            });
+        };
+        // dimension markers, extra ordinates are skipped when parsing points
+        if let Some(Token::Word(word)) = self.peek()
+            && matches!(word.as_str(), "Z" | "M" | "ZM")
+        {
+            self.index += 1;
+        }
+
+        match kind.as_str() {
+            "LINESTRING" => {
+                let points = self.line()?;
+                if !points.is_empty() {
+                    shapes.push(WktShape::Polyline(GeoPolyline { points }));
+                }
+            }
+            "MULTILINESTRING" => {
+                for points in self.list(Self::line)? {
+                    if !points.is_empty() {
+                        shapes.push(WktShape::Polyline(GeoPolyline { points }));
+                    }
+                }
+            }
+            "POLYGON" => shapes.extend(self.polygon()?.map(WktShape::Polygon)),
+            "MULTIPOLYGON" => {
+                for polygon in self.list(Self::polygon)?.into_iter().flatten() {
+                    shapes.push(WktShape::Polygon(polygon));
+                }
+            }
+            "GEOMETRYCOLLECTION" => {
+                self.list(|parser| parser.geometry(shapes))?;
+            }
+            _ => return Err(WktError::UnsupportedGeometry(kind)),
+        }
+        Ok(())
+    }
+
+    fn point(&mut self) -> Result<DVec2, WktError> {
+        let mut ordinates = Vec::with_capacity(4);
+        while let Some(Token::Number(value)) = self.peek() {
+            ordinates.push(*value);
+            self.index += 1;
+        }
+        if ordinates.len() < 2 {
+            let (position, token) = self.next()?;
+            return Err(WktError::UnexpectedToken {
+                position,
+                found: token.to_string(),
+            });
+        }
+        Ok(DVec2::new(ordinates[0], ordinates[1]).lonlat_to_mercator())
+    }
+
+    fn line(&mut self) -> Result<Vec<DVec2>, WktError> {
+        self.list(Self::point)
+    }
+
+    fn polygon(&mut self) -> Result<Option<GeoPolygon>, WktError> {
+        let mut rings = self.list(Self::line)?.into_iter().map(|mut ring| {
+            // WKT rings repeat the first point at the end, GeoPolygon closes itself
+            if ring.len() > 1 && ring.first() == ring.last() {
+                ring.pop();
+            }
+            ring
+        });
+        let Some(points) = rings.next() else {
+            return Ok(None);
+        };
+        Ok(Some(GeoPolygon {
+            points,
+            holes: rings.collect(),
+            ..Default::default()
+        }))
+    }
+}
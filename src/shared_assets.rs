@@ -0,0 +1,183 @@
+use std::hash::Hash;
+
+use bevy::{platform::collections::HashMap, prelude::*};
+
+/// Component holding a handle to an asset that can be shared through [`SharedAssets`].
+pub(crate) trait AssetUser: Component {
+    type Asset: Asset;
+
+    fn asset_id(&self) -> AssetId<Self::Asset>;
+}
+
+/// Assets shared between entities, keyed by what makes them interchangeable, e.g. the color of a material.
+///
+/// The components `C` holding a shared asset are counted when they are inserted and replaced (which includes removing
+/// them and despawning their entity), so an asset no component holds anymore is dropped in [`Last`] instead of being
+/// kept alive by the cache. Counting components instead of handles also covers clones, e.g. on world copies.
+/// Handles handed out during a frame are inserted by commands before [`Last`], so they are never dropped too early.
+#[derive(Resource)]
+pub(crate) struct SharedAssets<K, C: AssetUser> {
+    handles: HashMap<K, Handle<C::Asset>>,
+    /// Key and number of `C` components of each shared asset
+    users: HashMap<AssetId<C::Asset>, (K, usize)>,
+    /// Whether an asset may have lost its last user since the last eviction
+    unused: bool,
+}
+
+impl<K, C: AssetUser> Default for SharedAssets<K, C> {
+    fn default() -> Self {
+        Self {
+            handles: HashMap::default(),
+            users: HashMap::default(),
+            unused: false,
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash, C: AssetUser> SharedAssets<K, C> {
+    /// Handle of the asset shared for `key`, the asset is created by `add` if there is none yet.
+    pub(crate) fn get_or_add(
+        &mut self,
+        key: K,
+        add: impl FnOnce() -> Handle<C::Asset>,
+    ) -> Handle<C::Asset> {
+        if let Some(handle) = self.handles.get(&key) {
+            return handle.clone();
+        }
+        let handle = add();
+        self.users.insert(handle.id(), (key.clone(), 0));
+        self.handles.insert(key, handle.clone());
+        // evicted again if no component holds it by the end of the frame
+        self.unused = true;
+        handle
+    }
+}
+
+fn count_user<K: Send + Sync + 'static, C: AssetUser>(
+    insert: On<Insert, C>,
+    users: Query<&C>,
+    mut shared: ResMut<SharedAssets<K, C>>,
+) {
+    let Ok(user) = users.get(insert.entity) else {
+        return;
+    };
+    if let Some((_, count)) = shared.users.get_mut(&user.asset_id()) {
+        *count += 1;
+    }
+}
+
+fn release_user<K: Send + Sync + 'static, C: AssetUser>(
+    replace: On<Replace, C>,
+    users: Query<&C>,
+    mut shared: ResMut<SharedAssets<K, C>>,
+) {
+    let Ok(user) = users.get(replace.entity) else {
+        return;
+    };
+    let shared = &mut *shared;
+    if let Some((_, count)) = shared.users.get_mut(&user.asset_id()) {
+        *count = count.saturating_sub(1);
+        shared.unused |= *count == 0;
+    }
+}
+
+fn evict_unused<K: Eq + Hash + Send + Sync + 'static, C: AssetUser>(
+    mut shared: ResMut<SharedAssets<K, C>>,
+) {
+    if !shared.unused {
+        return;
+    }
+    let SharedAssets {
+        handles,
+        users,
+        unused,
+    } = &mut *shared;
+    *unused = false;
+    users.retain(|_, (key, count)| {
+        if *count == 0 {
+            handles.remove(key);
+        }
+        *count > 0
+    });
+}
+
+/// Shares the assets of the components `C` through [`SharedAssets<K, C>`].
+pub(crate) fn shared_assets_plugin<K: Clone + Eq + Hash + Send + Sync + 'static, C: AssetUser>(
+    app: &mut App,
+) {
+    app.init_resource::<SharedAssets<K, C>>()
+        .add_observer(count_user::<K, C>)
+        .add_observer(release_user::<K, C>)
+        .add_systems(Last, evict_unused::<K, C>);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Asset, TypePath, Debug)]
+    struct TestAsset;
+
+    #[derive(Component, Clone)]
+    struct UsesAsset(Handle<TestAsset>);
+
+    impl AssetUser for UsesAsset {
+        type Asset = TestAsset;
+
+        fn asset_id(&self) -> AssetId<TestAsset> {
+            self.0.id()
+        }
+    }
+
+    type TestShared = SharedAssets<u32, UsesAsset>;
+
+    fn shared_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<TestAsset>()
+            .add_plugins(shared_assets_plugin::<u32, UsesAsset>);
+        app
+    }
+
+    fn get_or_add(app: &mut App, key: u32) -> Handle<TestAsset> {
+        app.world_mut()
+            .resource_scope(|world, mut shared: Mut<TestShared>| {
+                shared.get_or_add(key, || {
+                    world.resource_mut::<Assets<TestAsset>>().add(TestAsset)
+                })
+            })
+    }
+
+    #[test]
+    fn assets_are_dropped_after_their_last_user() {
+        let mut app = shared_app();
+        let handle = get_or_add(&mut app, 1);
+        assert_eq!(get_or_add(&mut app, 1), handle);
+        let first = app.world_mut().spawn(UsesAsset(handle.clone())).id();
+        // e.g. a world copy cloning the component
+        let copy = app.world_mut().spawn(UsesAsset(handle.clone())).id();
+        drop(handle);
+        app.update();
+        assert_eq!(app.world().resource::<TestShared>().handles.len(), 1);
+
+        app.world_mut().entity_mut(first).despawn();
+        app.update();
+        assert_eq!(app.world().resource::<TestShared>().handles.len(), 1);
+
+        // replacing the component releases the asset as well
+        let other = get_or_add(&mut app, 2);
+        app.world_mut().entity_mut(copy).insert(UsesAsset(other));
+        app.update();
+        let shared = app.world().resource::<TestShared>();
+        assert_eq!(shared.handles.len(), 1);
+        assert!(shared.handles.contains_key(&2));
+    }
+
+    #[test]
+    fn assets_without_users_are_dropped_at_the_end_of_the_frame() {
+        let mut app = shared_app();
+        drop(get_or_add(&mut app, 1));
+        app.update();
+        assert_eq!(app.world().resource::<TestShared>().handles.len(), 0);
+    }
+}
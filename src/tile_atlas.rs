@@ -0,0 +1,388 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    image::ImageSampler,
+    platform::collections::HashMap,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+/// Width and height of an atlas page in pixels, the largest texture WebGL2 is guaranteed to support.
+const PAGE_SIZE: u32 = 2048;
+/// Pixels repeated around every tile in a page, so linear filtering at the tile edges doesn't blend in the neighbours.
+const PADDING: u32 = 1;
+
+/// Slot of a tile texture in a [`TileAtlas`] page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct AtlasSlot {
+    pub(crate) page: usize,
+    index: u32,
+}
+
+/// Atlas slot shown by a tile, counted so the slot is freed once no tile shows it anymore.
+#[derive(Component, Clone, Copy, Debug)]
+#[component(immutable)]
+pub(crate) struct TileAtlasSlot(pub(crate) AtlasSlot);
+
+#[derive(Debug)]
+struct AtlasPage {
+    image: Handle<Image>,
+    layer: usize,
+    tile_size: UVec2,
+    columns: u32,
+    capacity: u32,
+    /// Slots `next..capacity` were never used
+    next: u32,
+    /// Slots freed again
+    free: Vec<u32>,
+    used: u32,
+}
+
+impl AtlasPage {
+    /// Transparent texture of a page, with the sampler of the tiles of its layer
+    fn image(sampler: ImageSampler) -> Image {
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: PAGE_SIZE,
+                height: PAGE_SIZE,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+        image.sampler = sampler;
+        image
+    }
+
+    /// Top left pixel of the tile in slot `index`, inside the padding
+    fn origin(&self, index: u32) -> UVec2 {
+        let slot = self.tile_size + 2 * PADDING;
+        UVec2::new(index % self.columns, index / self.columns) * slot + PADDING
+    }
+
+    fn allocate(&mut self) -> Option<u32> {
+        let index = self.free.pop().or_else(|| {
+            (self.next < self.capacity).then(|| {
+                self.next += 1;
+                self.next - 1
+            })
+        })?;
+        self.used += 1;
+        Some(index)
+    }
+}
+
+/// Textures of raster tiles packed into shared pages, so tiles are drawn in a few batches instead of one draw call
+/// each, see [`MapPlugin::tile_atlas`](crate::MapPlugin::tile_atlas).
+///
+/// Every page holds tiles of one layer and size. Tiles with the same content share a slot like they share a texture
+/// without the atlas. The [`TileAtlasSlot`]s of the tiles are counted when they are inserted and replaced,
+/// slots no tile shows anymore are freed in [`Last`] and pages without tiles are dropped.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct TileAtlas {
+    pages: Vec<Option<AtlasPage>>,
+    /// Slot of each layer and content hash
+    slots: HashMap<(usize, u64), AtlasSlot>,
+    /// Layer, content hash and number of tiles of each slot
+    users: HashMap<AtlasSlot, ((usize, u64), usize)>,
+    /// Whether a slot may have lost its last tile since the last eviction
+    unused: bool,
+}
+
+impl TileAtlas {
+    /// Slot showing `image`, the texture of a tile of `layer` with the content hash `hash`, copied into a page
+    /// unless a tile with the same content is already in the atlas.
+    ///
+    /// `None` if the image can't be packed, e.g. because it's larger than a page, the tile gets its own texture then.
+    pub(crate) fn get_or_add(
+        &mut self,
+        layer: usize,
+        hash: u64,
+        image: &Image,
+        images: &mut Assets<Image>,
+    ) -> Option<AtlasSlot> {
+        if let Some(slot) = self.slots.get(&(layer, hash)) {
+            return Some(*slot);
+        }
+        let size = image.size();
+        let pixels = image.data.as_deref()?;
+        if image.texture_descriptor.format != TextureFormat::Rgba8UnormSrgb
+            || size.min_element() == 0
+            || size.max_element() + 2 * PADDING > PAGE_SIZE
+        {
+            return None;
+        }
+        let slot = self.allocate(layer, size, image, images);
+        self.slots.insert((layer, hash), slot);
+        self.users.insert(slot, ((layer, hash), 0));
+        // freed again if no tile shows it by the end of the frame
+        self.unused = true;
+        let page = self.pages[slot.page].as_ref()?;
+        // uploads the whole page again, tiles arriving in the same frame share the upload
+        let page_image = images.get_mut(&page.image)?;
+        copy_padded(
+            page_image.data.as_mut()?,
+            page.origin(slot.index),
+            size,
+            pixels,
+        );
+        Some(slot)
+    }
+
+    fn allocate(
+        &mut self,
+        layer: usize,
+        size: UVec2,
+        image: &Image,
+        images: &mut Assets<Image>,
+    ) -> AtlasSlot {
+        for (page_index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(page) = page
+                && page.layer == layer
+                && page.tile_size == size
+                && let Some(index) = page.allocate()
+            {
+                return AtlasSlot {
+                    page: page_index,
+                    index,
+                };
+            }
+        }
+        let slot = size + 2 * PADDING;
+        let columns = PAGE_SIZE / slot.x;
+        let mut page = AtlasPage {
+            image: images.add(AtlasPage::image(image.sampler.clone())),
+            layer,
+            tile_size: size,
+            columns,
+            capacity: columns * (PAGE_SIZE / slot.y),
+            next: 0,
+            free: Vec::new(),
+            used: 0,
+        };
+        let index = page.allocate().expect("a new page has a free slot");
+        let page_index = match self.pages.iter().position(Option::is_none) {
+            Some(empty) => {
+                self.pages[empty] = Some(page);
+                empty
+            }
+            None => {
+                self.pages.push(Some(page));
+                self.pages.len() - 1
+            }
+        };
+        AtlasSlot {
+            page: page_index,
+            index,
+        }
+    }
+
+    /// Texture of the page of `slot`.
+    pub(crate) fn image(&self, slot: AtlasSlot) -> Handle<Image> {
+        self.pages[slot.page]
+            .as_ref()
+            .map(|page| page.image.clone())
+            .unwrap_or_default()
+    }
+
+    /// Pixel rect in the page of `slot` showing `rect`, given in pixels of the tile texture.
+    pub(crate) fn page_rect(&self, slot: AtlasSlot, rect: URect) -> Rect {
+        let origin = self.pages[slot.page]
+            .as_ref()
+            .map_or(UVec2::ZERO, |page| page.origin(slot.index));
+        URect::from_corners(rect.min + origin, rect.max + origin).as_rect()
+    }
+}
+
+/// Copies the RGBA `pixels` of a tile of `size` to `origin` in a page, repeating the edge pixels into the padding.
+fn copy_padded(page: &mut [u8], origin: UVec2, size: UVec2, pixels: &[u8]) {
+    let (width, height, padding) = (size.x as usize, size.y as usize, PADDING as usize);
+    let stride = PAGE_SIZE as usize * 4;
+    for y in 0..height + 2 * padding {
+        let source = &pixels[y.saturating_sub(padding).min(height - 1) * width * 4..][..width * 4];
+        let start = (origin.y as usize + y - padding) * stride + (origin.x as usize - padding) * 4;
+        let row = &mut page[start..start + (width + 2 * padding) * 4];
+        for x in 0..padding {
+            row[x * 4..x * 4 + 4].copy_from_slice(&source[..4]);
+            row[(padding + width + x) * 4..(padding + width + x) * 4 + 4]
+                .copy_from_slice(&source[(width - 1) * 4..]);
+        }
+        row[padding * 4..(padding + width) * 4].copy_from_slice(source);
+    }
+}
+
+fn count_tile(
+    insert: On<Insert, TileAtlasSlot>,
+    slots: Query<&TileAtlasSlot>,
+    mut atlas: ResMut<TileAtlas>,
+) {
+    let Ok(slot) = slots.get(insert.entity) else {
+        return;
+    };
+    if let Some((_, count)) = atlas.users.get_mut(&slot.0) {
+        *count += 1;
+    }
+}
+
+fn release_tile(
+    replace: On<Replace, TileAtlasSlot>,
+    slots: Query<&TileAtlasSlot>,
+    mut atlas: ResMut<TileAtlas>,
+) {
+    let Ok(slot) = slots.get(replace.entity) else {
+        return;
+    };
+    let atlas = &mut *atlas;
+    if let Some((_, count)) = atlas.users.get_mut(&slot.0) {
+        *count = count.saturating_sub(1);
+        atlas.unused |= *count == 0;
+    }
+}
+
+fn free_unused_slots(mut atlas: ResMut<TileAtlas>) {
+    if !atlas.unused {
+        return;
+    }
+    let TileAtlas {
+        pages,
+        slots,
+        users,
+        unused,
+    } = &mut *atlas;
+    *unused = false;
+    users.retain(|slot, (key, count)| {
+        if *count > 0 {
+            return true;
+        }
+        slots.remove(key);
+        if let Some(page) = &mut pages[slot.page] {
+            page.free.push(slot.index);
+            page.used -= 1;
+        }
+        if pages[slot.page].as_ref().is_some_and(|page| page.used == 0) {
+            pages[slot.page] = None;
+        }
+        false
+    });
+}
+
+pub(crate) fn tile_atlas_plugin(app: &mut App) {
+    app.init_resource::<TileAtlas>()
+        .add_observer(count_tile)
+        .add_observer(release_tile)
+        .add_systems(Last, free_unused_slots);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile_image(size: u32, color: [u8; 4]) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &color,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+
+    fn atlas_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .add_plugins(tile_atlas_plugin);
+        app
+    }
+
+    fn add(app: &mut App, layer: usize, hash: u64, image: &Image) -> Option<AtlasSlot> {
+        app.world_mut()
+            .resource_scope(|world, mut atlas: Mut<TileAtlas>| {
+                atlas.get_or_add(
+                    layer,
+                    hash,
+                    image,
+                    &mut world.resource_mut::<Assets<Image>>(),
+                )
+            })
+    }
+
+    fn page_pixel(app: &App, slot: AtlasSlot, pixel: UVec2) -> [u8; 4] {
+        let atlas = app.world().resource::<TileAtlas>();
+        let image = app
+            .world()
+            .resource::<Assets<Image>>()
+            .get(&atlas.image(slot))
+            .unwrap();
+        let start = (pixel.y * PAGE_SIZE + pixel.x) as usize * 4;
+        image.data.as_ref().unwrap()[start..start + 4]
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn tiles_are_copied_with_padding() {
+        let mut app = atlas_app();
+        let red = [255, 0, 0, 255];
+        let green = [0, 255, 0, 255];
+        let first = add(&mut app, 0, 1, &tile_image(256, red)).unwrap();
+        let second = add(&mut app, 0, 2, &tile_image(256, green)).unwrap();
+        assert_eq!(first.page, second.page);
+        // the same content of a layer shares the slot
+        assert_eq!(add(&mut app, 0, 1, &tile_image(256, red)), Some(first));
+
+        let atlas = app.world().resource::<TileAtlas>();
+        let rect = atlas.page_rect(second, URect::new(0, 0, 256, 256));
+        assert_eq!(rect.size(), Vec2::splat(256.0));
+        let min = rect.min.as_uvec2();
+        assert_eq!(min, UVec2::new(256 + 3 * PADDING, PADDING));
+        assert_eq!(page_pixel(&app, second, min), green);
+        assert_eq!(page_pixel(&app, second, min + 255), green);
+        // the padding repeats the edges, the neighbouring tile starts behind it
+        assert_eq!(page_pixel(&app, second, min - UVec2::new(1, 0)), green);
+        assert_eq!(page_pixel(&app, second, min - UVec2::new(2, 0)), red);
+    }
+
+    #[test]
+    fn layers_and_sizes_use_separate_pages() {
+        let mut app = atlas_app();
+        let base = add(&mut app, 0, 1, &tile_image(256, [0; 4])).unwrap();
+        let overlay = add(&mut app, 1, 1, &tile_image(256, [0; 4])).unwrap();
+        let large = add(&mut app, 0, 2, &tile_image(512, [0; 4])).unwrap();
+        assert_ne!(base.page, overlay.page);
+        assert_ne!(base.page, large.page);
+        // too large for a page
+        assert_eq!(add(&mut app, 0, 3, &tile_image(PAGE_SIZE, [0; 4])), None);
+    }
+
+    #[test]
+    fn slots_are_freed_after_their_last_tile() {
+        let mut app = atlas_app();
+        let image = tile_image(256, [0; 4]);
+        let slot = add(&mut app, 0, 1, &image).unwrap();
+        let tiles = [
+            app.world_mut().spawn(TileAtlasSlot(slot)).id(),
+            app.world_mut().spawn(TileAtlasSlot(slot)).id(),
+        ];
+        // a slot nobody uses yet is freed at the end of the frame
+        add(&mut app, 0, 2, &image).unwrap();
+        app.update();
+        assert_eq!(app.world().resource::<TileAtlas>().slots.len(), 1);
+
+        app.world_mut().entity_mut(tiles[0]).despawn();
+        app.update();
+        assert_eq!(app.world().resource::<TileAtlas>().slots.len(), 1);
+
+        app.world_mut().entity_mut(tiles[1]).despawn();
+        app.update();
+        let atlas = app.world().resource::<TileAtlas>();
+        assert!(atlas.slots.is_empty());
+        // the page without tiles is dropped
+        assert!(atlas.pages.iter().all(Option::is_none));
+    }
+}
@@ -1,61 +1,425 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     fmt, fs,
-    path::PathBuf,
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex, mpsc},
+    time::{Duration, Instant},
 };
 
 use bevy::{
     asset::RenderAssetUsages,
+    ecs::system::SystemParam,
+    image::ImageSampler,
     log::tracing::trace_span,
     log::*,
+    math::DVec2,
     prelude::*,
     render::render_resource::{Extent3d, TextureDimension, TextureFormat},
-    tasks::IoTaskPool,
+    tasks::{IoTaskPool, Task, block_on, futures_lite::future},
 };
-use image::{GenericImageView, ImageError};
+use image::{ImageError, RgbaImage, imageops};
+#[cfg(feature = "async_http")]
+use reqwest::Client;
+#[cfg(not(feature = "async_http"))]
+use reqwest::blocking::Client;
 use reqwest::{
     StatusCode,
-    blocking::Client,
-    header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue},
+    header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue},
 };
 use tilemath::Tile as TileMathTile;
 
-use crate::Tile;
+#[cfg(feature = "mvt")]
+use crate::vector_tiles::{
+    VectorFeature, VectorTileStyle, decode_vector_tile, spawn_vector_features,
+};
+use crate::{
+    CurrentZoom, MainCam, MercatorAabb2d, MercatorCoords, TILE_SIZE, Tile, TileLayer, TileSource,
+    TileStreamingConfig, ToTileCoords, WebMercatorConversion, WorldCopy,
+    coord_conversions::WEB_MERCATOR_EXTENT,
+    plugin::{ZoomFade, tile_z},
+    shared_assets::{AssetUser, SharedAssets, shared_assets_plugin},
+    tile_atlas::{TileAtlas, TileAtlasSlot},
+};
+
+pub(crate) const DEFAULT_USER_AGENT: &str = "bevy-geo-tiles/0.1";
+
+/// How long a fallback source that succeeded is tried first, see [`TileFetchConfig::fallback_sources`].
+const FALLBACK_MEMORY: Duration = Duration::from_secs(60);
+
+/// Largest width and height in pixels of an image assembled by [`stitch_cached_tiles`], about 1 GiB of RGBA pixels.
+const MAX_STITCHED_SIZE: u32 = 16_384;
 
 /// Configuration for downloading map tiles.
 #[derive(Resource, Clone, Debug)]
 pub struct TileFetchConfig {
     /// Template URL that contains `{z}`, `{x}`, and `{y}` placeholders.
+    /// An optional `{r}` placeholder is replaced with `retina_suffix` for high-DPI tiles.
+    /// Servers addressing tiles by quadkey (e.g. Bing Maps) can use a `{quadkey}` placeholder instead of `{z}/{x}/{y}`.
+    ///
+    /// With [`wms`](Self::wms) enabled, `{bbox}`, `{width}` and `{height}` are replaced instead.
     pub url_template: String,
+    /// URL templates of further servers providing the same tiles, tried in order when a request fails with a network
+    /// or server error. A fallback that succeeded is tried first for the next minute, then `url_template` again.
+    pub fallback_sources: Vec<String>,
     /// Optional HTTP headers sent with every tile request.
     pub headers: HashMap<String, String>,
     /// Directory used to cache downloaded tiles on disk.
     pub cache_directory: PathBuf,
+    /// Whether downloaded tiles are cached on disk, if disabled nothing is read from or written to
+    /// `cache_directory` and tiles are only kept in memory while loaded (defaults to `true`).
+    pub cache_enabled: bool,
     /// File extension used when caching tiles locally (defaults to `png`).
     pub cache_extension: String,
-    /// Whether to use TMS-style Y coordinates (origin bottom-left) instead of XYZ-style (origin top-left).
+    /// Whether the server uses TMS-style Y coordinates (origin bottom-left) instead of XYZ-style (origin top-left).
+    /// Keep this disabled for OpenStreetMap and most other slippy map servers.
     pub reverse_y: bool,
     /// zoom level offset applied when fetching tiles (can be negative).
-    /// For example, with an offset of -1, tile 3/4/2 will be fetched when tile 4/4/2 is requested.
+    /// For example, with an offset of -1, tile 3/2/1 will be fetched when tile 4/4/2 is requested, e.g. for servers with 512px tiles.
+    /// The four tiles covered by a server tile share a single download, each showing its quarter of the image.
+    /// To show 512px tiles at their full size instead, set `tile_size` (and [`MapPlugin::tile_size`](crate::MapPlugin::tile_size))
+    /// to 512 and keep the offset at 0.
+    /// With a positive offset each tile is combined from several server tiles, e.g. with an offset of 1
+    /// tile 4/4/2 is combined from the tiles 5/8/4, 5/9/4, 5/8/5 and 5/9/5 for servers with 128px tiles.
+    /// Positive offsets are only supported for image tiles.
     pub zoom_offset: i8,
+    /// Optional tile source used instead of downloading from `url_template` (e.g. an MBTiles file).
+    /// Tiles loaded from a custom source are not cached on disk. If [`TileSource::network_fallback`]
+    /// is set, tiles missing from the source are downloaded from `url_template` as usual.
+    pub custom_source: Option<Arc<dyn TileSource>>,
+    /// Opacity of the rendered tiles, between 0.0 (invisible) and 1.0 (opaque).
+    pub opacity: f32,
+    /// Color multiplied into the tile images, e.g. a dark gray to dim the map for a dark theme (defaults to white, i.e. unchanged).
+    /// The alpha of the tint is combined with `opacity`.
+    pub tile_tint: Color,
+    /// Draws the tiles as vector shapes with this style instead of images,
+    /// for servers providing [Mapbox Vector Tiles](https://github.com/mapbox/vector-tile-spec) (`.pbf`).
+    #[cfg(feature = "mvt")]
+    pub vector_style: Option<VectorTileStyle>,
+    /// Maximum time to wait for a connection to the tile server, `None` waits indefinitely.
+    pub connect_timeout: Option<Duration>,
+    /// Maximum time for a whole tile request including the download, `None` waits indefinitely.
+    /// Timed out requests fail with [`TileFetchError::Network`].
+    pub request_timeout: Option<Duration>,
+    /// Optional API key of commercial tile providers and where to send it.
+    /// The key is never included in logs or error messages.
+    pub api_key: Option<(ApiKeyLocation, ApiKey)>,
+    /// Size of the tiles on screen in pixels, should match [`MapPlugin::tile_size`](crate::MapPlugin::tile_size),
+    /// which sets it for the base layer (defaults to 256).
+    /// WMS tiles are requested and [`stitch_cached_tiles`] assembles its images with this size times `tile_pixel_ratio`.
+    pub tile_size: u32,
+    /// Request high-DPI tiles (e.g. 512px instead of 256px) when greater than 1, for sharper maps on high-DPI screens.
+    /// The tiles keep their size on screen.
+    pub tile_pixel_ratio: u8,
+    /// Suffix of high-DPI tiles, inserted at the `{r}` placeholder or after `{y}` if the template has none.
+    /// Defaults to `@2x`
+    pub retina_suffix: String,
+    /// Request tiles from a WMS server with bbox-based `GetMap` requests instead of z/x/y tiles.
+    ///
+    /// `{bbox}` in `url_template` is replaced with the tile bounds in EPSG:3857 as `minx,miny,maxx,maxy`,
+    /// `{width}` and `{height}` with the requested size in pixels, e.g.
+    /// `https://example.com/wms?SERVICE=WMS&REQUEST=GetMap&VERSION=1.3.0&LAYERS=roads&CRS=EPSG:3857&BBOX={bbox}&WIDTH={width}&HEIGHT={height}&FORMAT=image/png`.
+    /// `reverse_y` and `zoom_offset` have no effect in this mode.
+    pub wms: bool,
+    /// Texture filtering of the tiles, noticeable when tiles are scaled up between zoom levels.
+    pub tile_filter_mode: TileFilterMode,
+    /// Number of consecutive failed downloads (network errors, server errors and rate limiting) after which new
+    /// requests are paused for `failure_cooldown`, then a single request probes whether the server recovered.
+    /// Tiles requested in the meantime keep loading instead of failing. `0` never pauses requests.
+    pub max_consecutive_failures: u32,
+    /// How long requests are paused after `max_consecutive_failures`, see there.
+    pub failure_cooldown: Duration,
+    /// Zoom levels available on the server, tiles of other zoom levels (after applying `zoom_offset`) are not requested.
+    pub zoom_range: RangeInclusive<u8>,
+    /// Attribution text of the tile provider, e.g. `© OpenStreetMap contributors`.
+    pub attribution: Option<String>,
+}
+
+/// Texture filtering used for tile images, see [`TileFetchConfig::tile_filter_mode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TileFilterMode {
+    /// Crisp, blocky pixels, e.g. for pixel-art style maps
+    Nearest,
+    /// Smooth interpolation between pixels
+    #[default]
+    Linear,
+}
+
+impl TileFilterMode {
+    fn sampler(self) -> ImageSampler {
+        match self {
+            TileFilterMode::Nearest => ImageSampler::nearest(),
+            TileFilterMode::Linear => ImageSampler::linear(),
+        }
+    }
+}
+
+/// Where an [`ApiKey`] is sent with tile requests.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ApiKeyLocation {
+    /// `Authorization: Bearer <key>` header
+    BearerHeader,
+    /// Custom header with the given name, e.g. `X-Api-Key`
+    Header(String),
+    /// Query parameter with the given name appended to the tile URL, e.g. `key`
+    QueryParam(String),
+}
+
+/// Secret API key, redacted in its `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+    /// Wraps `key` so it doesn't end up in debug output.
+    pub fn new(key: impl Into<String>) -> Self {
+        Self(key.into())
+    }
+
+    /// The actual key, take care not to log it.
+    pub fn secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ApiKey(<redacted>)")
+    }
+}
+
+impl From<String> for ApiKey {
+    fn from(key: String) -> Self {
+        Self(key)
+    }
+}
+
+impl From<&str> for ApiKey {
+    fn from(key: &str) -> Self {
+        Self(key.to_string())
+    }
 }
 
 impl Default for TileFetchConfig {
     fn default() -> Self {
         let mut headers = HashMap::new();
-        headers.insert("User-Agent".to_string(), "bevy-geo-tiles/0.1".to_string());
+        headers.insert("User-Agent".to_string(), DEFAULT_USER_AGENT.to_string());
         Self {
             url_template: "https://tile.openstreetmap.org/{z}/{x}/{y}.png".to_string(),
+            fallback_sources: Vec::new(),
             headers,
             cache_directory: default_cache_dir(),
+            cache_enabled: true,
             cache_extension: "png".to_string(),
             reverse_y: false,
             zoom_offset: 0,
+            custom_source: None,
+            opacity: 1.0,
+            tile_tint: Color::WHITE,
+            #[cfg(feature = "mvt")]
+            vector_style: None,
+            connect_timeout: Some(Duration::from_secs(10)),
+            request_timeout: Some(Duration::from_secs(30)),
+            api_key: None,
+            tile_size: TILE_SIZE as u32,
+            tile_pixel_ratio: 1,
+            retina_suffix: "@2x".to_string(),
+            wms: false,
+            tile_filter_mode: TileFilterMode::Linear,
+            max_consecutive_failures: 10,
+            failure_cooldown: Duration::from_secs(30),
+            zoom_range: 0..=u8::MAX,
+            attribution: None,
+        }
+    }
+}
+
+impl TileFetchConfig {
+    /// Whether tiles are downloaded from the OpenStreetMap tile servers without a descriptive `User-Agent` header,
+    /// either as `url_template` or as one of the `fallback_sources`.
+    ///
+    /// The [tile usage policy](https://operations.osmfoundation.org/policies/tiles/) requires
+    /// an application specific `User-Agent`, requests with the default one may get blocked.
+    pub fn uses_default_osm_user_agent(&self) -> bool {
+        let downloads = self
+            .custom_source
+            .as_ref()
+            .is_none_or(|source| source.network_fallback());
+        let uses_osm = std::iter::once(&self.url_template)
+            .chain(&self.fallback_sources)
+            .any(|template| template.contains("openstreetmap.org"));
+        if !downloads || !uses_osm {
+            return false;
+        }
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("User-Agent"))
+            .is_none_or(|(_, value)| value.trim().is_empty() || value == DEFAULT_USER_AGENT)
+    }
+
+    /// Path at which `tile` is cached, e.g. to pre-seed the cache with tiles downloaded by an external tool.
+    ///
+    /// `tile` uses the coordinates of the tile server as in the `{z}/{x}/{y}` URL placeholders,
+    /// i.e. XYZ-style y unless `reverse_y` is set. The layout is
+    /// `<cache_directory>/<z>/<x>/<y><retina_suffix>.<cache_extension>`, where `retina_suffix`
+    /// is only added if `tile_pixel_ratio` is greater than 1.
+    pub fn cache_path_for(&self, tile: &TileMathTile) -> PathBuf {
+        let (_, retina_suffix) = retina_template(self, &self.url_template);
+        tile_cache_path(
+            &self.cache_directory,
+            &retina_suffix,
+            &self.cache_extension,
+            tile,
+        )
+    }
+}
+
+/// Well-known public tile servers with their URL template, zoom range and attribution filled in,
+/// see [`TileFetchConfig::from_preset`] and [`MapPlugin::from_preset`](crate::MapPlugin::from_preset).
+///
+/// Mind the usage policy of the provider, e.g. OpenStreetMap requires an application specific `User-Agent` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TilePreset {
+    /// Standard OpenStreetMap tiles from `tile.openstreetmap.org`
+    OpenStreetMap,
+    /// Topographic map with contour lines and hill shading from OpenTopoMap
+    OpenTopoMap,
+    /// Light, low contrast CARTO basemap for data visualisation
+    CartoPositron,
+    /// Dark CARTO basemap for data visualisation
+    CartoDarkMatter,
+    /// Satellite and aerial imagery from Esri
+    EsriWorldImagery,
+}
+
+impl TilePreset {
+    /// URL template of the tile server, see [`TileFetchConfig::url_template`].
+    pub fn url_template(self) -> &'static str {
+        match self {
+            Self::OpenStreetMap => "https://tile.openstreetmap.org/{z}/{x}/{y}.png",
+            Self::OpenTopoMap => "https://tile.opentopomap.org/{z}/{x}/{y}.png",
+            Self::CartoPositron => "https://basemaps.cartocdn.com/light_all/{z}/{x}/{y}{r}.png",
+            Self::CartoDarkMatter => "https://basemaps.cartocdn.com/dark_all/{z}/{x}/{y}{r}.png",
+            Self::EsriWorldImagery => {
+                "https://server.arcgisonline.com/ArcGIS/rest/services/World_Imagery/MapServer/tile/{z}/{y}/{x}"
+            }
+        }
+    }
+
+    /// Zoom levels provided by the tile server.
+    pub fn zoom_range(self) -> RangeInclusive<u8> {
+        match self {
+            Self::OpenStreetMap => 0..=19,
+            Self::OpenTopoMap => 0..=17,
+            Self::CartoPositron | Self::CartoDarkMatter => 0..=20,
+            Self::EsriWorldImagery => 0..=19,
+        }
+    }
+
+    /// Attribution the provider requires to be shown with the map.
+    pub fn attribution(self) -> &'static str {
+        match self {
+            Self::OpenStreetMap => "© OpenStreetMap contributors",
+            Self::OpenTopoMap => {
+                "Map data: © OpenStreetMap contributors, SRTM | Map style: © OpenTopoMap (CC-BY-SA)"
+            }
+            Self::CartoPositron | Self::CartoDarkMatter => "© OpenStreetMap contributors © CARTO",
+            Self::EsriWorldImagery => {
+                "Tiles © Esri — Source: Esri, i-cubed, USDA, USGS, AEX, GeoEye, Getmapping, Aerogrid, IGN, IGP, UPR-EGP, and the GIS User Community"
+            }
+        }
+    }
+
+    /// Name of the subdirectory of the default cache directory, so the tiles of different presets don't mix
+    fn cache_name(self) -> &'static str {
+        match self {
+            Self::OpenStreetMap => "openstreetmap",
+            Self::OpenTopoMap => "opentopomap",
+            Self::CartoPositron => "carto_positron",
+            Self::CartoDarkMatter => "carto_dark_matter",
+            Self::EsriWorldImagery => "esri_world_imagery",
+        }
+    }
+}
+
+impl TileFetchConfig {
+    /// Configures the URL template, zoom range, attribution and cache directory of a well-known tile server,
+    /// the other settings keep their defaults and can still be changed afterwards.
+    pub fn from_preset(preset: TilePreset) -> Self {
+        Self {
+            url_template: preset.url_template().to_string(),
+            cache_directory: default_cache_dir().join(preset.cache_name()),
+            cache_extension: match preset {
+                TilePreset::EsriWorldImagery => "jpg".to_string(),
+                _ => "png".to_string(),
+            },
+            zoom_range: preset.zoom_range(),
+            attribution: Some(preset.attribution().to_string()),
+            ..default()
         }
     }
 }
 
+impl From<TilePreset> for TileFetchConfig {
+    fn from(preset: TilePreset) -> Self {
+        Self::from_preset(preset)
+    }
+}
+
+#[cfg(feature = "tilejson")]
+#[derive(serde::Deserialize)]
+struct TileJson {
+    tiles: Vec<String>,
+    minzoom: Option<u8>,
+    maxzoom: Option<u8>,
+    attribution: Option<String>,
+    scheme: Option<String>,
+}
+
+#[cfg(feature = "tilejson")]
+impl TileFetchConfig {
+    /// Fetches a [TileJSON](https://github.com/mapbox/tilejson-spec) document and configures the URL template,
+    /// zoom range, attribution and y convention from it, the other settings keep their defaults.
+    ///
+    /// This blocks until the document is downloaded, call it once at startup.
+    pub fn from_tilejson(url: &str) -> Result<Self, TileFetchError> {
+        let mut config = Self::default();
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        let client = reqwest::blocking::Client::builder()
+            .default_headers(headers)
+            .timeout(config.request_timeout)
+            .build()
+            .map_err(TileFetchError::from_network)?;
+        let response = client
+            .get(url)
+            .send()
+            .map_err(TileFetchError::from_network)?;
+        if !response.status().is_success() {
+            return Err(TileFetchError::HttpStatus(response.status()));
+        }
+        let tilejson: TileJson = response
+            .json()
+            .map_err(|err| TileFetchError::Decode(err.without_url().to_string()))?;
+
+        config.url_template =
+            tilejson.tiles.into_iter().next().ok_or_else(|| {
+                TileFetchError::Decode("TileJSON contains no tile URL".to_string())
+            })?;
+        config.zoom_range = tilejson.minzoom.unwrap_or(0)..=tilejson.maxzoom.unwrap_or(30);
+        config.attribution = tilejson.attribution;
+        config.reverse_y = tilejson.scheme.as_deref() == Some("tms");
+        Ok(config)
+    }
+}
+
 pub(crate) fn default_cache_dir() -> PathBuf {
     std::env::var("BEVY_GEO_TILES_CACHE")
         .map(PathBuf::from)
@@ -69,6 +433,15 @@ pub enum TileFetchError {
     Network(String),
     Io(String),
     Decode(String),
+    /// The server responded with something other than an image, e.g. an HTML error page.
+    /// Contains the reported content type.
+    NotAnImage(String),
+    NotFound,
+    /// An image assembled by [`stitch_cached_tiles`] would exceed the maximum size, contains the requested size in pixels.
+    ImageTooLarge {
+        width: u64,
+        height: u64,
+    },
 }
 
 impl fmt::Display for TileFetchError {
@@ -78,6 +451,14 @@ impl fmt::Display for TileFetchError {
             TileFetchError::Network(err) => write!(f, "network error: {err}"),
             TileFetchError::Io(err) => write!(f, "io error: {err}"),
             TileFetchError::Decode(err) => write!(f, "decode error: {err}"),
+            TileFetchError::NotAnImage(content_type) => {
+                write!(f, "server returned {content_type} instead of an image")
+            }
+            TileFetchError::NotFound => write!(f, "tile not found in source"),
+            TileFetchError::ImageTooLarge { width, height } => write!(
+                f,
+                "stitched image of {width}x{height} pixels exceeds the maximum of {MAX_STITCHED_SIZE}x{MAX_STITCHED_SIZE}"
+            ),
         }
     }
 }
@@ -86,7 +467,8 @@ impl std::error::Error for TileFetchError {}
 
 impl TileFetchError {
     fn from_network(err: reqwest::Error) -> Self {
-        Self::Network(err.to_string())
+        // the URL may contain an API key
+        Self::Network(err.without_url().to_string())
     }
 
     fn from_io(err: std::io::Error) -> Self {
@@ -99,45 +481,438 @@ impl TileFetchError {
 }
 
 #[derive(Debug)]
-struct PreparedConfig {
-    template: String,
+pub(crate) struct PreparedConfig {
+    /// `url_template` followed by the `fallback_sources`
+    templates: Vec<String>,
+    /// Fallback source that succeeded last and when
+    preferred_source: Mutex<Option<(usize, Instant)>>,
+    /// `retina_suffix` if high-DPI tiles are requested, otherwise empty
+    retina_suffix: String,
+    api_key_query: Option<(String, ApiKey)>,
     headers: Vec<(HeaderName, HeaderValue)>,
     cache_directory: PathBuf,
+    cache_enabled: bool,
     cache_extension: String,
+    custom_source: Option<Arc<dyn TileSource>>,
+    opacity: f32,
+    tint: Color,
+    #[cfg(feature = "mvt")]
+    vector_style: Option<VectorTileStyle>,
+    reverse_y: bool,
+    zoom_offset: i8,
+    wms: bool,
+    /// requested width and height of a tile in pixels
+    tile_pixels: u32,
+    filter_mode: TileFilterMode,
+    max_consecutive_failures: u32,
+    failure_cooldown: Duration,
+    zoom_range: RangeInclusive<u8>,
 }
 
 impl PreparedConfig {
-    fn format_url(&self, tile: &TileMathTile) -> String {
-        self.template
-            .replace("{z}", &tile.zoom.to_string())
-            .replace("{x}", &tile.x.to_string())
-            .replace("{y}", &tile.y.to_string())
+    /// Whether the layer serves vector tiles instead of images
+    fn is_vector(&self) -> bool {
+        #[cfg(feature = "mvt")]
+        if self.vector_style.is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Indices of the `templates` in the order they are tried.
+    fn source_order(&self) -> Vec<usize> {
+        let preferred = self
+            .preferred_source
+            .lock()
+            .unwrap()
+            .filter(|(_, since)| since.elapsed() < FALLBACK_MEMORY)
+            .map_or(0, |(source, _)| source);
+        std::iter::once(preferred)
+            .chain((0..self.templates.len()).filter(|source| *source != preferred))
+            .collect()
+    }
+
+    /// Whether the server has the tiles shown at zoom level `zoom`, after applying the `zoom_offset`
+    pub(crate) fn serves_zoom(&self, zoom: u8) -> bool {
+        let tile = TileMathTile { zoom, x: 0, y: 0 };
+        let request = server_tile(&tile, self.reverse_y, self.zoom_offset, self.wms);
+        let server_zoom = request.zoom + zoom_in_levels(&request, self.zoom_offset, self.wms);
+        self.zoom_range.contains(&server_zoom)
+    }
+
+    fn remember_source(&self, source: usize) {
+        *self.preferred_source.lock().unwrap() = (source != 0).then(|| (source, Instant::now()));
+    }
+
+    fn format_url(&self, source: usize, tile: &TileMathTile) -> String {
+        let template = &self.templates[source];
+        let url = if self.wms {
+            let bbox = crate::tile_to_mercator_aabb(*tile);
+            template
+                .replace(
+                    "{bbox}",
+                    &format!(
+                        "{},{},{},{}",
+                        bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y
+                    ),
+                )
+                .replace("{width}", &self.tile_pixels.to_string())
+                .replace("{height}", &self.tile_pixels.to_string())
+        } else {
+            template
+                .replace("{z}", &tile.zoom.to_string())
+                .replace("{x}", &tile.x.to_string())
+                .replace("{y}", &tile.y.to_string())
+                .replace("{r}", &self.retina_suffix)
+                .replace("{quadkey}", &quadkey(tile))
+        };
+        let Some((name, key)) = &self.api_key_query else {
+            return url;
+        };
+        match reqwest::Url::parse(&url) {
+            Ok(mut parsed) => {
+                parsed.query_pairs_mut().append_pair(name, key.secret());
+                parsed.into()
+            }
+            Err(_) => url,
+        }
+    }
+
+    /// Whether tiles of this layer are downloaded from the tile server, i.e. it has no custom source
+    /// or tiles missing from the custom source are downloaded instead
+    fn downloads_tiles(&self) -> bool {
+        self.custom_source
+            .as_ref()
+            .is_none_or(|source| source.network_fallback())
+    }
+
+    /// Whether downloaded tiles of this layer are read from and written to the `cache_directory`
+    fn uses_disk_cache(&self) -> bool {
+        self.cache_enabled && self.downloads_tiles()
     }
 
     fn cache_path(&self, tile: &TileMathTile) -> PathBuf {
-        let mut path = self.cache_directory.clone();
-        path.push(tile.zoom.to_string());
-        path.push(tile.x.to_string());
-        path.push(format!("{}.{}", tile.y, self.cache_extension));
-        path
+        tile_cache_path(
+            &self.cache_directory,
+            &self.retina_suffix,
+            &self.cache_extension,
+            tile,
+        )
+    }
+
+    pub(crate) fn sprite_color(&self) -> Color {
+        self.tint.with_alpha(self.tint.alpha() * self.opacity)
+    }
+}
+
+fn tile_cache_path(
+    cache_directory: &Path,
+    retina_suffix: &str,
+    cache_extension: &str,
+    tile: &TileMathTile,
+) -> PathBuf {
+    let mut path = cache_directory.to_path_buf();
+    path.push(tile.zoom.to_string());
+    path.push(tile.x.to_string());
+    // high-DPI tiles are cached separately from regular ones
+    path.push(format!("{}{}.{}", tile.y, retina_suffix, cache_extension));
+    path
+}
+
+/// Returns the URL template with an `{r}` placeholder and the suffix to insert for it.
+fn retina_template(config: &TileFetchConfig, url_template: &str) -> (String, String) {
+    if config.tile_pixel_ratio <= 1 || config.wms {
+        return (url_template.to_string(), String::new());
+    }
+    let template = if url_template.contains("{r}") {
+        url_template.to_string()
+    } else {
+        url_template.replacen("{y}", "{y}{r}", 1)
+    };
+    (template, config.retina_suffix.clone())
+}
+
+/// Bing Maps style quadkey of an XYZ tile, one base-4 digit per zoom level interleaving the x and y bits.
+fn quadkey(tile: &TileMathTile) -> String {
+    (1..=tile.zoom)
+        .rev()
+        .map(|level| {
+            let mask = 1 << (level - 1);
+            let digit = (tile.x & mask != 0) as u8 + 2 * (tile.y & mask != 0) as u8;
+            char::from(b'0' + digit)
+        })
+        .collect()
+}
+
+/// Converts an internal tile to the tile requested from the tile server.
+///
+/// Internal tiles use TMS-style y coordinates (origin bottom-left, like the mercator y axis),
+/// so the y coordinate is flipped for XYZ-style servers (`reverse_y == false`, e.g. OpenStreetMap)
+/// and kept as is for TMS servers.
+/// WMS requests are based on the bounds of the internal tile, which is returned unchanged.
+///
+/// With a negative zoom offset this is the server tile containing `tile`, see [`server_tile_region`] for its part of the image.
+/// With a positive offset the tile keeps its zoom level and is combined from the tiles of [`server_subtiles`].
+pub(crate) fn server_tile(
+    tile: &TileMathTile,
+    reverse_y: bool,
+    zoom_offset: i8,
+    wms: bool,
+) -> TileMathTile {
+    if wms {
+        return *tile;
+    }
+    // flip within the row count of the displayed zoom level, before the zoom offset is applied
+    let y = if reverse_y {
+        tile.y
+    } else {
+        (1 << tile.zoom) - 1 - tile.y
+    };
+    let levels = zoom_out_levels(tile, zoom_offset);
+    if levels > 0 {
+        // the server tile containing the tile, several tiles share it
+        return TileMathTile {
+            zoom: tile.zoom - levels,
+            x: tile.x >> levels,
+            y: y >> levels,
+        };
+    }
+    // a positive offset is applied when fetching, the tile is combined from several server tiles
+    TileMathTile {
+        zoom: tile.zoom,
+        x: tile.x,
+        y,
+    }
+}
+
+/// How many zoom levels a positive zoom offset moves the server tiles down from `tile`.
+fn zoom_in_levels(tile: &TileMathTile, zoom_offset: i8, wms: bool) -> u8 {
+    if zoom_offset > 0 && !wms {
+        // tile coordinates have to fit into a u32
+        (zoom_offset as u8).min(31u8.saturating_sub(tile.zoom))
+    } else {
+        0
+    }
+}
+
+/// Server tiles combined into the image of `request` (see [`server_tile`]) for a positive zoom offset,
+/// row by row from the top of the image. Contains only `request` itself without a positive offset.
+fn server_subtiles(
+    request: &TileMathTile,
+    reverse_y: bool,
+    zoom_offset: i8,
+    wms: bool,
+) -> Vec<TileMathTile> {
+    let levels = zoom_in_levels(request, zoom_offset, wms);
+    if levels == 0 {
+        return vec![*request];
+    }
+    let divisions = 1 << levels;
+    let mut subtiles = Vec::with_capacity((divisions * divisions) as usize);
+    for row in 0..divisions {
+        // XYZ rows count from the top like the image rows, TMS rows from the bottom
+        let y = if reverse_y { divisions - 1 - row } else { row };
+        for column in 0..divisions {
+            subtiles.push(TileMathTile {
+                zoom: request.zoom + levels,
+                x: (request.x << levels) + column,
+                y: (request.y << levels) + y,
+            });
+        }
+    }
+    subtiles
+}
+
+/// How many zoom levels a negative zoom offset moves the server tile up from `tile`.
+fn zoom_out_levels(tile: &TileMathTile, zoom_offset: i8) -> u8 {
+    if zoom_offset < 0 {
+        zoom_offset.unsigned_abs().min(tile.zoom)
+    } else {
+        0
+    }
+}
+
+/// Part of the server tile image showing `tile` if a negative zoom offset maps several tiles to one server tile,
+/// as `(column, row, divisions)` of the image split into `divisions` x `divisions` parts, rows counted from the top.
+fn server_tile_region(tile: &TileMathTile, zoom_offset: i8, wms: bool) -> Option<(u32, u32, u32)> {
+    let levels = zoom_out_levels(tile, zoom_offset);
+    if wms || levels == 0 {
+        return None;
+    }
+    let divisions = 1 << levels;
+    let mask = divisions - 1;
+    // internal rows count from the south, image rows from the top regardless of the server's y convention
+    let row_from_top = (1 << tile.zoom) - 1 - tile.y;
+    Some((tile.x & mask, row_from_top & mask, divisions))
+}
+
+/// Pixel rect of `region` (see [`server_tile_region`]) in an image of `size` pixels.
+fn region_rect(size: UVec2, (column, row, divisions): (u32, u32, u32)) -> URect {
+    let part = size / divisions;
+    let min = UVec2::new(column, row) * part;
+    URect::from_corners(min, min + part)
+}
+
+/// Content of a fetched tile
+#[derive(Debug, PartialEq)]
+enum TileData {
+    /// The file as served by the tile server or stored in the cache, e.g. a PNG or vector tile
+    Encoded(Vec<u8>),
+    /// Pixels assembled on the IO task pool, e.g. by [`combine_subtiles`]
+    Decoded(RgbaImage),
+}
+
+impl TileData {
+    /// Identifies the content, tiles with the same content share a texture
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        match self {
+            TileData::Encoded(bytes) => bytes.hash(&mut hasher),
+            TileData::Decoded(image) => {
+                image.dimensions().hash(&mut hasher);
+                image.as_raw().hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
+    fn into_rgba(self) -> Result<RgbaImage, TileFetchError> {
+        match self {
+            TileData::Encoded(bytes) => image::load_from_memory(&bytes)
+                .map(|image| image.to_rgba8())
+                .map_err(TileFetchError::from_decode),
+            TileData::Decoded(image) => Ok(image),
+        }
     }
 }
 
 #[derive(Debug)]
 struct TileImagePayload {
-    bytes: Vec<u8>,
+    data: TileData,
     cached_path: Option<PathBuf>,
     content_type: Option<String>,
     from_cache: bool,
+    /// Whether the tile was requested from a tile server, as opposed to the disk cache or a custom source
+    downloaded: bool,
 }
 
-#[derive(Resource, Debug)]
-pub struct TileFetcher {
+/// A fetched tile decoded on the IO task pool, so only the texture upload is left for the main schedule
+#[derive(Debug)]
+struct DecodedTile {
+    content: Result<TileContent, TileFetchError>,
+    cached_path: Option<PathBuf>,
+    content_type: Option<String>,
+    from_cache: bool,
+}
+
+#[derive(Debug)]
+enum TileContent {
+    /// `hash` identifies the tile content, tiles with the same content share a texture
+    Raster { image: Image, hash: u64 },
+    #[cfg(feature = "mvt")]
+    Vector(Vec<VectorFeature>),
+}
+
+impl DecodedTile {
+    /// Decodes the payload of the server tile `tile`.
+    #[cfg_attr(not(feature = "mvt"), allow(unused_variables))]
+    fn decode(payload: TileImagePayload, config: &PreparedConfig, tile: &TileMathTile) -> Self {
+        #[cfg(feature = "mvt")]
+        if let Some(style) = &config.vector_style {
+            // server tiles use XYZ coordinates unless reversed, the bounds are computed in TMS
+            let tms = if config.reverse_y || config.wms {
+                *tile
+            } else {
+                TileMathTile {
+                    y: (1 << tile.zoom) - 1 - tile.y,
+                    ..*tile
+                }
+            };
+            let bounds = crate::tile_to_mercator_aabb(tms);
+            let content = match &payload.data {
+                TileData::Encoded(bytes) => decode_vector_tile(bytes, &bounds, style),
+                TileData::Decoded(_) => Err(TileFetchError::Decode(
+                    "vector tiles can't be combined for a positive zoom offset".to_string(),
+                )),
+            };
+            return Self {
+                content: content.map(TileContent::Vector),
+                cached_path: payload.cached_path,
+                content_type: payload.content_type,
+                from_cache: payload.from_cache,
+            };
+        }
+        let hash = payload.data.content_hash();
+        Self {
+            content: build_image_from_data(payload.data, config.filter_mode)
+                .map(|image| TileContent::Raster { image, hash }),
+            cached_path: payload.cached_path,
+            content_type: payload.content_type,
+            from_cache: payload.from_cache,
+        }
+    }
+}
+
+/// Tile layers rendered on top of the base layer, e.g. a transparent labels or heatmap layer.
+///
+/// Layer `0` is the base layer configured by [`TileFetchConfig`], overlay `i` is layer `i + 1`.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct OverlayLayers(pub Vec<TileFetchConfig>);
+
+/// The duration is the time the request took if it was sent to the tile server
+type FetchMessage = (
+    usize,
+    TileMathTile,
+    Result<DecodedTile, TileFetchError>,
+    Option<Duration>,
+);
+
+#[derive(Debug)]
+struct FetchLayer {
     client: Arc<Client>,
     config: Arc<PreparedConfig>,
-    sender: mpsc::Sender<(TileMathTile, Result<TileImagePayload, TileFetchError>)>,
-    receiver: Arc<Mutex<mpsc::Receiver<(TileMathTile, Result<TileImagePayload, TileFetchError>)>>>,
-    waiting: HashMap<TileMathTile, Vec<Entity>>,
+    breaker: CircuitBreaker,
+}
+
+/// Pauses the requests of a layer while its server keeps failing.
+#[derive(Debug, Default)]
+struct CircuitBreaker {
+    consecutive_failures: u32,
+    /// Set while requests are paused, the first request after this time probes the server.
+    paused_until: Option<Instant>,
+    probing: bool,
+    /// requests held back while paused
+    paused: Vec<TileMathTile>,
+}
+
+impl CircuitBreaker {
+    fn is_paused(&self) -> bool {
+        self.paused_until.is_some()
+    }
+
+    fn pause(&mut self, cooldown: Duration) {
+        self.paused_until = Some(Instant::now() + cooldown);
+        self.probing = false;
+    }
+}
+
+/// Whether `err` indicates a problem with the server or the connection rather than with a single tile.
+fn is_server_failure(err: &TileFetchError) -> bool {
+    match err {
+        TileFetchError::Network(_) => true,
+        TileFetchError::HttpStatus(status) => {
+            status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+        }
+        _ => false,
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct TileFetcher {
+    layers: Vec<FetchLayer>,
+    sender: mpsc::Sender<FetchMessage>,
+    receiver: Arc<Mutex<mpsc::Receiver<FetchMessage>>>,
+    waiting: HashMap<(usize, TileMathTile), Vec<Entity>>,
 }
 
 impl FromWorld for TileFetcher {
@@ -146,12 +921,17 @@ impl FromWorld for TileFetcher {
             .get_resource::<TileFetchConfig>()
             .cloned()
             .unwrap_or_default();
-        TileFetcher::new(config).expect("failed to construct TileFetcher")
+        let overlays = world
+            .get_resource::<OverlayLayers>()
+            .cloned()
+            .unwrap_or_default();
+        let layers = std::iter::once(config).chain(overlays.0).collect();
+        TileFetcher::with_layers(layers).expect("failed to construct TileFetcher")
     }
 }
 
-impl TileFetcher {
-    pub fn new(config: TileFetchConfig) -> Result<Self, TileFetchError> {
+impl FetchLayer {
+    fn new(config: TileFetchConfig) -> Result<Self, TileFetchError> {
         let mut default_headers = HeaderMap::new();
         let mut prepared_headers = Vec::new();
         for (name, value) in &config.headers {
@@ -163,35 +943,142 @@ impl TileFetcher {
             prepared_headers.push((header_name, header_value));
         }
 
-        let client = Client::builder()
-            .default_headers(default_headers.clone())
-            .build()
-            .map_err(TileFetchError::from_network)?;
+        let mut api_key_query = None;
+        if let Some((location, key)) = &config.api_key {
+            let header = match location {
+                ApiKeyLocation::BearerHeader => {
+                    Some((AUTHORIZATION, format!("Bearer {}", key.secret())))
+                }
+                ApiKeyLocation::Header(name) => Some((
+                    HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|err| TileFetchError::Network(err.to_string()))?,
+                    key.secret().to_string(),
+                )),
+                ApiKeyLocation::QueryParam(name) => {
+                    api_key_query = Some((name.clone(), key.clone()));
+                    None
+                }
+            };
+            if let Some((header_name, value)) = header {
+                // don't leak the key into error messages
+                let mut header_value = HeaderValue::from_str(&value)
+                    .map_err(|_| TileFetchError::Network("invalid API key".to_string()))?;
+                header_value.set_sensitive(true);
+                default_headers.insert(header_name.clone(), header_value.clone());
+                prepared_headers.push((header_name, header_value));
+            }
+        }
+
+        let mut client = Client::builder().default_headers(default_headers.clone());
+        if let Some(timeout) = config.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        // the blocking client has a default timeout of 30 seconds, `None` disables it
+        #[cfg(not(feature = "async_http"))]
+        {
+            client = client.timeout(config.request_timeout);
+        }
+        // the async client has no timeout by default
+        #[cfg(feature = "async_http")]
+        if let Some(timeout) = config.request_timeout {
+            client = client.timeout(timeout);
+        }
+        let client = client.build().map_err(TileFetchError::from_network)?;
 
+        let (template, retina_suffix) = retina_template(&config, &config.url_template);
+        let templates = std::iter::once(template)
+            .chain(
+                config
+                    .fallback_sources
+                    .iter()
+                    .map(|fallback| retina_template(&config, fallback).0),
+            )
+            .collect();
         let prepared = PreparedConfig {
-            template: config.url_template.clone(),
+            templates,
+            preferred_source: Mutex::new(None),
+            retina_suffix,
+            api_key_query,
             headers: prepared_headers,
             cache_directory: config.cache_directory,
+            cache_enabled: config.cache_enabled,
             cache_extension: config.cache_extension,
+            custom_source: config.custom_source,
+            opacity: config.opacity.clamp(0.0, 1.0),
+            tint: config.tile_tint,
+            #[cfg(feature = "mvt")]
+            vector_style: config.vector_style,
+            reverse_y: config.reverse_y,
+            zoom_offset: config.zoom_offset,
+            wms: config.wms,
+            tile_pixels: config.tile_size.max(1) * config.tile_pixel_ratio.max(1) as u32,
+            filter_mode: config.tile_filter_mode,
+            max_consecutive_failures: config.max_consecutive_failures,
+            failure_cooldown: config.failure_cooldown,
+            zoom_range: config.zoom_range,
         };
 
-        if !prepared.cache_directory.exists() {
+        if prepared.cache_enabled && !prepared.cache_directory.exists() {
             fs::create_dir_all(&prepared.cache_directory).map_err(TileFetchError::from_io)?;
         }
 
-        let (sender, receiver) = mpsc::channel();
-
         Ok(Self {
             client: Arc::new(client),
             config: Arc::new(prepared),
+            breaker: CircuitBreaker::default(),
+        })
+    }
+}
+
+impl TileFetcher {
+    /// Creates a fetcher for a single tile layer.
+    pub fn new(config: TileFetchConfig) -> Result<Self, TileFetchError> {
+        Self::with_layers(vec![config])
+    }
+
+    /// Creates a fetcher for multiple tile layers, the first one being the base layer.
+    pub fn with_layers(configs: Vec<TileFetchConfig>) -> Result<Self, TileFetchError> {
+        let layers = configs
+            .into_iter()
+            .map(FetchLayer::new)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (i, layer) in layers.iter().enumerate() {
+            let shared = layers[..i].iter().any(|other| {
+                other.config.cache_directory == layer.config.cache_directory
+                    && other.config.uses_disk_cache()
+                    && layer.config.uses_disk_cache()
+            });
+            if shared {
+                warn!(
+                    "tile layer {} uses the same cache directory as another layer ({}), cached tiles will be mixed up",
+                    i,
+                    layer.config.cache_directory.display()
+                );
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel();
+
+        Ok(Self {
+            layers,
             sender,
             receiver: Arc::new(Mutex::new(receiver)),
             waiting: HashMap::new(),
         })
     }
 
-    pub fn request_tile(&mut self, entity: Entity, tile: TileMathTile) {
-        let entry = self.waiting.entry(tile).or_default();
+    /// Number of tile layers, including the base layer.
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub(crate) fn layer_config(&self, layer: usize) -> &PreparedConfig {
+        &self.layers[layer].config
+    }
+
+    pub fn request_tile(&mut self, entity: Entity, layer: usize, tile: TileMathTile) {
+        let entry = self.waiting.entry((layer, tile)).or_default();
         if !entry.contains(&entity) {
             entry.push(entity);
         }
@@ -200,27 +1087,54 @@ impl TileFetcher {
             return;
         }
 
-        let client = Arc::clone(&self.client);
+        let breaker = &mut self.layers[layer].breaker;
+        if breaker.is_paused() {
+            breaker.paused.push(tile);
+            return;
+        }
+        self.spawn_fetch(layer, tile);
+    }
+
+    fn spawn_fetch(&self, layer: usize, tile: TileMathTile) {
+        let client = Arc::clone(&self.layers[layer].client);
         let sender = self.sender.clone();
-        let config = Arc::clone(&self.config);
+        let config = Arc::clone(&self.layers[layer].config);
 
         IoTaskPool::get()
             .spawn(async move {
-                let result = fetch_tile(config, client, tile);
-                let _ = sender.send((tile, result));
+                let start = Instant::now();
+                #[cfg(not(feature = "async_http"))]
+                let result = fetch_tile(Arc::clone(&config), client, tile);
+                // reqwest needs a tokio reactor, async-compat provides one while the task runs on the bevy task pool
+                #[cfg(feature = "async_http")]
+                let result =
+                    async_compat::Compat::new(fetch_tile(Arc::clone(&config), client, tile)).await;
+                // tiles from the disk cache or a custom source say nothing about the server
+                let downloaded = match &result {
+                    Ok(payload) => payload.downloaded,
+                    Err(_) => config.downloads_tiles(),
+                };
+                let latency = downloaded.then(|| start.elapsed());
+                let result = result.map(|payload| DecodedTile::decode(payload, &config, &tile));
+                let _ = sender.send((layer, tile, result, latency));
             })
             .detach();
     }
 
+    /// Returns the results of at most `limit` requested tiles, the others stay queued for the next call.
     fn drain_ready(
         &mut self,
+        limit: usize,
+        health: &mut TileServerHealth,
     ) -> Vec<(
         Vec<Entity>,
+        usize,
         TileMathTile,
-        Result<TileImagePayload, TileFetchError>,
+        Result<DecodedTile, TileFetchError>,
     )> {
+        self.probe_paused_layers();
         let mut responses = Vec::new();
-        loop {
+        while responses.len() < limit {
             let _span_once = info_span!("drain_ready_once", name = "drain_ready_once").entered();
             let message = {
                 let receiver = self.receiver.lock().unwrap();
@@ -228,9 +1142,13 @@ impl TileFetcher {
             };
 
             match message {
-                Ok((tile, result)) => {
-                    if let Some(listeners) = self.waiting.remove(&tile) {
-                        responses.push((listeners, tile, result));
+                Ok((layer, tile, result, latency)) => {
+                    self.record_result(layer, &result);
+                    if let Some(latency) = latency {
+                        health.record(layer, result.is_ok(), latency);
+                    }
+                    if let Some(listeners) = self.waiting.remove(&(layer, tile)) {
+                        responses.push((listeners, layer, tile, result));
                     }
                 }
                 Err(mpsc::TryRecvError::Empty) => break,
@@ -239,61 +1157,416 @@ impl TileFetcher {
         }
         responses
     }
+
+    /// Sends a single request of each paused layer whose cooldown is over.
+    fn probe_paused_layers(&mut self) {
+        let now = Instant::now();
+        for layer in 0..self.layers.len() {
+            let breaker = &mut self.layers[layer].breaker;
+            if breaker.probing || breaker.paused_until.is_none_or(|until| until > now) {
+                continue;
+            }
+            // requests of tiles that were despawned in the meantime are not needed anymore
+            let waiting = &self.waiting;
+            breaker
+                .paused
+                .retain(|tile| waiting.contains_key(&(layer, *tile)));
+            let Some(tile) = breaker.paused.pop() else {
+                breaker.paused_until = None;
+                continue;
+            };
+            debug!("probing tile layer {} after pausing requests", layer);
+            breaker.probing = true;
+            self.spawn_fetch(layer, tile);
+        }
+    }
+
+    fn record_result(&mut self, layer: usize, result: &Result<DecodedTile, TileFetchError>) {
+        let limit = self.layers[layer].config.max_consecutive_failures;
+        let cooldown = self.layers[layer].config.failure_cooldown;
+        let breaker = &mut self.layers[layer].breaker;
+        match result {
+            Err(err) if is_server_failure(err) => {
+                breaker.consecutive_failures += 1;
+                if breaker.probing {
+                    breaker.pause(cooldown);
+                } else if limit > 0 && breaker.consecutive_failures >= limit && !breaker.is_paused()
+                {
+                    warn!(
+                        "tile layer {} failed {} times in a row, pausing requests for {:?}",
+                        layer, breaker.consecutive_failures, cooldown
+                    );
+                    breaker.pause(cooldown);
+                }
+            }
+            _ => {
+                breaker.consecutive_failures = 0;
+                if breaker.probing {
+                    info!("tile layer {} recovered, resuming requests", layer);
+                    breaker.probing = false;
+                    breaker.paused_until = None;
+                    for tile in std::mem::take(&mut breaker.paused) {
+                        self.spawn_fetch(layer, tile);
+                    }
+                }
+            }
+        }
+    }
 }
 
-fn fetch_tile(
-    config: Arc<PreparedConfig>,
-    client: Arc<Client>,
-    tile: TileMathTile,
-) -> Result<TileImagePayload, TileFetchError> {
-    let cache_path = config.cache_path(&tile);
+/// Loads `tile` from the custom source or the disk cache, `None` if it has to be downloaded.
+fn load_local_tile(
+    config: &PreparedConfig,
+    tile: &TileMathTile,
+) -> Result<Option<TileImagePayload>, TileFetchError> {
+    if let Some(source) = &config.custom_source {
+        debug!(
+            "loading tile (x={}, y={}) from {:?}",
+            tile.x, tile.y, source
+        );
+        match source.load_tile(tile) {
+            Err(TileFetchError::NotFound) if source.network_fallback() => {
+                debug!("tile (x={}, y={}) not in {:?}", tile.x, tile.y, source);
+            }
+            result => {
+                return Ok(Some(TileImagePayload {
+                    data: TileData::Encoded(result?),
+                    cached_path: None,
+                    content_type: None,
+                    from_cache: false,
+                    downloaded: false,
+                }));
+            }
+        }
+    }
+    if !config.cache_enabled {
+        return Ok(None);
+    }
+    let cache_path = config.cache_path(tile);
     if cache_path.exists() {
         debug!("loading cached tile (x={}, y={})", tile.x, tile.y);
         let data = fs::read(&cache_path).map_err(TileFetchError::from_io)?;
-        return Ok(TileImagePayload {
-            bytes: data,
-            cached_path: Some(cache_path),
-            content_type: None,
-            from_cache: true,
-        });
+        // vector tiles can't be checked without decoding them
+        if config.is_vector() || is_complete_image(&data) {
+            return Ok(Some(TileImagePayload {
+                data: TileData::Encoded(data),
+                cached_path: Some(cache_path),
+                content_type: None,
+                from_cache: true,
+                downloaded: false,
+            }));
+        }
+        // e.g. truncated by an interrupted download, remove it and download the tile again
+        warn!("removing corrupt cached tile {}", cache_path.display());
+        fs::remove_file(&cache_path).map_err(TileFetchError::from_io)?;
     }
-    debug!("fetching tile (x={}, y={})", tile.x, tile.y);
-    let mut request = client.get(config.format_url(&tile));
-    for (name, value) in &config.headers {
-        request = request.header(name.clone(), value.clone());
+    Ok(None)
+}
+
+fn content_type(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|s| s.to_string())
+}
+
+/// Checks a downloaded tile and writes it to the disk cache if enabled.
+fn store_downloaded_tile(
+    config: &PreparedConfig,
+    tile: &TileMathTile,
+    bytes: Vec<u8>,
+    content_type: Option<String>,
+) -> Result<TileImagePayload, TileFetchError> {
+    // don't cache error pages served with a success status (rate limiting, captive portals, ...)
+    let is_tile = if config.is_vector() {
+        !content_type
+            .as_deref()
+            .is_some_and(|content_type| content_type.starts_with("text/"))
+    } else {
+        content_type
+            .as_deref()
+            .is_some_and(|content_type| content_type.starts_with("image/"))
+            || image::guess_format(&bytes).is_ok()
+    };
+    if !is_tile {
+        return Err(TileFetchError::NotAnImage(
+            content_type.unwrap_or_else(|| "an unknown content type".to_string()),
+        ));
+    }
+
+    if !config.cache_enabled {
+        return Ok(TileImagePayload {
+            data: TileData::Encoded(bytes),
+            cached_path: None,
+            content_type,
+            from_cache: false,
+            downloaded: true,
+        });
+    }
+    let cache_path = config.cache_path(tile);
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent).map_err(TileFetchError::from_io)?;
+    }
+    write_atomic(&cache_path, &bytes).map_err(TileFetchError::from_io)?;
+
+    Ok(TileImagePayload {
+        data: TileData::Encoded(bytes),
+        cached_path: Some(cache_path),
+        content_type,
+        from_cache: false,
+        downloaded: true,
+    })
+}
+
+/// Fetches `request`, combining the server tiles of a positive zoom offset into one image.
+#[cfg(not(feature = "async_http"))]
+fn fetch_tile(
+    config: Arc<PreparedConfig>,
+    client: Arc<Client>,
+    request: TileMathTile,
+) -> Result<TileImagePayload, TileFetchError> {
+    let subtiles = server_subtiles(&request, config.reverse_y, config.zoom_offset, config.wms);
+    if let [tile] = subtiles[..] {
+        return fetch_server_tile(config, client, tile);
+    }
+    let parts = subtiles
+        .into_iter()
+        .map(|tile| fetch_server_tile(Arc::clone(&config), Arc::clone(&client), tile))
+        .collect::<Result<Vec<_>, _>>()?;
+    combine_subtiles(parts)
+}
+
+#[cfg(not(feature = "async_http"))]
+fn fetch_server_tile(
+    config: Arc<PreparedConfig>,
+    client: Arc<Client>,
+    tile: TileMathTile,
+) -> Result<TileImagePayload, TileFetchError> {
+    if let Some(payload) = load_local_tile(&config, &tile)? {
+        return Ok(payload);
+    }
+    let mut result = Err(TileFetchError::NotFound);
+    for source in config.source_order() {
+        result = download_tile(&config, &client, source, &tile);
+        match &result {
+            Err(err) if is_server_failure(err) => {
+                debug!("tile source {} failed: {}", source, err);
+                continue;
+            }
+            Ok(_) => config.remember_source(source),
+            Err(_) => {}
+        }
+        break;
+    }
+    let (bytes, content_type) = result?;
+    store_downloaded_tile(&config, &tile, bytes, content_type)
+}
+
+/// Downloads `tile` from the `source`th URL template, returns the bytes and the content type.
+#[cfg(not(feature = "async_http"))]
+fn download_tile(
+    config: &PreparedConfig,
+    client: &Client,
+    source: usize,
+    tile: &TileMathTile,
+) -> Result<(Vec<u8>, Option<String>), TileFetchError> {
+    debug!("fetching tile (x={}, y={})", tile.x, tile.y);
+    let mut request = client.get(config.format_url(source, tile));
+    for (name, value) in &config.headers {
+        request = request.header(name.clone(), value.clone());
     }
 
     let response = request.send().map_err(TileFetchError::from_network)?;
     if !response.status().is_success() {
         return Err(TileFetchError::HttpStatus(response.status()));
     }
-
-    let content_type = response
-        .headers()
-        .get(CONTENT_TYPE)
-        .and_then(|value| value.to_str().ok())
-        .map(|s| s.to_string());
+    let content_type = content_type(response.headers());
     let bytes = response
         .bytes()
         .map_err(TileFetchError::from_network)?
         .to_vec();
+    Ok((bytes, content_type))
+}
 
-    if let Some(parent) = cache_path.parent() {
-        fs::create_dir_all(parent).map_err(TileFetchError::from_io)?;
+/// Fetches `request`, combining the server tiles of a positive zoom offset into one image.
+#[cfg(feature = "async_http")]
+async fn fetch_tile(
+    config: Arc<PreparedConfig>,
+    client: Arc<Client>,
+    request: TileMathTile,
+) -> Result<TileImagePayload, TileFetchError> {
+    let subtiles = server_subtiles(&request, config.reverse_y, config.zoom_offset, config.wms);
+    if let [tile] = subtiles[..] {
+        return fetch_server_tile(config, client, tile).await;
+    }
+    let mut parts = Vec::with_capacity(subtiles.len());
+    for tile in subtiles {
+        parts.push(fetch_server_tile(Arc::clone(&config), Arc::clone(&client), tile).await?);
     }
-    fs::write(&cache_path, &bytes).map_err(TileFetchError::from_io)?;
+    combine_subtiles(parts)
+}
 
+#[cfg(feature = "async_http")]
+async fn fetch_server_tile(
+    config: Arc<PreparedConfig>,
+    client: Arc<Client>,
+    tile: TileMathTile,
+) -> Result<TileImagePayload, TileFetchError> {
+    if let Some(payload) = load_local_tile(&config, &tile)? {
+        return Ok(payload);
+    }
+    let mut result = Err(TileFetchError::NotFound);
+    for source in config.source_order() {
+        result = download_tile(&config, &client, source, &tile).await;
+        match &result {
+            Err(err) if is_server_failure(err) => {
+                debug!("tile source {} failed: {}", source, err);
+                continue;
+            }
+            Ok(_) => config.remember_source(source),
+            Err(_) => {}
+        }
+        break;
+    }
+    let (bytes, content_type) = result?;
+    store_downloaded_tile(&config, &tile, bytes, content_type)
+}
+
+/// Downloads `tile` from the `source`th URL template, returns the bytes and the content type.
+#[cfg(feature = "async_http")]
+async fn download_tile(
+    config: &PreparedConfig,
+    client: &Client,
+    source: usize,
+    tile: &TileMathTile,
+) -> Result<(Vec<u8>, Option<String>), TileFetchError> {
+    debug!("fetching tile (x={}, y={})", tile.x, tile.y);
+    let mut request = client.get(config.format_url(source, tile));
+    for (name, value) in &config.headers {
+        request = request.header(name.clone(), value.clone());
+    }
+
+    let response = request.send().await.map_err(TileFetchError::from_network)?;
+    if !response.status().is_success() {
+        return Err(TileFetchError::HttpStatus(response.status()));
+    }
+    let content_type = content_type(response.headers());
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(TileFetchError::from_network)?
+        .to_vec();
+    Ok((bytes, content_type))
+}
+
+/// Combines the server tiles of [`server_subtiles`] into a single image, `parts` row by row from the top.
+fn combine_subtiles(parts: Vec<TileImagePayload>) -> Result<TileImagePayload, TileFetchError> {
+    let divisions = (parts.len() as f64).sqrt() as u32;
+    let from_cache = parts.iter().all(|part| part.from_cache);
+    let downloaded = parts.iter().any(|part| part.downloaded);
+    let images = parts
+        .into_iter()
+        .map(|part| part.data.into_rgba())
+        .collect::<Result<Vec<_>, _>>()?;
+    let (width, height) = images.first().map_or((0, 0), |image| image.dimensions());
+    let mut combined = RgbaImage::new(width * divisions, height * divisions);
+    for (i, image) in images.iter().enumerate() {
+        let (column, row) = (i as u32 % divisions, i as u32 / divisions);
+        imageops::replace(
+            &mut combined,
+            image,
+            (column * width) as i64,
+            (row * height) as i64,
+        );
+    }
     Ok(TileImagePayload {
-        bytes,
-        cached_path: Some(cache_path),
-        content_type,
-        from_cache: false,
+        data: TileData::Decoded(combined),
+        cached_path: None,
+        content_type: None,
+        from_cache,
+        downloaded,
     })
 }
 
+/// Writes to a temporary file first, so readers and cache clears never see a partially written tile.
+fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".part");
+    fs::write(&temp_path, bytes)?;
+    fs::rename(&temp_path, path)
+}
+
+/// Cheap check whether `bytes` is a known image format and not truncated, without decoding it.
+fn is_complete_image(bytes: &[u8]) -> bool {
+    const PNG_END: [u8; 8] = [0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82];
+    const JPEG_END: [u8; 2] = [0xFF, 0xD9];
+    match image::guess_format(bytes) {
+        Ok(image::ImageFormat::Png) => bytes.ends_with(&PNG_END),
+        Ok(image::ImageFormat::Jpeg) => bytes.ends_with(&JPEG_END),
+        Ok(_) => true,
+        Err(_) => false,
+    }
+}
+
+/// Marks tiles whose image is being downloaded or loaded from the cache, see [`LoadingTiles`] for their bounds.
 #[derive(Component, Debug, Default)]
 pub struct TileTextureLoading;
 
+/// The tiles currently loading with their mercator bounds, e.g. for drawing a spinner on every loading tile
+/// or showing a global loading indicator while [`LoadingTiles::is_empty`] is false.
+///
+/// Convert the bounds with [`LocalOriginConversion::mercator_to_local`](crate::LocalOriginConversion::mercator_to_local)
+/// to place entities or gizmos on the tiles, see the `loading_tiles` example.
+#[derive(SystemParam)]
+pub struct LoadingTiles<'w, 's> {
+    tiles: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Tile,
+            &'static TileLayer,
+            &'static WorldCopy,
+        ),
+        With<TileTextureLoading>,
+    >,
+}
+
+/// A tile being loaded, see [`LoadingTiles`].
+#[derive(Debug, Clone, Copy)]
+pub struct LoadingTile {
+    pub entity: Entity,
+    /// Tile coordinates with TMS-style y, see [`Tile`]
+    pub tile: TileMathTile,
+    pub layer: usize,
+    /// Bounds of the tile in mercator coordinates, including the offset of its [`WorldCopy`]
+    pub bounds: MercatorAabb2d,
+}
+
+impl LoadingTiles<'_, '_> {
+    pub fn iter(&self) -> impl Iterator<Item = LoadingTile> + '_ {
+        self.tiles.iter().map(|(entity, tile, layer, world)| {
+            let offset = DVec2::new(world.0 as f64 * 2.0 * WEB_MERCATOR_EXTENT, 0.0);
+            let bounds = crate::tile_to_mercator_aabb(tile.0);
+            LoadingTile {
+                entity,
+                tile: tile.0,
+                layer: layer.0,
+                bounds: MercatorAabb2d::new(bounds.min + offset, bounds.max + offset),
+            }
+        })
+    }
+
+    /// Number of tiles currently loading.
+    pub fn len(&self) -> usize {
+        self.tiles.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct TileTextureError {
     pub message: Arc<str>,
@@ -308,29 +1581,125 @@ impl TileTextureError {
     }
 }
 
+/// Number of recent requests per layer [`TileServerHealth`] keeps.
+const HEALTH_WINDOW: usize = 100;
+
+/// Outcomes and latencies of the recent requests to the tile servers, e.g. for a status display
+/// or switching to a secondary server when a layer keeps failing.
+///
+/// Only requests sent to a server are counted, tiles loaded from the disk cache or a [`TileSource`] are not.
+/// Each layer keeps its last 100 requests.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct TileServerHealth {
+    layers: Vec<LayerHealth>,
+}
+
+impl TileServerHealth {
+    /// Health of tile layer `layer`, `0` being the base layer, see [`OverlayLayers`].
+    pub fn layer(&self, layer: usize) -> Option<&LayerHealth> {
+        self.layers.get(layer)
+    }
+
+    fn record(&mut self, layer: usize, success: bool, latency: Duration) {
+        if self.layers.len() <= layer {
+            self.layers.resize_with(layer + 1, LayerHealth::default);
+        }
+        let requests = &mut self.layers[layer].requests;
+        if requests.len() == HEALTH_WINDOW {
+            requests.pop_front();
+        }
+        requests.push_back((success, latency));
+    }
+}
+
+/// Recent requests of a tile layer, see [`TileServerHealth`].
+#[derive(Debug, Clone, Default)]
+pub struct LayerHealth {
+    /// success and duration of each request, oldest first
+    requests: VecDeque<(bool, Duration)>,
+}
+
+impl LayerHealth {
+    /// Number of requests in the window.
+    pub fn request_count(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Fraction of successful requests between 0.0 and 1.0, `None` without requests.
+    pub fn success_rate(&self) -> Option<f32> {
+        if self.requests.is_empty() {
+            return None;
+        }
+        let successes = self.requests.iter().filter(|(success, _)| *success).count();
+        Some(successes as f32 / self.requests.len() as f32)
+    }
+
+    /// Average duration of the requests including failed ones, `None` without requests.
+    pub fn average_latency(&self) -> Option<Duration> {
+        if self.requests.is_empty() {
+            return None;
+        }
+        let total: Duration = self.requests.iter().map(|(_, latency)| *latency).sum();
+        Some(total / self.requests.len() as u32)
+    }
+}
+
+/// Triggered when a tile image was loaded and applied to its [`Tile`] entities.
+#[derive(Event, Debug, Clone)]
+pub struct TileLoaded {
+    /// The tile as requested from the tile server or custom source, after the y flip and zoom offset of its layer.
+    pub tile: TileMathTile,
+    /// Index of the tile layer, `0` is the base layer.
+    pub layer: usize,
+    /// The [`Tile`] entities showing the image.
+    pub entities: Vec<Entity>,
+    /// Whether the image was read from the disk cache instead of being downloaded.
+    pub from_cache: bool,
+}
+
+/// Triggered when downloading or decoding a tile failed, the entities get a [`TileTextureError`].
+#[derive(Event, Debug, Clone)]
+pub struct TileLoadFailed {
+    /// The tile as requested from the tile server or custom source, see [`TileLoaded::tile`].
+    pub tile: TileMathTile,
+    /// Index of the tile layer, `0` is the base layer.
+    pub layer: usize,
+    /// The [`Tile`] entities waiting for the image.
+    pub entities: Vec<Entity>,
+    pub error: String,
+}
+
 pub fn queue_tile_downloads(
     mut commands: Commands,
     mut fetcher: ResMut<TileFetcher>,
-    config: Res<TileFetchConfig>,
-    tiles: Query<(Entity, &Tile), Added<Tile>>,
+    current_zoom: Res<CurrentZoom>,
+    camera: Option<Single<&Transform, With<MainCam>>>,
+    tiles: Query<(Entity, &Tile, &TileLayer, Option<&Transform>), Added<Tile>>,
 ) {
-    for (entity, tile) in tiles.iter() {
-        // some tile-servers use
-        let zoom = (tile.0.zoom as i8 + config.zoom_offset).max(0) as u8;
-        // current logic is inverted, so we flip the condition
-        let y = if !config.reverse_y {
-            (1 << zoom) - 1 - tile.0.y
-        } else {
-            tile.0.y
-        };
-        fetcher.request_tile(
-            entity,
-            TileMathTile {
-                zoom,
-                x: tile.0.x,
-                y,
-            },
-        );
+    // the task pool roughly starts the downloads in request order, so request the tiles of the current zoom level
+    // closest to the center of the view first and prefetched or partially visible tiles later
+    let center = camera
+        .map(|cam| cam.translation.truncate())
+        .unwrap_or_default();
+    let zoom = current_zoom.current_zoom();
+    let priority = |tile: &Tile, transform: Option<&Transform>| {
+        let distance = transform.map_or(0.0, |tr| tr.translation.truncate().distance(center));
+        (zoom.abs_diff(tile.0.zoom), distance)
+    };
+    let mut added: Vec<_> = tiles.iter().collect();
+    added.sort_by(|(_, a, _, a_tr), (_, b, _, b_tr)| {
+        let (a_zoom, a_distance) = priority(a, *a_tr);
+        let (b_zoom, b_distance) = priority(b, *b_tr);
+        a_zoom.cmp(&b_zoom).then(a_distance.total_cmp(&b_distance))
+    });
+    for (entity, tile, layer, _) in added {
+        let config = fetcher.layer_config(layer.0);
+        // not spawned by the map, but tiles could be spawned by hand
+        if !config.serves_zoom(tile.0.zoom) {
+            continue;
+        }
+        let request = server_tile(&tile.0, config.reverse_y, config.zoom_offset, config.wms);
+        fetcher.request_tile(entity, layer.0, request);
         commands
             .entity(entity)
             .remove::<TileTextureError>()
@@ -338,79 +1707,795 @@ pub fn queue_tile_downloads(
     }
 }
 
+impl AssetUser for Sprite {
+    type Asset = Image;
+
+    fn asset_id(&self) -> AssetId<Image> {
+        self.image.id()
+    }
+}
+
+/// Textures of the raster tiles by layer and content hash, e.g. one texture for all solid ocean tiles of a layer.
+pub(crate) type TileTextures = SharedAssets<(usize, u64), Sprite>;
+
+/// Shares the textures of tiles with the same content until no tile shows them anymore.
+pub(crate) fn tile_textures_plugin(app: &mut App) {
+    app.add_plugins(shared_assets_plugin::<(usize, u64), Sprite>);
+}
+
 pub fn apply_tile_fetch_results(
     mut commands: Commands,
     mut fetcher: ResMut<TileFetcher>,
     mut images: ResMut<Assets<Image>>,
+    streaming: Res<TileStreamingConfig>,
+    tiles: Query<&Tile>,
+    zoom_levels: Query<&ChildOf, With<Tile>>,
+    fades: Query<&ZoomFade>,
+    mut coords: Query<&mut MercatorCoords, With<Tile>>,
+    mut health: ResMut<TileServerHealth>,
+    mut shared_textures: ResMut<TileTextures>,
+    mut atlas: ResMut<TileAtlas>,
 ) {
     let _span = trace_span!("apply_tile_fetch_results",).entered();
-    for (entities, tile, result) in fetcher.drain_ready() {
+    for (entities, layer, tile, result) in
+        fetcher.drain_ready(streaming.max_tiles_applied_per_frame, &mut health)
+    {
         match result {
-            Ok(payload) => {
-                if payload.from_cache
-                    && let Some(path) = &payload.cached_path
+            Ok(decoded) => {
+                if decoded.from_cache
+                    && let Some(path) = &decoded.cached_path
                 {
                     trace!("tile {:?} served from cache {}", tile, path.display());
                 }
 
-                if let Some(content_type) = &payload.content_type {
+                if let Some(content_type) = &decoded.content_type {
                     trace!("tile {:?} reported content-type {}", tile, content_type);
                 }
 
-                match build_image_from_payload(&payload) {
-                    Ok(image) => {
-                        let handle = images.add(image);
+                let config = fetcher.layer_config(layer);
+                match decoded.content {
+                    #[cfg(feature = "mvt")]
+                    Ok(TileContent::Vector(features)) => {
+                        // the shapes belong to the first tile, they cover the whole server tile
+                        let owner = entities
+                            .iter()
+                            .copied()
+                            .find(|entity| tiles.contains(*entity));
+                        if let (Some(owner), Some(style)) = (owner, &config.vector_style) {
+                            spawn_vector_features(
+                                &mut commands,
+                                owner,
+                                tiles.get(owner).map_or(tile.zoom, |tile| tile.0.zoom),
+                                features,
+                                style,
+                                streaming.tile_size,
+                            );
+                        }
                         for entity in &entities {
+                            if let Ok(mut entity_commands) = commands.get_entity(*entity) {
+                                entity_commands
+                                    .remove::<TileTextureLoading>()
+                                    .remove::<TileTextureError>();
+                            }
+                        }
+                        commands.trigger(TileLoaded {
+                            tile,
+                            layer,
+                            entities,
+                            from_cache: decoded.from_cache,
+                        });
+                    }
+                    Ok(TileContent::Raster { image, hash }) => {
+                        let image_size = image.size();
+                        let slot = if streaming.tile_atlas {
+                            atlas.get_or_add(layer, hash, &image, &mut images)
+                        } else {
+                            None
+                        };
+                        // e.g. solid ocean tiles, the same bytes of a layer always decode to the same image
+                        let handle = match slot {
+                            Some(slot) => atlas.image(slot),
+                            None => shared_textures.get_or_add((layer, hash), || images.add(image)),
+                        };
+                        let base_color = config.sprite_color();
+                        for entity in &entities {
+                            // start at the opacity of a fading zoom level instead of flashing up for a frame
+                            let opacity = zoom_levels
+                                .get(*entity)
+                                .and_then(|level| fades.get(level.parent()))
+                                .map_or(1.0, ZoomFade::opacity);
+                            let color = base_color.with_alpha(base_color.alpha() * opacity);
+                            // tiles sharing a server tile show their part of it
+                            let region = tiles
+                                .get(*entity)
+                                .ok()
+                                .and_then(|tile| {
+                                    server_tile_region(&tile.0, config.zoom_offset, config.wms)
+                                })
+                                .map(|region| region_rect(image_size, region));
+                            let rect = match slot {
+                                Some(slot) => Some(atlas.page_rect(
+                                    slot,
+                                    region.unwrap_or(URect::from_corners(UVec2::ZERO, image_size)),
+                                )),
+                                None => region.map(|region| region.as_rect()),
+                            };
+                            if streaming.tile_atlas
+                                && let Ok(mut coords) = coords.get_mut(*entity)
+                            {
+                                let z = tile_z(layer, slot.map(|slot| slot.page)) as f64;
+                                if coords.0.z != z {
+                                    coords.0.z = z;
+                                }
+                            }
                             if let Ok(mut entity_commands) = commands.get_entity(*entity) {
                                 entity_commands.remove::<TileTextureLoading>();
                                 entity_commands
                                     .insert(Sprite {
                                         image: handle.clone(),
+                                        color,
                                         custom_size: Some(Vec2::ONE),
+                                        rect,
                                         ..Default::default()
                                     })
                                     .remove::<TileTextureError>();
+                                match slot {
+                                    Some(slot) => entity_commands.insert(TileAtlasSlot(slot)),
+                                    None => entity_commands.remove::<TileAtlasSlot>(),
+                                };
                             }
                         }
+                        commands.trigger(TileLoaded {
+                            tile,
+                            layer,
+                            entities,
+                            from_cache: decoded.from_cache,
+                        });
                     }
                     Err(err) => {
                         error!("failed to decode tile {:?}: {}", tile, err);
-                        for entity in entities {
-                            if let Ok(mut entity_commands) = commands.get_entity(entity) {
+                        for entity in &entities {
+                            if let Ok(mut entity_commands) = commands.get_entity(*entity) {
                                 entity_commands.remove::<TileTextureLoading>();
                                 entity_commands.insert(TileTextureError::new(err.to_string()));
                             }
                         }
+                        commands.trigger(TileLoadFailed {
+                            tile,
+                            layer,
+                            entities,
+                            error: err.to_string(),
+                        });
                     }
                 }
             }
             Err(err) => {
-                error!("failed to fetch tile {:?}: {:?}", tile, err);
-                for entity in entities {
-                    if let Ok(mut entity_commands) = commands.get_entity(entity) {
+                // missing tiles are expected, e.g. at the edges of an MBTiles extract
+                if matches!(err, TileFetchError::NotFound) {
+                    debug!("tile {:?} not found", tile);
+                } else {
+                    error!("failed to fetch tile {:?}: {:?}", tile, err);
+                }
+                for entity in &entities {
+                    if let Ok(mut entity_commands) = commands.get_entity(*entity) {
                         entity_commands.remove::<TileTextureLoading>();
                         entity_commands
                             .insert(TileTextureError::new(format!("Download failed: {:?}", err)));
                     }
                 }
+                commands.trigger(TileLoadFailed {
+                    tile,
+                    layer,
+                    entities,
+                    error: err.to_string(),
+                });
             }
         }
     }
 }
 
-fn build_image_from_payload(payload: &TileImagePayload) -> Result<Image, TileFetchError> {
-    let dynamic = image::load_from_memory(&payload.bytes).map_err(TileFetchError::from_decode)?;
-    let rgba = dynamic.to_rgba8();
-    let (width, height) = dynamic.dimensions();
-    Ok(Image::new_fill(
+fn build_image_from_data(
+    data: TileData,
+    filter_mode: TileFilterMode,
+) -> Result<Image, TileFetchError> {
+    let rgba = data.into_rgba()?;
+    let (width, height) = rgba.dimensions();
+    let mut image = Image::new(
         Extent3d {
             width,
             height,
             depth_or_array_layers: 1,
         },
         TextureDimension::D2,
-        &rgba,
+        rgba.into_raw(),
         TextureFormat::Rgba8UnormSrgb,
         RenderAssetUsages::default(),
-    ))
+    );
+    image.sampler = filter_mode.sampler();
+    Ok(image)
+}
+
+/// Assembles the cached tiles of `config` covering the box between the opposite corners `corner_a` and `corner_b`
+/// (lon/lat in degrees) at `zoom` into a single image, e.g. to export or print a map view.
+///
+/// Only tiles already in the cache directory are used, missing tiles stay transparent.
+/// The image covers whole tiles, so it is usually slightly larger than the box.
+/// Returns [`TileFetchError::ImageTooLarge`] if the image would be wider or higher than 16384 pixels,
+/// use a lower `zoom` for large areas. Use [`RgbaImage::save`] to write it to disk.
+pub fn stitch_cached_tiles(
+    config: &TileFetchConfig,
+    corner_a: DVec2,
+    corner_b: DVec2,
+    zoom: u8,
+) -> Result<RgbaImage, TileFetchError> {
+    let (a, b) = (corner_a.lonlat_to_mercator(), corner_b.lonlat_to_mercator());
+    let bounds = MercatorAabb2d::new(a.min(b), a.max(b)).mercator_to_tile_coords(zoom);
+    let tile_size = config.tile_size.max(1) * config.tile_pixel_ratio.max(1) as u32;
+    let columns = bounds.max.x - bounds.min.x + 1;
+    let rows = bounds.max.y - bounds.min.y + 1;
+    let (width, height) = (
+        columns as u64 * tile_size as u64,
+        rows as u64 * tile_size as u64,
+    );
+    if width > MAX_STITCHED_SIZE as u64 || height > MAX_STITCHED_SIZE as u64 {
+        return Err(TileFetchError::ImageTooLarge { width, height });
+    }
+    let mut stitched = RgbaImage::new(width as u32, height as u32);
+
+    for x in bounds.x_range() {
+        for y in bounds.y_range() {
+            let tile = TileMathTile { zoom, x, y };
+            let request = server_tile(&tile, config.reverse_y, config.zoom_offset, config.wms);
+            let subtiles =
+                server_subtiles(&request, config.reverse_y, config.zoom_offset, config.wms);
+            let divisions = (subtiles.len() as f64).sqrt() as u32;
+            let part_size = tile_size / divisions;
+            let mut image = RgbaImage::new(tile_size, tile_size);
+            let mut found = false;
+            for (i, subtile) in subtiles.iter().enumerate() {
+                let Some(mut part) = load_cached_image(&config.cache_path_for(subtile))? else {
+                    continue;
+                };
+                if let Some(region) = server_tile_region(&tile, config.zoom_offset, config.wms) {
+                    let rect = region_rect(part.dimensions().into(), region);
+                    part = imageops::crop_imm(
+                        &part,
+                        rect.min.x,
+                        rect.min.y,
+                        rect.width(),
+                        rect.height(),
+                    )
+                    .to_image();
+                }
+                if part.dimensions() != (part_size, part_size) {
+                    part = imageops::resize(
+                        &part,
+                        part_size,
+                        part_size,
+                        imageops::FilterType::Triangle,
+                    );
+                }
+                let (column, row) = (i as u32 % divisions, i as u32 / divisions);
+                imageops::replace(
+                    &mut image,
+                    &part,
+                    (column * part_size) as i64,
+                    (row * part_size) as i64,
+                );
+                found = true;
+            }
+            if !found {
+                continue;
+            }
+            // internal tile rows count from the south, image rows from the top
+            let left = (x - bounds.min.x) * tile_size;
+            let top = (bounds.max.y - y) * tile_size;
+            imageops::replace(&mut stitched, &image, left as i64, top as i64);
+        }
+    }
+    Ok(stitched)
+}
+
+/// Loads a cached tile image, `None` if it isn't cached or can't be decoded.
+fn load_cached_image(path: &Path) -> Result<Option<RgbaImage>, TileFetchError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(TileFetchError::from_io(err)),
+    };
+    match image::load_from_memory(&bytes) {
+        Ok(image) => Ok(Some(image.to_rgba8())),
+        Err(err) => {
+            warn!("skipping cached tile {}: {}", path.display(), err);
+            Ok(None)
+        }
+    }
+}
+
+/// Event to delete all cached tiles on disk and reload the visible tiles, e.g. for a "clear cache" button.
+///
+/// Layers using a [`TileSource`] are reloaded as well, but their source is left untouched.
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct ClearTileCache;
+
+/// Cache directories currently being cleared, the tiles are reloaded once all are done.
+#[derive(Resource, Default)]
+pub(crate) struct PendingCacheClears(Vec<Task<()>>);
+
+pub(crate) fn clear_tile_cache(
+    _clear: On<ClearTileCache>,
+    fetcher: Res<TileFetcher>,
+    mut pending: ResMut<PendingCacheClears>,
+) {
+    let mut directories = fetcher
+        .layers
+        .iter()
+        .filter(|layer| layer.config.uses_disk_cache())
+        .map(|layer| layer.config.cache_directory.clone())
+        .collect::<Vec<_>>();
+    directories.sort();
+    directories.dedup();
+    info!("clearing tile cache in {:?}", directories);
+    pending.0.push(IoTaskPool::get().spawn(async move {
+        for directory in directories {
+            clear_directory(&directory);
+        }
+    }));
+}
+
+// removes the contents but keeps the directory itself, downloads finishing meanwhile may still add tiles
+fn clear_directory(directory: &Path) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!(
+                "failed to clear tile cache {}: {}",
+                directory.display(),
+                err
+            );
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let result = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        if let Err(err) = result
+            && err.kind() != std::io::ErrorKind::NotFound
+        {
+            warn!("failed to remove cached tiles {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// Despawns all tiles once the cache is cleared, the visible ones are respawned and downloaded again.
+pub(crate) fn reload_tiles_after_cache_clear(
+    mut commands: Commands,
+    mut pending: ResMut<PendingCacheClears>,
+    tiles: Query<Entity, With<Tile>>,
+) {
+    if pending.0.is_empty() {
+        return;
+    }
+    pending
+        .0
+        .retain_mut(|task| block_on(future::poll_once(task)).is_none());
+    if !pending.0.is_empty() {
+        return;
+    }
+    for entity in tiles.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(zoom: u8, x: u32, y: u32) -> TileMathTile {
+        TileMathTile { zoom, x, y }
+    }
+
+    /// Empty directory in the temp dir, unique per test and process.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "bevy_geo_tiles_test_{}_{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn prepared(config: TileFetchConfig) -> Arc<PreparedConfig> {
+        FetchLayer::new(config).unwrap().config
+    }
+
+    fn png_bytes() -> Vec<u8> {
+        encode_png(&RgbaImage::new(2, 2))
+    }
+
+    fn encode_png(image: &RgbaImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        bytes
+    }
+
+    /// Image of `size` x `size` pixels with a distinct color per pixel.
+    fn pixel_grid(size: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, y| grid_color(x, y))
+    }
+
+    fn grid_color(x: u32, y: u32) -> image::Rgba<u8> {
+        image::Rgba([x as u8 * 40, y as u8 * 40, 100, 255])
+    }
+
+    /// Center of the internal `tile` in lon/lat, to stitch just that tile.
+    fn tile_center(tile: TileMathTile) -> DVec2 {
+        let (south_west, north_east) = crate::coord_conversions::tile_to_lonlat_bbox(tile);
+        (south_west + north_east) / 2.0
+    }
+
+    #[test]
+    fn server_tile_y_of_berlin() {
+        // Berlin is tile 10/550/335 on OpenStreetMap (XYZ), 688 counted from the south (TMS)
+        let internal = DVec2::new(13.405, 52.52)
+            .lonlat_to_mercator()
+            .mercator_to_tile_coords(10);
+        assert_eq!(internal, UVec2::new(550, 688));
+        let internal = tile(10, internal.x, internal.y);
+        assert_eq!(server_tile(&internal, false, 0, false), tile(10, 550, 335));
+        assert_eq!(server_tile(&internal, true, 0, false), tile(10, 550, 688));
+        // the northernmost row is the first one on XYZ servers
+        assert_eq!(server_tile(&tile(3, 2, 7), false, 0, false), tile(3, 2, 0));
+        // WMS requests use the bounds of the internal tile
+        assert_eq!(server_tile(&internal, false, 0, true), internal);
+    }
+
+    #[test]
+    fn corrupt_cached_tile_is_removed_and_fetched_again() {
+        let dir = test_dir("corrupt_cache");
+        let config = prepared(TileFetchConfig {
+            cache_directory: dir.clone(),
+            cache_enabled: true,
+            ..default()
+        });
+        let tile = tile(3, 2, 5);
+        let path = config.cache_path(&tile);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let png = png_bytes();
+        let truncated = png[..png.len() - 4].to_vec();
+        for garbage in [b"<html>rate limited</html>".to_vec(), truncated] {
+            fs::write(&path, garbage).unwrap();
+            assert!(load_local_tile(&config, &tile).unwrap().is_none());
+            assert!(!path.exists());
+
+            // the download replaces it in the cache
+            let downloaded =
+                store_downloaded_tile(&config, &tile, png.clone(), Some("image/png".into()))
+                    .unwrap();
+            assert_eq!(downloaded.cached_path.as_deref(), Some(path.as_path()));
+            let cached = load_local_tile(&config, &tile).unwrap().unwrap();
+            assert!(cached.from_cache);
+            assert_eq!(cached.data, TileData::Encoded(png.clone()));
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn quadkey_matches_bing_examples() {
+        // examples of the Bing Maps tile system documentation
+        assert_eq!(quadkey(&tile(3, 3, 5)), "213");
+        assert_eq!(quadkey(&tile(1, 0, 0)), "0");
+        assert_eq!(quadkey(&tile(1, 1, 0)), "1");
+        assert_eq!(quadkey(&tile(1, 0, 1)), "2");
+        assert_eq!(quadkey(&tile(1, 1, 1)), "3");
+        // the whole world at zoom 0
+        assert_eq!(quadkey(&tile(0, 0, 0)), "");
+        // one digit per zoom level
+        assert_eq!(quadkey(&tile(18, 0, 0)), "0".repeat(18));
+    }
+
+    #[test]
+    fn negative_zoom_offset_maps_to_parent_tile_regions() {
+        // internal 5/13/22 is 5/13/9 on XYZ servers
+        let internal = tile(5, 13, 22);
+        assert_eq!(server_tile(&internal, false, -2, false), tile(3, 3, 2));
+        assert_eq!(server_tile(&internal, true, -2, false), tile(3, 3, 5));
+        // second column and row of the 4 x 4 parts, counted from the top for both y conventions
+        assert_eq!(server_tile_region(&internal, -2, false), Some((1, 1, 4)));
+        assert_eq!(
+            region_rect(UVec2::splat(256), (1, 1, 4)),
+            URect::new(64, 64, 128, 128)
+        );
+        assert_eq!(
+            region_rect(UVec2::splat(512), (3, 0, 4)),
+            URect::new(384, 0, 512, 128)
+        );
+        // the northernmost row is the top of the server image
+        assert_eq!(
+            server_tile_region(&tile(5, 0, 31), -2, false),
+            Some((0, 0, 4))
+        );
+        assert_eq!(
+            server_tile_region(&tile(5, 3, 28), -2, false),
+            Some((3, 3, 4))
+        );
+
+        // the offset is limited by zoom level 0
+        let shallow = tile(1, 1, 0);
+        assert_eq!(server_tile(&shallow, false, -2, false), tile(0, 0, 0));
+        assert_eq!(server_tile_region(&shallow, -2, false), Some((1, 1, 2)));
+
+        // a single server tile per tile without a negative offset and for WMS
+        assert_eq!(
+            server_subtiles(&tile(3, 3, 2), false, -2, false),
+            vec![tile(3, 3, 2)]
+        );
+        assert_eq!(server_tile_region(&internal, 0, false), None);
+        assert_eq!(server_tile_region(&internal, 1, false), None);
+        assert_eq!(server_tile_region(&internal, -2, true), None);
+    }
+
+    #[test]
+    fn positive_zoom_offset_combines_child_tiles() {
+        // internal 4/4/2 is 4/4/13 on XYZ servers
+        let internal = tile(4, 4, 2);
+        let request = server_tile(&internal, false, 1, false);
+        assert_eq!(request, tile(4, 4, 13));
+        // row by row from the top of the image
+        assert_eq!(
+            server_subtiles(&request, false, 1, false),
+            vec![
+                tile(5, 8, 26),
+                tile(5, 9, 26),
+                tile(5, 8, 27),
+                tile(5, 9, 27)
+            ]
+        );
+        // TMS rows count from the bottom, the top row has the larger y
+        let request = server_tile(&internal, true, 1, false);
+        assert_eq!(request, tile(4, 4, 2));
+        assert_eq!(
+            server_subtiles(&request, true, 1, false),
+            vec![tile(5, 8, 5), tile(5, 9, 5), tile(5, 8, 4), tile(5, 9, 4)]
+        );
+        assert_eq!(server_subtiles(&request, true, 2, false).len(), 16);
+
+        // coordinates stay within a u32
+        assert_eq!(zoom_in_levels(&tile(30, 0, 0), 3, false), 1);
+        assert_eq!(zoom_in_levels(&tile(31, 0, 0), 3, false), 0);
+        // WMS requests use the bounds of the internal tile
+        assert_eq!(server_subtiles(&internal, false, 1, true), vec![internal]);
+    }
+
+    #[test]
+    fn subtiles_are_combined_row_by_row() {
+        let colors = [(0, 0), (1, 0), (0, 1), (1, 1)];
+        let parts = colors
+            .iter()
+            .map(|&(x, y)| TileImagePayload {
+                data: TileData::Encoded(encode_png(&RgbaImage::from_pixel(2, 2, grid_color(x, y)))),
+                cached_path: None,
+                content_type: Some("image/png".into()),
+                from_cache: true,
+                downloaded: false,
+            })
+            .collect();
+        let combined = combine_subtiles(parts).unwrap();
+        assert!(combined.from_cache);
+        assert!(!combined.downloaded);
+        let TileData::Decoded(image) = &combined.data else {
+            panic!("expected the stitched pixels, got {:?}", combined.data);
+        };
+        assert_eq!(image.dimensions(), (4, 4));
+        for (x, y, pixel) in image.enumerate_pixels() {
+            assert_eq!(*pixel, grid_color(x / 2, y / 2), "pixel ({x}, {y})");
+        }
+
+        // the texture is built from the stitched pixels without encoding them again
+        let pixels = image.as_raw().clone();
+        let texture = build_image_from_data(combined.data, TileFilterMode::default()).unwrap();
+        assert_eq!(texture.size(), UVec2::new(4, 4));
+        assert_eq!(texture.data, Some(pixels));
+    }
+
+    #[test]
+    fn stitch_crops_parent_tiles_for_negative_zoom_offset() {
+        let dir = test_dir("stitch_negative_offset");
+        let config = TileFetchConfig {
+            cache_directory: dir.clone(),
+            zoom_offset: -2,
+            ..default()
+        };
+        // 5/13/22 shows the part (1, 1) of the server tile 3/3/2
+        let path = config.cache_path_for(&tile(3, 3, 2));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, encode_png(&pixel_grid(4))).unwrap();
+
+        let center = tile_center(tile(5, 13, 22));
+        let stitched = stitch_cached_tiles(&config, center, center, 5).unwrap();
+        assert_eq!(stitched.dimensions(), (256, 256));
+        assert_eq!(*stitched.get_pixel(128, 128), grid_color(1, 1));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stitch_combines_child_tiles_for_positive_zoom_offset() {
+        let dir = test_dir("stitch_positive_offset");
+        let config = TileFetchConfig {
+            cache_directory: dir.clone(),
+            zoom_offset: 1,
+            ..default()
+        };
+        // 4/4/2 is combined from the zoom 5 tiles, the bottom right one is missing
+        let subtiles = [(8, 26), (9, 26), (8, 27)];
+        for (x, y) in subtiles {
+            let path = config.cache_path_for(&tile(5, x, y));
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let image = RgbaImage::from_pixel(2, 2, grid_color(x - 8, y - 26));
+            fs::write(&path, encode_png(&image)).unwrap();
+        }
+
+        let center = tile_center(tile(4, 4, 2));
+        let stitched = stitch_cached_tiles(&config, center, center, 4).unwrap();
+        assert_eq!(stitched.dimensions(), (256, 256));
+        assert_eq!(*stitched.get_pixel(64, 64), grid_color(0, 0));
+        assert_eq!(*stitched.get_pixel(192, 64), grid_color(1, 0));
+        assert_eq!(*stitched.get_pixel(64, 192), grid_color(0, 1));
+        assert_eq!(*stitched.get_pixel(192, 192), image::Rgba([0, 0, 0, 0]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn default_user_agent_is_detected_for_osm_fallbacks() {
+        let osm = "https://tile.openstreetmap.org/{z}/{x}/{y}.png".to_string();
+        let other = "https://tiles.example.com/{z}/{x}/{y}.png".to_string();
+        let config = TileFetchConfig {
+            url_template: other.clone(),
+            fallback_sources: vec![osm.clone()],
+            ..default()
+        };
+        assert!(config.uses_default_osm_user_agent());
+        assert!(
+            TileFetchConfig {
+                url_template: osm,
+                fallback_sources: vec![other.clone()],
+                ..default()
+            }
+            .uses_default_osm_user_agent()
+        );
+        assert!(
+            !TileFetchConfig {
+                url_template: other.clone(),
+                fallback_sources: vec![other],
+                ..default()
+            }
+            .uses_default_osm_user_agent()
+        );
+
+        let mut custom = config.clone();
+        custom
+            .headers
+            .insert("User-Agent".to_string(), "my-app/1.0".to_string());
+        assert!(!custom.uses_default_osm_user_agent());
+    }
+
+    #[test]
+    fn tile_of_512_pixels_covers_four_tiles_of_256_pixels() {
+        // 512px tiles are shown one zoom level lower at the same camera scale
+        for zoom in 2..=18 {
+            let scale = crate::zoom_to_scale(zoom, 0, 256.0);
+            assert_eq!(crate::scale_to_zoom(scale, 0, 512.0), zoom - 1);
+        }
+        // and cover the ground of the four tiles of the zoom level above
+        let large = crate::tile_to_mercator_aabb(tile(4, 4, 2));
+        let small = crate::tile_to_mercator_aabb(tile(5, 8, 4))
+            .merge(&crate::tile_to_mercator_aabb(tile(5, 9, 5)));
+        assert_eq!((large.min, large.max), (small.min, small.max));
+
+        // WMS requests keep the same resolution
+        let wms = |tile_size| {
+            prepared(TileFetchConfig {
+                url_template: "https://example.com/wms?BBOX={bbox}&WIDTH={width}&HEIGHT={height}"
+                    .to_string(),
+                wms: true,
+                cache_enabled: false,
+                tile_size,
+                ..default()
+            })
+        };
+        assert!(
+            wms(512)
+                .format_url(0, &tile(4, 4, 2))
+                .ends_with("&WIDTH=512&HEIGHT=512")
+        );
+        assert!(
+            wms(256)
+                .format_url(0, &tile(5, 8, 4))
+                .ends_with("&WIDTH=256&HEIGHT=256")
+        );
+    }
+
+    #[test]
+    fn stitched_512_pixel_tiles_match_256_pixel_tiles_one_zoom_higher() {
+        let dir = test_dir("stitch_tile_size");
+        let large = TileFetchConfig {
+            cache_directory: dir.join("large"),
+            tile_size: 512,
+            ..default()
+        };
+        let small = TileFetchConfig {
+            cache_directory: dir.join("small"),
+            ..default()
+        };
+        // internal 4/4/2 is 4/4/13 on the server, covered by 5/8..9/26..27
+        let cache = |config: &TileFetchConfig, tile: TileMathTile, size: u32| {
+            let path = config.cache_path_for(&tile);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let image = RgbaImage::from_pixel(size, size, grid_color(1, 2));
+            fs::write(&path, encode_png(&image)).unwrap();
+        };
+        cache(&large, tile(4, 4, 13), 512);
+        for (x, y) in [(8, 26), (9, 26), (8, 27), (9, 27)] {
+            cache(&small, tile(5, x, y), 256);
+        }
+
+        let center = tile_center(tile(4, 4, 2));
+        let large_image = stitch_cached_tiles(&large, center, center, 4).unwrap();
+        // the centers of the south-west and north-east tiles of the same area
+        let (south_west, north_east) = (tile_center(tile(5, 8, 4)), tile_center(tile(5, 9, 5)));
+        let small_image = stitch_cached_tiles(&small, south_west, north_east, 5).unwrap();
+        assert_eq!(large_image.dimensions(), (512, 512));
+        assert_eq!(small_image.dimensions(), large_image.dimensions());
+        assert_eq!(large_image, small_image);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn directory_source_falls_back_to_download_for_missing_tiles() {
+        let dir = test_dir("directory_fallback");
+        let source = crate::DirectoryTileSource::new(dir.join("bundled"));
+        let bundled = tile(3, 4, 5);
+        let path = source.tile_path(&bundled);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, png_bytes()).unwrap();
+        let config = |source: crate::DirectoryTileSource| {
+            prepared(TileFetchConfig {
+                cache_directory: dir.join("cache"),
+                custom_source: Some(Arc::new(source)),
+                ..default()
+            })
+        };
+
+        // bundled tiles are loaded from the directory, missing ones are downloaded
+        let fallback = config(source.clone());
+        let loaded = load_local_tile(&fallback, &bundled).unwrap().unwrap();
+        assert_eq!(loaded.data, TileData::Encoded(png_bytes()));
+        assert!(!loaded.downloaded);
+        assert!(
+            load_local_tile(&fallback, &tile(3, 4, 6))
+                .unwrap()
+                .is_none()
+        );
+        assert!(fallback.downloads_tiles());
+
+        // without the fallback missing tiles fail instead
+        let offline = config(source.with_network_fallback(false));
+        assert!(load_local_tile(&offline, &bundled).unwrap().is_some());
+        assert!(matches!(
+            load_local_tile(&offline, &tile(3, 4, 6)),
+            Err(TileFetchError::NotFound)
+        ));
+        assert!(!offline.downloads_tiles());
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,252 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "mbtiles")]
+use std::sync::Mutex;
+
+use tilemath::Tile as TileMathTile;
+
+use crate::tile_fetcher::TileFetchError;
+
+/// A source of encoded tile images (PNG, JPEG, ...) used instead of downloading tiles over HTTP.
+///
+/// Tiles are requested in the y convention of the tile server, i.e. XYZ-style (origin top-left)
+/// unless `reverse_y` is set in the [`TileFetchConfig`](crate::TileFetchConfig).
+/// Implementations are called from the IO task pool and may block.
+pub trait TileSource: Send + Sync + fmt::Debug + 'static {
+    /// Returns the encoded image bytes of `tile`, or [`TileFetchError::NotFound`] if the source doesn't contain it.
+    fn load_tile(&self, tile: &TileMathTile) -> Result<Vec<u8>, TileFetchError>;
+
+    /// Whether tiles this source doesn't contain are downloaded from the `url_template` of the layer instead,
+    /// e.g. to bundle the tiles of a home region with the application and load the rest over the network.
+    /// Defaults to false, i.e. the source replaces the tile server.
+    fn network_fallback(&self) -> bool {
+        false
+    }
+}
+
+/// Tile source serving encoded tile images from memory, e.g. images bundled with the application
+/// or fixed tiles for deterministic tests without network access.
+///
+/// The tiles are keyed in the y convention of the tile server, see [`TileSource`].
+#[derive(Clone, Default)]
+pub struct StaticTileSource {
+    tiles: HashMap<TileMathTile, Vec<u8>>,
+    network_fallback: bool,
+}
+
+impl StaticTileSource {
+    pub fn new(tiles: HashMap<TileMathTile, Vec<u8>>) -> Self {
+        Self {
+            tiles,
+            network_fallback: false,
+        }
+    }
+
+    /// Downloads the tiles missing from this source from the tile server, see [`TileSource::network_fallback`].
+    pub fn with_network_fallback(mut self, network_fallback: bool) -> Self {
+        self.network_fallback = network_fallback;
+        self
+    }
+}
+
+impl fmt::Debug for StaticTileSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticTileSource")
+            .field("tiles", &self.tiles.len())
+            .field("network_fallback", &self.network_fallback)
+            .finish()
+    }
+}
+
+impl TileSource for StaticTileSource {
+    fn load_tile(&self, tile: &TileMathTile) -> Result<Vec<u8>, TileFetchError> {
+        self.tiles
+            .get(tile)
+            .cloned()
+            .ok_or(TileFetchError::NotFound)
+    }
+
+    fn network_fallback(&self) -> bool {
+        self.network_fallback
+    }
+}
+
+/// Tile source reading tiles from a `{z}/{x}/{y}.png` directory tree, e.g. tiles shipped next to the executable
+/// or in the Bevy asset folder (`FileAssetReader::get_base_path().join("assets/tiles")`).
+///
+/// Tiles missing from the directory are downloaded from the tile server, so a bundled region works offline
+/// while the rest of the map is loaded as usual. Use [`Self::with_network_fallback`] to only show the bundled tiles.
+/// The directory uses the y convention of the tile server, see [`TileSource`].
+#[derive(Clone, Debug)]
+pub struct DirectoryTileSource {
+    root: PathBuf,
+    extension: String,
+    network_fallback: bool,
+}
+
+impl DirectoryTileSource {
+    /// Reads tiles from `root/{z}/{x}/{y}.png`.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            extension: "png".to_string(),
+            network_fallback: true,
+        }
+    }
+
+    /// Sets the file extension of the tiles, e.g. `"jpg"` or `"pbf"` (defaults to `"png"`).
+    pub fn with_extension(mut self, extension: impl Into<String>) -> Self {
+        self.extension = extension.into();
+        self
+    }
+
+    /// Sets whether tiles missing from the directory are downloaded from the tile server (defaults to true).
+    pub fn with_network_fallback(mut self, network_fallback: bool) -> Self {
+        self.network_fallback = network_fallback;
+        self
+    }
+
+    /// Path of `tile` in the directory.
+    pub fn tile_path(&self, tile: &TileMathTile) -> PathBuf {
+        self.root
+            .join(tile.zoom.to_string())
+            .join(tile.x.to_string())
+            .join(format!("{}.{}", tile.y, self.extension))
+    }
+}
+
+impl TileSource for DirectoryTileSource {
+    fn load_tile(&self, tile: &TileMathTile) -> Result<Vec<u8>, TileFetchError> {
+        fs::read(self.tile_path(tile)).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => TileFetchError::NotFound,
+            _ => TileFetchError::Io(err.to_string()),
+        })
+    }
+
+    fn network_fallback(&self) -> bool {
+        self.network_fallback
+    }
+}
+
+/// Tile source reading raster tiles from an [MBTiles](https://github.com/mapbox/mbtiles-spec) SQLite file.
+///
+/// MBTiles stores tile rows in TMS order (origin bottom-left), the y coordinate is flipped internally,
+/// so keep `reverse_y` disabled when using this source.
+#[cfg(feature = "mbtiles")]
+pub struct MbTilesSource {
+    path: PathBuf,
+    connection: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "mbtiles")]
+impl MbTilesSource {
+    /// Opens the MBTiles file at `path` read-only.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, TileFetchError> {
+        let path = path.as_ref().to_path_buf();
+        let connection = rusqlite::Connection::open_with_flags(
+            &path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|err| TileFetchError::Io(err.to_string()))?;
+        Ok(Self {
+            path,
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+#[cfg(feature = "mbtiles")]
+impl fmt::Debug for MbTilesSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MbTilesSource")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+#[cfg(feature = "mbtiles")]
+impl TileSource for MbTilesSource {
+    fn load_tile(&self, tile: &TileMathTile) -> Result<Vec<u8>, TileFetchError> {
+        use rusqlite::OptionalExtension;
+
+        // MBTiles uses TMS rows, flip the XYZ y coordinate
+        let Some(row) = (1u32 << tile.zoom).checked_sub(tile.y + 1) else {
+            return Err(TileFetchError::NotFound);
+        };
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                rusqlite::params![tile.zoom, tile.x, row],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()
+            .map_err(|err| TileFetchError::Io(err.to_string()))?
+            .ok_or(TileFetchError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(zoom: u8, x: u32, y: u32) -> TileMathTile {
+        TileMathTile { zoom, x, y }
+    }
+
+    #[test]
+    fn directory_tile_path_layout() {
+        let source = DirectoryTileSource::new("tiles");
+        assert_eq!(
+            source.tile_path(&tile(12, 2200, 1343)),
+            Path::new("tiles").join("12").join("2200").join("1343.png")
+        );
+        let source = source.with_extension("jpg");
+        assert_eq!(
+            source.tile_path(&tile(0, 0, 0)),
+            Path::new("tiles").join("0").join("0").join("0.jpg")
+        );
+    }
+
+    #[test]
+    fn directory_source_maps_missing_files_to_not_found() {
+        let root = std::env::temp_dir().join(format!(
+            "bevy_geo_tiles_test_{}_directory_source",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        let source = DirectoryTileSource::new(&root);
+        let path = source.tile_path(&tile(3, 4, 5));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"tile").unwrap();
+
+        assert_eq!(source.load_tile(&tile(3, 4, 5)).unwrap(), b"tile");
+        // neither the file nor its directories exist
+        assert!(matches!(
+            source.load_tile(&tile(3, 4, 6)),
+            Err(TileFetchError::NotFound)
+        ));
+        assert!(matches!(
+            source.load_tile(&tile(9, 0, 0)),
+            Err(TileFetchError::NotFound)
+        ));
+        // other errors are reported as such, e.g. a directory in place of the file
+        fs::create_dir_all(source.tile_path(&tile(3, 4, 7))).unwrap();
+        assert!(matches!(
+            source.load_tile(&tile(3, 4, 7)),
+            Err(TileFetchError::Io(_))
+        ));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn directory_source_falls_back_to_network_by_default() {
+        let source = DirectoryTileSource::new("tiles");
+        assert!(source.network_fallback());
+        assert!(!source.with_network_fallback(false).network_fallback());
+    }
+}
@@ -0,0 +1,689 @@
+use std::{collections::HashMap, fmt::Display};
+
+use bevy::{math::DVec2, prelude::*};
+
+use crate::{
+    CurrentZoom, MercatorAabb2d,
+    shapes::{
+        polygon::GeoPolygon,
+        polyline::{GeoPolyline, GeoPolylineConfig},
+    },
+    tile_fetcher::TileFetchError,
+};
+
+// field numbers and constants of the vector tile specification, https://github.com/mapbox/vector-tile-spec
+const TILE_LAYERS: u64 = 3;
+const LAYER_NAME: u64 = 1;
+const LAYER_FEATURES: u64 = 2;
+const LAYER_EXTENT: u64 = 5;
+const FEATURE_TYPE: u64 = 3;
+const FEATURE_GEOMETRY: u64 = 4;
+const GEOMETRY_LINESTRING: u64 = 2;
+const GEOMETRY_POLYGON: u64 = 3;
+const COMMAND_MOVE_TO: u32 = 1;
+const COMMAND_LINE_TO: u32 = 2;
+const COMMAND_CLOSE_PATH: u32 = 7;
+const DEFAULT_EXTENT: u32 = 4096;
+
+pub(crate) fn vector_tiles_plugin(app: &mut App) {
+    app.add_systems(Update, update_vector_feature_visibility);
+}
+
+/// Styling of a tile layer serving [Mapbox Vector Tiles](https://github.com/mapbox/vector-tile-spec) (`.pbf`),
+/// set as [`TileFetchConfig::vector_style`](crate::TileFetchConfig::vector_style).
+///
+/// Polygons and lines of the tile are drawn as [`GeoPolygon`]s and [`GeoPolyline`]s, points are skipped.
+/// Tiles have to be served uncompressed (or with a `Content-Encoding` the HTTP client decodes).
+#[derive(Debug, Clone)]
+pub struct VectorTileStyle {
+    /// Colors of the layers of the vector tiles by name, e.g. `water` or `roads`
+    pub layer_colors: HashMap<String, Color>,
+    /// Color of the layers missing in `layer_colors`, `None` hides them
+    pub default_color: Option<Color>,
+    /// Width of lines in pixels at the zoom level of their tile
+    pub line_width: f32,
+}
+
+impl Default for VectorTileStyle {
+    fn default() -> Self {
+        Self {
+            layer_colors: HashMap::new(),
+            default_color: Some(Color::srgb(0.4, 0.4, 0.4)),
+            line_width: 1.5,
+        }
+    }
+}
+
+impl VectorTileStyle {
+    fn color_of(&self, layer: &str) -> Option<Color> {
+        self.layer_colors.get(layer).copied().or(self.default_color)
+    }
+}
+
+/// A feature of a vector tile in mercator coordinates
+#[derive(Debug)]
+pub(crate) struct VectorFeature {
+    color: Color,
+    geometry: VectorGeometry,
+}
+
+#[derive(Debug, PartialEq)]
+enum VectorGeometry {
+    Lines(Vec<Vec<DVec2>>),
+    /// outer ring and holes of each polygon
+    Polygons(Vec<(Vec<DVec2>, Vec<Vec<DVec2>>)>),
+}
+
+/// Links the shapes of a vector tile to the tile entity, they are despawned with it
+#[derive(Component, Debug)]
+#[relationship(relationship_target = VectorTileFeatures)]
+pub(crate) struct VectorFeatureOf(Entity);
+
+#[derive(Component, Debug)]
+#[relationship_target(relationship = VectorFeatureOf, linked_spawn)]
+pub(crate) struct VectorTileFeatures(Vec<Entity>);
+
+/// Zoom level of the tile a vector tile shape belongs to, only the current zoom level is shown
+#[derive(Component, Debug)]
+pub(crate) struct VectorFeatureZoom(u8);
+
+/// Decodes the features of a vector tile covering `bounds` that have a color in `style`.
+pub(crate) fn decode_vector_tile(
+    bytes: &[u8],
+    bounds: &MercatorAabb2d,
+    style: &VectorTileStyle,
+) -> Result<Vec<VectorFeature>, TileFetchError> {
+    let mut features = Vec::new();
+    let mut tile = ProtoReader::new(bytes);
+    while let Some((field, value)) = tile.next_field()? {
+        if let (TILE_LAYERS, Field::Bytes(layer)) = (field, value) {
+            decode_layer(layer, bounds, style, &mut features)?;
+        }
+    }
+    Ok(features)
+}
+
+fn decode_layer(
+    bytes: &[u8],
+    bounds: &MercatorAabb2d,
+    style: &VectorTileStyle,
+    features: &mut Vec<VectorFeature>,
+) -> Result<(), TileFetchError> {
+    let mut name = None;
+    let mut extent = DEFAULT_EXTENT;
+    let mut raw_features = Vec::new();
+    let mut layer = ProtoReader::new(bytes);
+    while let Some((field, value)) = layer.next_field()? {
+        match (field, value) {
+            (LAYER_NAME, Field::Bytes(bytes)) => {
+                name = Some(std::str::from_utf8(bytes).map_err(decode_error)?);
+            }
+            (LAYER_FEATURES, Field::Bytes(bytes)) => raw_features.push(bytes),
+            (LAYER_EXTENT, Field::Varint(value)) => extent = value as u32,
+            _ => {}
+        }
+    }
+    let Some(color) = name.and_then(|name| style.color_of(name)) else {
+        return Ok(());
+    };
+
+    // tile coordinates have their origin at the top left
+    let scale = bounds.size() / extent.max(1) as f64;
+    let to_mercator = |ring: Vec<IVec2>| -> Vec<DVec2> {
+        ring.into_iter()
+            .map(|p| {
+                DVec2::new(
+                    bounds.min.x + p.x as f64 * scale.x,
+                    bounds.max.y - p.y as f64 * scale.y,
+                )
+            })
+            .collect()
+    };
+
+    for feature in raw_features {
+        let mut geometry_type = 0;
+        let mut geometry = None;
+        let mut reader = ProtoReader::new(feature);
+        while let Some((field, value)) = reader.next_field()? {
+            match (field, value) {
+                (FEATURE_TYPE, Field::Varint(value)) => geometry_type = value,
+                (FEATURE_GEOMETRY, Field::Bytes(bytes)) => geometry = Some(bytes),
+                _ => {}
+            }
+        }
+        let Some(geometry) = geometry else {
+            continue;
+        };
+        let parts = decode_geometry(geometry)?;
+        let geometry = match geometry_type {
+            GEOMETRY_LINESTRING => {
+                VectorGeometry::Lines(parts.into_iter().map(to_mercator).collect())
+            }
+            GEOMETRY_POLYGON => VectorGeometry::Polygons(
+                group_polygon_rings(parts)
+                    .into_iter()
+                    .map(|(outer, holes)| {
+                        (
+                            to_mercator(outer),
+                            holes.into_iter().map(to_mercator).collect(),
+                        )
+                    })
+                    .collect(),
+            ),
+            // points and unknown geometries are not drawn
+            _ => continue,
+        };
+        features.push(VectorFeature { color, geometry });
+    }
+    Ok(())
+}
+
+/// Decodes the geometry commands of a feature into its parts, every `MoveTo` starts a new part.
+fn decode_geometry(bytes: &[u8]) -> Result<Vec<Vec<IVec2>>, TileFetchError> {
+    let mut reader = ProtoReader::new(bytes);
+    let mut parts: Vec<Vec<IVec2>> = Vec::new();
+    let mut cursor = IVec2::ZERO;
+    while !reader.is_empty() {
+        let command = reader.varint()? as u32;
+        let (id, count) = (command & 0x7, command >> 3);
+        match id {
+            COMMAND_MOVE_TO | COMMAND_LINE_TO => {
+                for _ in 0..count {
+                    let delta = IVec2::new(zigzag(reader.varint()?), zigzag(reader.varint()?));
+                    cursor = cursor.wrapping_add(delta);
+                    if id == COMMAND_MOVE_TO {
+                        parts.push(Vec::new());
+                    }
+                    parts
+                        .last_mut()
+                        .ok_or_else(|| decode_error("LineTo without MoveTo"))?
+                        .push(cursor);
+                }
+            }
+            // rings are closed implicitly by the polygon tessellation
+            COMMAND_CLOSE_PATH => {}
+            other => {
+                return Err(decode_error(format_args!(
+                    "unknown geometry command {other}"
+                )));
+            }
+        }
+    }
+    Ok(parts)
+}
+
+fn zigzag(value: u64) -> i32 {
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+/// Splits polygon rings into outer rings and the holes following them, using the winding order of the specification.
+fn group_polygon_rings(rings: Vec<Vec<IVec2>>) -> Vec<(Vec<IVec2>, Vec<Vec<IVec2>>)> {
+    let mut polygons: Vec<(Vec<IVec2>, Vec<Vec<IVec2>>)> = Vec::new();
+    for ring in rings {
+        // positive for clockwise rings in tile coordinates (y down), which are outer rings
+        // i128 as the cross products of coordinates near the i32 limits overflow i64
+        let area: i128 = ring
+            .iter()
+            .zip(ring.iter().cycle().skip(1))
+            .map(|(a, b)| a.x as i128 * b.y as i128 - b.x as i128 * a.y as i128)
+            .sum();
+        match polygons.last_mut() {
+            _ if area == 0 => {}
+            Some((_, holes)) if area < 0 => holes.push(ring),
+            _ => polygons.push((ring, Vec::new())),
+        }
+    }
+    polygons
+}
+
+fn decode_error(err: impl Display) -> TileFetchError {
+    TileFetchError::Decode(format!("invalid vector tile: {err}"))
+}
+
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    /// fixed size values, not used by vector tiles
+    Fixed,
+}
+
+/// Minimal protobuf reader for the parts of the vector tile format used for rendering
+struct ProtoReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn varint(&mut self) -> Result<u64, TileFetchError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self
+                .data
+                .split_first()
+                .ok_or_else(|| decode_error("unexpected end of data"))?;
+            self.data = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(decode_error("varint too long"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TileFetchError> {
+        if len > self.data.len() {
+            return Err(decode_error("unexpected end of data"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    /// Field number and value of the next field, `None` at the end of the message.
+    fn next_field(&mut self) -> Result<Option<(u64, Field<'a>)>, TileFetchError> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => Field::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Field::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Field::Fixed
+            }
+            wire_type => {
+                return Err(decode_error(format_args!(
+                    "unsupported wire type {wire_type}"
+                )));
+            }
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+/// Spawns the shapes of a vector tile, replacing the shapes previously spawned for `tile`.
+pub(crate) fn spawn_vector_features(
+    commands: &mut Commands,
+    tile: Entity,
+    zoom: u8,
+    features: Vec<VectorFeature>,
+    style: &VectorTileStyle,
+    tile_size: f32,
+) {
+    commands
+        .entity(tile)
+        .despawn_related::<VectorTileFeatures>();
+    // lines keep roughly the same width on screen while their zoom level is shown
+    let meters_per_pixel = 2.0 * crate::coord_conversions::WEB_MERCATOR_EXTENT
+        / (tile_size as f64 * (1u64 << zoom) as f64);
+    let line_width = style.line_width * meters_per_pixel as f32;
+    for VectorFeature { color, geometry } in features {
+        match geometry {
+            VectorGeometry::Polygons(polygons) => {
+                for (points, holes) in polygons {
+                    commands.spawn((
+                        GeoPolygon {
+                            points,
+                            holes,
+                            fill_color: Some(color),
+                            ..default()
+                        },
+                        VectorFeatureOf(tile),
+                        VectorFeatureZoom(zoom),
+                        Visibility::Hidden,
+                    ));
+                }
+            }
+            VectorGeometry::Lines(lines) => {
+                for points in lines.into_iter().filter(|line| line.len() >= 2) {
+                    commands.spawn((
+                        GeoPolyline { points },
+                        GeoPolylineConfig::new(line_width, color),
+                        VectorFeatureOf(tile),
+                        VectorFeatureZoom(zoom),
+                        Visibility::Hidden,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+// the shapes of all loaded zoom levels would overlap, unlike raster tiles they are not hidden by their zoom level
+fn update_vector_feature_visibility(
+    current_zoom: Res<CurrentZoom>,
+    mut features: Query<(Ref<VectorFeatureZoom>, &mut Visibility)>,
+) {
+    for (zoom, mut visibility) in features.iter_mut() {
+        if current_zoom.is_changed() || zoom.is_added() {
+            visibility.set_if_neq(if zoom.0 == current_zoom.current_zoom() {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // encoding helpers building vector tiles for the decoder
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn varint_field(field: u64, value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        varint(field << 3, &mut out);
+        varint(value, &mut out);
+        out
+    }
+
+    fn bytes_field(field: u64, bytes: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        varint((field << 3) | 2, &mut out);
+        varint(bytes.len() as u64, &mut out);
+        out.extend_from_slice(bytes);
+        out
+    }
+
+    fn command(id: u32, count: u32) -> u64 {
+        u64::from(id | (count << 3))
+    }
+
+    fn zigzag_encode(value: i32) -> u64 {
+        ((value << 1) ^ (value >> 31)) as u32 as u64
+    }
+
+    /// Geometry of rings or lines given in absolute tile coordinates.
+    fn geometry(parts: &[&[IVec2]], close: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut cursor = IVec2::ZERO;
+        let mut push_point = |point: IVec2, out: &mut Vec<u8>| {
+            let delta = point - cursor;
+            varint(zigzag_encode(delta.x), out);
+            varint(zigzag_encode(delta.y), out);
+            cursor = point;
+        };
+        for part in parts {
+            varint(command(COMMAND_MOVE_TO, 1), &mut out);
+            push_point(part[0], &mut out);
+            varint(command(COMMAND_LINE_TO, part.len() as u32 - 1), &mut out);
+            for point in &part[1..] {
+                push_point(*point, &mut out);
+            }
+            if close {
+                varint(command(COMMAND_CLOSE_PATH, 1), &mut out);
+            }
+        }
+        out
+    }
+
+    fn feature(geometry_type: u64, geometry: &[u8]) -> Vec<u8> {
+        let mut out = varint_field(FEATURE_TYPE, geometry_type);
+        out.extend(bytes_field(FEATURE_GEOMETRY, geometry));
+        out
+    }
+
+    fn layer(name: &str, extent: u64, features: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = bytes_field(LAYER_NAME, name.as_bytes());
+        for feature in features {
+            out.extend(bytes_field(LAYER_FEATURES, feature));
+        }
+        out.extend(varint_field(LAYER_EXTENT, extent));
+        out
+    }
+
+    fn points(coords: &[(i32, i32)]) -> Vec<IVec2> {
+        coords.iter().map(|&(x, y)| IVec2::new(x, y)).collect()
+    }
+
+    // one tile unit per mercator unit, with y flipped
+    fn bounds() -> MercatorAabb2d {
+        MercatorAabb2d::new(DVec2::ZERO, DVec2::splat(4096.0))
+    }
+
+    fn to_mercator(ring: &[IVec2]) -> Vec<DVec2> {
+        ring.iter()
+            .map(|p| DVec2::new(p.x as f64, 4096.0 - p.y as f64))
+            .collect()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Vec<VectorFeature>, TileFetchError> {
+        decode_vector_tile(bytes, &bounds(), &VectorTileStyle::default())
+    }
+
+    fn fixture() -> Vec<u8> {
+        // clockwise in tile coordinates (y down) are outer rings, counter-clockwise ones holes
+        let outer = points(&[(0, 0), (100, 0), (100, 100), (0, 100)]);
+        let hole = points(&[(20, 20), (20, 80), (80, 80), (80, 20)]);
+        let second_outer = points(&[(200, 200), (300, 200), (300, 300)]);
+        let line = points(&[(10, 4000), (50, 3000), (4096, 0)]);
+        let mut point_geometry = Vec::new();
+        varint(command(COMMAND_MOVE_TO, 1), &mut point_geometry);
+        varint(zigzag_encode(5), &mut point_geometry);
+        varint(zigzag_encode(7), &mut point_geometry);
+
+        let water = layer(
+            "water",
+            4096,
+            &[
+                feature(1, &point_geometry),
+                feature(
+                    GEOMETRY_POLYGON,
+                    &geometry(&[&outer, &hole, &second_outer], true),
+                ),
+                feature(GEOMETRY_LINESTRING, &geometry(&[&line], false)),
+            ],
+        );
+        let mut tile = bytes_field(TILE_LAYERS, &water);
+        // unknown fields of every wire type are skipped
+        tile.extend(varint_field(10, 42));
+        tile.extend([(11 << 3) | 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        tile.extend([(12 << 3) | 5, 0, 0, 0, 0]);
+        tile.extend(bytes_field(13, b"ignored"));
+        tile
+    }
+
+    #[test]
+    fn decodes_lines_and_polygons_with_holes() {
+        let features = decode(&fixture()).unwrap();
+        // the point is skipped
+        assert_eq!(features.len(), 2);
+        let color = VectorTileStyle::default().default_color.unwrap();
+        assert!(features.iter().all(|feature| feature.color == color));
+
+        let outer = points(&[(0, 0), (100, 0), (100, 100), (0, 100)]);
+        let hole = points(&[(20, 20), (20, 80), (80, 80), (80, 20)]);
+        let second_outer = points(&[(200, 200), (300, 200), (300, 300)]);
+        assert_eq!(
+            features[0].geometry,
+            VectorGeometry::Polygons(vec![
+                (to_mercator(&outer), vec![to_mercator(&hole)]),
+                (to_mercator(&second_outer), Vec::new()),
+            ])
+        );
+        let line = points(&[(10, 4000), (50, 3000), (4096, 0)]);
+        assert_eq!(
+            features[1].geometry,
+            VectorGeometry::Lines(vec![to_mercator(&line)])
+        );
+    }
+
+    #[test]
+    fn polygon_rings_are_grouped_by_winding() {
+        let outer = points(&[(0, 0), (10, 0), (10, 10), (0, 10)]);
+        let hole = points(&[(2, 2), (2, 8), (8, 8), (8, 2)]);
+        let degenerate = points(&[(0, 0), (5, 5), (10, 10)]);
+        let polygons = group_polygon_rings(vec![
+            outer.clone(),
+            hole.clone(),
+            degenerate,
+            outer.clone(),
+            hole.clone(),
+            hole.clone(),
+        ]);
+        assert_eq!(
+            polygons,
+            vec![
+                (outer.clone(), vec![hole.clone()]),
+                (outer, vec![hole.clone(), hole]),
+            ]
+        );
+
+        // coordinates at the limits of i32 don't overflow the area
+        let huge = points(&[
+            (i32::MIN, i32::MIN),
+            (i32::MAX, i32::MIN),
+            (i32::MAX, i32::MAX),
+            (i32::MIN, i32::MAX),
+        ]);
+        assert_eq!(
+            group_polygon_rings(vec![huge.clone()]),
+            vec![(huge, Vec::new())]
+        );
+    }
+
+    #[test]
+    fn layers_are_colored_by_style() {
+        let line = geometry(&[&points(&[(0, 0), (1, 1)])], false);
+        let mut tile = bytes_field(
+            TILE_LAYERS,
+            &layer("roads", 4096, &[feature(GEOMETRY_LINESTRING, &line)]),
+        );
+        tile.extend(bytes_field(
+            TILE_LAYERS,
+            &layer("buildings", 4096, &[feature(GEOMETRY_LINESTRING, &line)]),
+        ));
+        let style = VectorTileStyle {
+            layer_colors: HashMap::from([("roads".to_string(), Color::WHITE)]),
+            default_color: None,
+            ..default()
+        };
+        let features = decode_vector_tile(&tile, &bounds(), &style).unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].color, Color::WHITE);
+    }
+
+    #[test]
+    fn extent_scales_to_bounds() {
+        let line = geometry(&[&points(&[(0, 0), (512, 256)])], false);
+        let tile = bytes_field(
+            TILE_LAYERS,
+            &layer("roads", 512, &[feature(GEOMETRY_LINESTRING, &line)]),
+        );
+        let features = decode(&tile).unwrap();
+        assert_eq!(
+            features[0].geometry,
+            VectorGeometry::Lines(vec![vec![
+                DVec2::new(0.0, 4096.0),
+                DVec2::new(4096.0, 2048.0),
+            ]])
+        );
+        // an extent of zero doesn't divide by zero
+        let tile = bytes_field(
+            TILE_LAYERS,
+            &layer("roads", 0, &[feature(GEOMETRY_LINESTRING, &line)]),
+        );
+        assert!(decode(&tile).is_ok());
+    }
+
+    #[test]
+    fn invalid_varints_are_errors() {
+        // ends within a varint
+        assert!(decode(&[0x80]).is_err());
+        assert!(decode(&[(TILE_LAYERS << 3) as u8 | 2, 0xff]).is_err());
+        // more than ten bytes
+        assert!(decode(&[0xff; 11]).is_err());
+        let mut reader = ProtoReader::new(&[0xff; 10]);
+        assert!(reader.varint().is_err());
+    }
+
+    #[test]
+    fn invalid_lengths_are_errors() {
+        // a length past the end of the data
+        assert!(decode(&[(TILE_LAYERS << 3) as u8 | 2, 100, 1, 2]).is_err());
+        // a length that doesn't fit into memory
+        let mut tile = vec![(TILE_LAYERS << 3) as u8 | 2];
+        varint(u64::MAX, &mut tile);
+        assert!(decode(&tile).is_err());
+        // truncated fixed size values
+        assert!(decode(&[(11 << 3) | 1, 0, 0, 0]).is_err());
+        assert!(decode(&[(12 << 3) | 5, 0]).is_err());
+        // a truncated layer
+        let mut tile = bytes_field(TILE_LAYERS, &bytes_field(LAYER_NAME, b"water"));
+        tile[1] -= 1;
+        assert!(decode(&tile).is_err());
+    }
+
+    #[test]
+    fn unknown_wire_types_are_errors() {
+        for wire_type in [3, 4, 6, 7] {
+            assert!(decode(&[(TILE_LAYERS << 3) as u8 | wire_type, 0]).is_err());
+            let tile = bytes_field(TILE_LAYERS, &[(LAYER_NAME << 3) as u8 | wire_type]);
+            assert!(decode(&tile).is_err());
+        }
+    }
+
+    #[test]
+    fn invalid_geometries_are_errors() {
+        let decode_geometry = |geometry: &[u8]| {
+            decode(&bytes_field(
+                TILE_LAYERS,
+                &layer("water", 4096, &[feature(GEOMETRY_LINESTRING, geometry)]),
+            ))
+        };
+        // LineTo before the first MoveTo
+        let mut geometry = Vec::new();
+        varint(command(COMMAND_LINE_TO, 1), &mut geometry);
+        geometry.extend([2, 2]);
+        assert!(decode_geometry(&geometry).is_err());
+        // unknown command
+        assert!(decode_geometry(&[command(3, 1) as u8, 2, 2]).is_err());
+        // missing parameters, also with a count far larger than the data
+        assert!(decode_geometry(&[command(COMMAND_MOVE_TO, 1) as u8, 2]).is_err());
+        let mut geometry = Vec::new();
+        varint(command(COMMAND_MOVE_TO, u32::MAX >> 3), &mut geometry);
+        assert!(decode_geometry(&geometry).is_err());
+        // invalid UTF-8 as layer name
+        let tile = bytes_field(TILE_LAYERS, &bytes_field(LAYER_NAME, &[0xff, 0xfe]));
+        assert!(decode(&tile).is_err());
+    }
+
+    #[test]
+    fn corrupted_tiles_do_not_panic() {
+        let tile = fixture();
+        for len in 0..tile.len() {
+            let _ = decode(&tile[..len]);
+        }
+        for index in 0..tile.len() {
+            for value in [0x00, 0x7f, 0x80, 0xff] {
+                let mut corrupted = tile.clone();
+                corrupted[index] = value;
+                let _ = decode(&corrupted);
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+
+use crate::{
+    LocalOrigin, MainCam, MercatorCoords, Tile, ViewportConv,
+    coord_conversions::{WEB_MERCATOR_EXTENT, nearest_world_copy, split_world_copies},
+};
+
+/// Copy of its parent drawn the given number of world widths to the east (positive) or west (negative).
+///
+/// Markers and shapes are placed on the world copy closest to the [`LocalOrigin`]. When other world copies are visible,
+/// e.g. zoomed out or near the antimeridian, a child with this component is spawned for each of them, so they repeat
+/// like the tiles do. The child gets the [`Sprite`] and [`Anchor`](bevy::sprite::Anchor) of its parent,
+/// and with the `shapes` feature its [`Mesh2d`] and `ColorMaterial`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[component(immutable)]
+pub struct WorldCopyInstance(pub i32);
+
+type Sources = (
+    With<MercatorCoords>,
+    With<Visibility>,
+    Without<Tile>,
+    Without<WorldCopyInstance>,
+);
+
+/// Spawns, moves and despawns the [`WorldCopyInstance`]s of visible entities for the visible world copies.
+///
+/// Only entities that moved are updated, unless the visible world copies or the [`LocalOrigin`] changed.
+pub(crate) fn sync_world_copy_instances(
+    mut commands: Commands,
+    view: ViewportConv<MainCam>,
+    origin: Res<LocalOrigin>,
+    mut visible_worlds: Local<Vec<i32>>,
+    sources: Query<(Entity, &MercatorCoords, Ref<Transform>, Option<&Children>), Sources>,
+    changed: Query<
+        Entity,
+        (
+            Sources,
+            Or<(
+                Changed<MercatorCoords>,
+                Changed<Transform>,
+                Added<Visibility>,
+            )>,
+        ),
+    >,
+    mut instances: Query<(&WorldCopyInstance, &mut Transform), Without<MercatorCoords>>,
+    mut removed_coords: RemovedComponents<MercatorCoords>,
+    mut removed_visibility: RemovedComponents<Visibility>,
+    children: Query<&Children>,
+) {
+    // copies of entities that lost their coordinates or visibility
+    for entity in removed_coords.read().chain(removed_visibility.read()) {
+        for child in children.get(entity).into_iter().flatten() {
+            if instances.contains(*child) {
+                commands.entity(*child).despawn();
+            }
+        }
+    }
+    let Ok(bbox) = view.visible_mercator_aabb() else {
+        return;
+    };
+    let worlds = split_world_copies(&bbox)
+        .into_iter()
+        .map(|(world, _)| world)
+        .collect::<Vec<_>>();
+    // e.g. panned across the antimeridian or zoomed out
+    let update_all = *visible_worlds != worlds || origin.is_changed();
+    let entities = if update_all {
+        sources
+            .iter()
+            .map(|(entity, ..)| entity)
+            .collect::<Vec<_>>()
+    } else {
+        changed.iter().collect()
+    };
+    let world_width = 2.0 * WEB_MERCATOR_EXTENT;
+    for (entity, coords, transform, entity_children) in sources.iter_many(&entities) {
+        // the same world copy the entity is placed on by the coordinate sync
+        let x = nearest_world_copy(coords.0, origin.mercator_origin().x).x;
+        let own = ((x + WEB_MERCATOR_EXTENT) / world_width).floor() as i32;
+        let mut missing = worlds
+            .iter()
+            .map(|world| world - own)
+            .filter(|shift| *shift != 0)
+            .collect::<Vec<_>>();
+        let offset = |shift: i32| {
+            // children are scaled and rotated with the entity, e.g. by `KeepDisplaySize`
+            let offset = Vec3::new((shift as f64 * world_width) as f32, 0.0, 0.0);
+            transform.rotation.inverse() * (offset / transform.scale)
+        };
+        for child in entity_children.into_iter().flatten() {
+            let Ok((instance, mut child_transform)) = instances.get_mut(*child) else {
+                continue;
+            };
+            let Some(index) = missing.iter().position(|shift| *shift == instance.0) else {
+                commands.entity(*child).despawn();
+                continue;
+            };
+            missing.swap_remove(index);
+            if transform.is_changed() {
+                child_transform.translation = offset(instance.0);
+            }
+        }
+        for shift in missing {
+            commands.spawn((
+                WorldCopyInstance(shift),
+                Transform::from_translation(offset(shift)),
+                Visibility::Inherited,
+                ChildOf(entity),
+            ));
+        }
+    }
+    *visible_worlds = worlds;
+}
+
+/// Keeps the component `C` of the [`WorldCopyInstance`]s equal to the one of their parent.
+pub(crate) fn copy_to_world_copies<C: Component + Clone>(
+    mut commands: Commands,
+    changed: Query<(&C, &Children), (Changed<C>, Without<WorldCopyInstance>)>,
+    added: Query<(Entity, &ChildOf), Added<WorldCopyInstance>>,
+    sources: Query<&C, Without<WorldCopyInstance>>,
+    instances: Query<(), With<WorldCopyInstance>>,
+    children: Query<&Children>,
+    mut removed: RemovedComponents<C>,
+) {
+    for (component, entity_children) in &changed {
+        for child in entity_children
+            .iter()
+            .filter(|child| instances.contains(*child))
+        {
+            commands.entity(child).insert(component.clone());
+        }
+    }
+    for (entity, child_of) in &added {
+        if let Ok(component) = sources.get(child_of.parent()) {
+            commands.entity(entity).insert(component.clone());
+        }
+    }
+    for entity in removed.read() {
+        let Ok(entity_children) = children.get(entity) else {
+            continue;
+        };
+        for child in entity_children
+            .iter()
+            .filter(|child| instances.contains(*child))
+        {
+            commands.entity(child).try_remove::<C>();
+        }
+    }
+}